@@ -0,0 +1,287 @@
+use std::{
+    fs,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+    sync::{Once, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use tracing::{debug, info, warn};
+
+use crate::fetch_manager::get_cache_path;
+
+/// Whether the cache is used at all, set from `--no-cache`.
+pub static ENABLED: OnceLock<bool> = OnceLock::new();
+/// Max size of the cache in bytes, set from `--cache-size`.
+pub static MAX_SIZE: OnceLock<u64> = OnceLock::new();
+
+static EVICT: Once = Once::new();
+
+fn enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(true)
+}
+
+fn thumbnails_dir() -> PathBuf {
+    get_cache_path().join("thumbnails")
+}
+
+fn remote_dir() -> PathBuf {
+    get_cache_path().join("remote")
+}
+
+fn math_dir() -> PathBuf {
+    get_cache_path().join("math")
+}
+
+fn hash(key: &str) -> String {
+    format!("{:x}", md5::compute(key.as_bytes()))
+}
+
+fn file_uri(path: &Path) -> Option<String> {
+    let abs = fs::canonicalize(path).ok()?;
+    let abs = abs.display().to_string();
+    let abs = abs.strip_prefix(r"\\?\").unwrap_or(&abs).replace('\\', "/");
+    if abs.starts_with('/') {
+        Some(format!("file://{abs}"))
+    } else {
+        Some(format!("file:///{abs}"))
+    }
+}
+
+// (uri, mtime, size) of a file, what a thumbnail is checked against
+fn file_stamp(path: &Path) -> Option<(String, u64, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    let mtime = meta
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some((file_uri(path)?, mtime, meta.len()))
+}
+
+// marks an entry as recently used, eviction removes the oldest first
+fn touch(path: &Path) {
+    if let Ok(f) = fs::File::options().write(true).open(path) {
+        let _ = f.set_modified(SystemTime::now());
+    }
+}
+
+/// Loads the cached thumbnail of `path`, if it is still valid for the file.
+///
+/// thumbnails follow the freedesktop thumbnail spec, a png named after the md5 of the file uri
+/// with `Thumb::URI`, `Thumb::MTime` and `Thumb::Size`. since the sizes aren't the spec ones
+/// they are kept in a folder per `size` (e.g. `256x128`) under mcat's own cache.
+pub fn load_thumbnail(path: &Path, size: &str) -> Option<DynamicImage> {
+    if !enabled() {
+        return None;
+    }
+    let (uri, mtime, len) = file_stamp(path)?;
+    let thumb_path = thumbnails_dir()
+        .join(size)
+        .join(format!("{}.png", hash(&uri)));
+    let bytes = fs::read(&thumb_path).ok()?;
+
+    let reader = png::Decoder::new(Cursor::new(&bytes)).read_info().ok()?;
+    let text = &reader.info().uncompressed_latin1_text;
+    let get = |key: &str| {
+        text.iter()
+            .find(|t| t.keyword == key)
+            .map(|t| t.text.as_str())
+    };
+    let mtime = mtime.to_string();
+    let len = len.to_string();
+    if get("Thumb::URI") != Some(uri.as_str())
+        || get("Thumb::MTime") != Some(mtime.as_str())
+        || get("Thumb::Size") != Some(len.as_str())
+    {
+        debug!(path = %path.display(), "stale thumbnail");
+        return None;
+    }
+
+    let img = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png).ok()?;
+    touch(&thumb_path);
+    debug!(path = %path.display(), size, "using cached thumbnail");
+    Some(img)
+}
+
+/// Saves the thumbnail of `path`, see [`load_thumbnail`].
+pub fn store_thumbnail(path: &Path, size: &str, img: &DynamicImage) {
+    if !enabled() {
+        return;
+    }
+    let Some((uri, mtime, len)) = file_stamp(path) else {
+        return;
+    };
+    let dir = thumbnails_dir().join(size);
+    let thumb_path = dir.join(format!("{}.png", hash(&uri)));
+    let result = (|| -> Result<()> {
+        fs::create_dir_all(&dir)?;
+        let img = img.to_rgba8();
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, img.width(), img.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.add_text_chunk("Thumb::URI".to_owned(), uri)?;
+        encoder.add_text_chunk("Thumb::MTime".to_owned(), mtime.to_string())?;
+        encoder.add_text_chunk("Thumb::Size".to_owned(), len.to_string())?;
+        encoder.add_text_chunk("Software".to_owned(), "mcat".to_owned())?;
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(img.as_raw())?;
+        writer.finish()?;
+
+        // written to a temp file first, so a concurrent mcat never reads half a png
+        let mut tmp = tempfile::NamedTempFile::new_in(&dir)?;
+        tmp.write_all(&png)?;
+        tmp.persist(&thumb_path)?;
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        warn!(path = %path.display(), error = %e, "failed to cache thumbnail");
+    }
+    evict_once();
+}
+
+/// A fetched url, along with what's needed to revalidate it.
+pub struct RemoteEntry {
+    pub bytes: Vec<u8>,
+    pub mime: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Loads the cached response for `url`, it should be revalidated with the etag / last modified.
+pub fn load_remote(url: &str) -> Option<RemoteEntry> {
+    if !enabled() {
+        return None;
+    }
+    let base = remote_dir().join(hash(url));
+    let meta = fs::read(base.with_extension("json")).ok()?;
+    let meta: serde_json::Value = serde_json::from_slice(&meta).ok()?;
+    if meta.get("url").and_then(|v| v.as_str()) != Some(url) {
+        return None;
+    }
+    let bytes = fs::read(base.with_extension("bin")).ok()?;
+    touch(&base.with_extension("bin"));
+
+    let field = |key: &str| meta.get(key).and_then(|v| v.as_str()).map(str::to_owned);
+    Some(RemoteEntry {
+        bytes,
+        mime: field("mime"),
+        etag: field("etag"),
+        last_modified: field("last_modified"),
+    })
+}
+
+/// Caches the response for `url`, responses without an etag or last modified are skipped
+/// since there's no way to revalidate them.
+pub fn store_remote(url: &str, entry: &RemoteEntry) {
+    if !enabled() || (entry.etag.is_none() && entry.last_modified.is_none()) {
+        return;
+    }
+    let dir = remote_dir();
+    let base = dir.join(hash(url));
+    let meta = serde_json::json!({
+        "url": url,
+        "mime": entry.mime,
+        "etag": entry.etag,
+        "last_modified": entry.last_modified,
+    });
+    let result = (|| -> Result<()> {
+        fs::create_dir_all(&dir)?;
+        fs::write(base.with_extension("bin"), &entry.bytes)?;
+        fs::write(base.with_extension("json"), meta.to_string())?;
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        warn!(url, error = %e, "failed to cache response");
+    }
+    evict_once();
+}
+
+/// Loads a typeset formula, `key` covers everything that went into it (source, colour, zoom).
+pub fn load_math(key: &str) -> Option<Vec<u8>> {
+    if !enabled() {
+        return None;
+    }
+    let path = math_dir().join(format!("{}.svg", hash(key)));
+    let svg = fs::read(&path).ok()?;
+    touch(&path);
+    Some(svg)
+}
+
+/// Caches a typeset formula, latex is slow enough to be worth skipping on every view.
+pub fn store_math(key: &str, svg: &[u8]) {
+    if !enabled() {
+        return;
+    }
+    let dir = math_dir();
+    let result = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(dir.join(format!("{}.svg", hash(key))), svg));
+
+    if let Err(e) = result {
+        warn!(error = %e, "failed to cache typeset math");
+    }
+    evict_once();
+}
+
+// runs on the first write of every run, so the cache stays bounded across runs
+fn evict_once() {
+    EVICT.call_once(|| {
+        let max = MAX_SIZE.get().copied().unwrap_or(256 * 1024 * 1024);
+        if let Err(e) = evict(max) {
+            warn!(error = %e, "cache eviction failed");
+        }
+    });
+}
+
+/// Removes the least recently used entries until the cache fits in `max` bytes.
+fn evict(max: u64) -> Result<()> {
+    let mut entries = Vec::new();
+    for dir in [thumbnails_dir(), remote_dir(), math_dir()] {
+        collect_entries(&dir, &mut entries)?;
+    }
+    let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+    if total <= max {
+        return Ok(());
+    }
+
+    entries.sort_by_key(|(_, _, modified)| *modified);
+    let mut removed = 0;
+    for (path, len, _) in entries {
+        if total <= max {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total -= len;
+            removed += 1;
+        }
+    }
+    info!(removed, total, max, "evicted cache entries");
+    Ok(())
+}
+
+fn collect_entries(dir: &Path, out: &mut Vec<(PathBuf, u64, SystemTime)>) -> Result<()> {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in read_dir.flatten() {
+        let meta = entry
+            .metadata()
+            .with_context(|| format!("failed to stat {}", entry.path().display()))?;
+        if meta.is_dir() {
+            collect_entries(&entry.path(), out)?;
+        } else {
+            out.push((
+                entry.path(),
+                meta.len(),
+                meta.modified().unwrap_or(UNIX_EPOCH),
+            ));
+        }
+    }
+    Ok(())
+}
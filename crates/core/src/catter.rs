@@ -4,10 +4,10 @@ use crossterm::{
     tty::IsTty,
 };
 use image::DynamicImage;
-use rasteroid::{Encoder, RasterEncoder, image_extended::InlineImage, term_misc};
+use rasteroid::{Encoder, RasterEncoder, image_extended::InlineImage, iterm_encoder, term_misc};
 use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use std::{
-    io::{Cursor, Write, stdout},
+    io::{Cursor, Write, stdin, stdout},
     process::{Command, Stdio},
 };
 
@@ -15,6 +15,7 @@ use tracing::{info, warn};
 
 use crate::{
     config::{ColorMode, McatConfig, OutputFormat},
+    fold_viewer,
     image_viewer::{clear_screen, draw_frame, run_interactive_viewer, show_help_prompt},
     markdown_viewer,
     mcat_file::{McatFile, McatKind},
@@ -91,6 +92,9 @@ pub fn cat(files: Vec<McatFile>, out: &mut impl Write, config: &McatConfig) -> R
                 | McatKind::Url
                 | McatKind::JpegXL
                 | McatKind::Mermaid
+                | McatKind::Dot
+                | McatKind::PlantUml
+                | McatKind::D2
                 | McatKind::Typst => {
                     let img = v.to_image(config, false, true)?;
                     let f = McatFile::from_image(img, v.path, v.id);
@@ -122,6 +126,9 @@ pub fn cat(files: Vec<McatFile>, out: &mut impl Write, config: &McatConfig) -> R
             | McatKind::Exe
             | McatKind::JpegXL
             | McatKind::Mermaid
+            | McatKind::Dot
+            | McatKind::PlantUml
+            | McatKind::D2
             | McatKind::Lnk => Some(OutputFormat::Inline),
             McatKind::PreMarkdown
             | McatKind::Markdown
@@ -150,12 +157,39 @@ pub fn cat(files: Vec<McatFile>, out: &mut impl Write, config: &McatConfig) -> R
             img.write_to(&mut Cursor::new(&mut buf), image::ImageFormat::Png)?;
             out.write_all(&buf)?;
         }
+        Some(OutputFormat::Pdf) => {
+            let pdf = mcat_file.to_pdf(config)?;
+            out.write_all(&pdf)?;
+        }
         Some(OutputFormat::Inline) => {
             let is_ascii = config
                 .encoder
                 .map(|v| v == RasterEncoder::Ascii)
                 .unwrap_or(false);
             match mcat_file.kind {
+                // iterm plays gifs itself, no need to redraw every frame
+                McatKind::Gif if encoder == RasterEncoder::Iterm => {
+                    let img = mcat_file.to_image(config, false, true)?;
+                    let cols = wininfo.dim_to_cells(
+                        &format!("{}px", img.width()),
+                        term_misc::SizeDirection::Width,
+                    )?;
+                    let rows = wininfo.dim_to_cells(
+                        &format!("{}px", img.height()),
+                        term_misc::SizeDirection::Height,
+                    )?;
+                    let offset = wininfo.center_offset(img.width() as u16, false);
+                    let offset = if config.no_center { None } else { Some(offset) };
+                    let gif = mcat_file.to_gif()?;
+                    iterm_encoder::encode_animation(
+                        &gif,
+                        out,
+                        offset,
+                        None,
+                        (cols, rows),
+                        wininfo,
+                    )?;
+                }
                 McatKind::Video | McatKind::Gif => {
                     let (mut frames, mut width, _) = mcat_file.to_frames()?;
                     // frames don't give width according to the encoder
@@ -169,7 +203,12 @@ pub fn cat(files: Vec<McatFile>, out: &mut impl Write, config: &McatConfig) -> R
                 }
                 _ => {
                     let img = mcat_file.to_image(config, false, true)?;
-                    let offset = wininfo.center_offset(img.width() as u16, is_ascii);
+                    let width = if is_ascii {
+                        img.width() / wininfo.text_mode.cell_size().0
+                    } else {
+                        img.width()
+                    };
+                    let offset = wininfo.center_offset(width as u16, is_ascii);
                     let offset = if config.no_center { None } else { Some(offset) };
                     encoder.encode_image(&img, out, wininfo, offset, None)?;
                 }
@@ -186,42 +225,65 @@ pub fn cat(files: Vec<McatFile>, out: &mut impl Write, config: &McatConfig) -> R
                 ColorMode::Always => true,
                 ColorMode::Auto => is_tty,
             };
+            if use_color && config.fold && is_tty && stdin().is_tty() {
+                return fold_viewer::run(&md, mcat_file.path.as_deref(), out, config.clone());
+            }
             let content = match use_color {
                 true => {
-                    markdown_viewer::md_to_ansi(&md, config.clone(), mcat_file.path.as_deref())?
+                    // pages scraped from the web keep their url as the id
+                    let source_url = mcat_file
+                        .id
+                        .as_deref()
+                        .filter(|id| id.starts_with("https://") || id.starts_with("http://"));
+                    markdown_viewer::md_to_ansi(
+                        &md,
+                        config.clone(),
+                        mcat_file.path.as_deref(),
+                        source_url,
+                    )?
                 }
                 false => md,
             };
 
-            let use_pager = match config.paging {
-                crate::config::PagingMode::Never => false,
-                crate::config::PagingMode::Always => true,
-                crate::config::PagingMode::Auto => {
-                    is_tty && content.lines().count() > wininfo.sc_height as usize
-                }
-            };
+            write_paged(&content, out, config, is_tty)?;
+        }
+    }
 
-            if use_pager {
-                if let Some(pager) = Pager::new(&config.pager) {
-                    info!(pager = %config.pager, "using pager");
-                    if pager.page(&content).is_err() {
-                        warn!(pager = %config.pager, "pager failed, writing directly");
-                        out.write_all(content.as_bytes())?;
-                    }
-                } else {
-                    warn!(pager = %config.pager, "pager not found, writing directly");
-                    out.write_all(content.as_bytes())?;
-                }
-            } else {
+    Ok(())
+}
+
+/// Writes text output, through the pager when `--paging` asks for it or it overflows the screen.
+pub fn write_paged(
+    content: &str,
+    out: &mut impl Write,
+    config: &McatConfig,
+    is_tty: bool,
+) -> Result<()> {
+    let sc_height = config.wininfo.as_ref().map_or(u16::MAX, |w| w.sc_height);
+    let use_pager = match config.paging {
+        crate::config::PagingMode::Never => false,
+        crate::config::PagingMode::Always => true,
+        crate::config::PagingMode::Auto => is_tty && content.lines().count() > sc_height as usize,
+    };
+
+    if use_pager {
+        if let Some(pager) = Pager::new(&config.pager) {
+            info!(pager = %config.pager, "using pager");
+            if pager.page(content).is_err() {
+                warn!(pager = %config.pager, "pager failed, writing directly");
                 out.write_all(content.as_bytes())?;
             }
+        } else {
+            warn!(pager = %config.pager, "pager not found, writing directly");
+            out.write_all(content.as_bytes())?;
         }
+    } else {
+        out.write_all(content.as_bytes())?;
     }
-
     Ok(())
 }
 
-fn interact_with_image(
+pub fn interact_with_image(
     images: Vec<DynamicImage>,
     opts: &McatConfig,
     out: &mut impl Write,
@@ -245,10 +307,11 @@ fn interact_with_image(
     let image_height = img.height();
 
     let resize_for_ascii = encoder == &RasterEncoder::Ascii;
+    let (cell_w, cell_h) = wininfo.text_mode.cell_size();
 
     let height = wininfo.sc_height - 4;
     let should_disable_raw_mode = match encoder {
-        RasterEncoder::Kitty => wininfo.is_tmux,
+        RasterEncoder::Kitty => wininfo.multiplexer.is_passthrough(),
         RasterEncoder::Ascii => true,
         RasterEncoder::Iterm | RasterEncoder::Sixel => false,
     };
@@ -283,9 +346,14 @@ fn interact_with_image(
                     false,
                 )
                 .ok()?;
-            let center = wininfo.center_offset(img.width() as u16, resize_for_ascii);
+            let img_width = if resize_for_ascii {
+                img.width() / cell_w
+            } else {
+                img.width()
+            };
+            let center = wininfo.center_offset(img_width as u16, resize_for_ascii);
             let img_height_cells = if resize_for_ascii {
-                img.height() / 2
+                img.height().div_ceil(cell_h)
             } else {
                 wininfo
                     .dim_to_cells(
@@ -15,6 +15,17 @@ use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
 use crate::fetch_manager::BrowserConfig;
 
+/// How a page is laid out and captured by [`ChromeHeadless::capture_screenshot`].
+pub struct Viewport {
+    /// css pixels, `None` keeps the width the page lays itself out at
+    pub width: Option<u32>,
+    /// the first screen's height in css pixels, all a capture keeps unless `full_page`
+    pub height: u32,
+    /// `None` picks one so the capture is at least about 1920x1080
+    pub scale: Option<f64>,
+    pub full_page: bool,
+}
+
 pub struct ChromeHeadless {
     process: Arc<Mutex<Child>>,
     port: u16,
@@ -130,34 +141,60 @@ impl ChromeHeadless {
         }
     }
 
-    pub async fn capture_screenshot(&self) -> Result<Vec<u8>> {
-        let endpoint = self.get_websocket_endpoint().await?;
-        let (mut ws_stream, _) = tokio_tungstenite::connect_async(&endpoint).await?;
+    pub async fn capture_screenshot(&self, viewport: &Viewport) -> Result<Vec<u8>> {
+        let mut ws_stream = self.open_page().await?;
 
-        // Get the page ready
-        self.send_command(&mut ws_stream, 1, "Page.enable", None, true)
+        // lay the page out at the asked width first, its height follows from it
+        if let Some(width) = viewport.width {
+            self.send_command(
+                &mut ws_stream,
+                7,
+                "Emulation.setDeviceMetricsOverride",
+                Some(json!({
+                    "mobile": false,
+                    "width": width,
+                    "height": viewport.height,
+                    "deviceScaleFactor": 1
+                })),
+                true,
+            )
             .await?;
-        self.wait_for_load_event(&mut ws_stream).await?;
+        }
 
         //  Get layout metrics
         let metrics = self
             .send_command(&mut ws_stream, 2, "Page.getLayoutMetrics", None, true)
             .await?;
-        let width = metrics["contentSize"]["width"].as_f64().unwrap();
-        let height = metrics["contentSize"]["height"].as_f64().unwrap();
-        let desired_width = 1920.0;
-        let desired_height = 1080.0;
-        let scalex = if width > desired_width {
-            1.0
-        } else {
-            desired_width / width
-        };
-        let scaleh = if height > desired_height {
-            1.0
-        } else {
-            desired_height / height
+        // content wider than the asked width (a wide table, fixed width elements) widens the capture
+        let width = viewport
+            .width
+            .map(f64::from)
+            .into_iter()
+            .chain(metrics["contentSize"]["width"].as_f64())
+            .reduce(f64::max)
+            .context("failed to get the page width")?;
+        let height = metrics["contentSize"]["height"]
+            .as_f64()
+            .context("failed to get the page height")?;
+        let height = match viewport.full_page {
+            true => height,
+            false => height.min(f64::from(viewport.height)),
         };
-        let scale = scalex.min(scaleh).round() as u16;
+        let scale = viewport.scale.unwrap_or_else(|| {
+            let desired_width = 1920.0;
+            let desired_height = 1080.0;
+            let scalex = if width > desired_width {
+                1.0
+            } else {
+                desired_width / width
+            };
+            let scaleh = if height > desired_height {
+                1.0
+            } else {
+                desired_height / height
+            };
+            scalex.min(scaleh).round()
+        });
 
         // Set viewport
         self.send_command(
@@ -190,7 +227,7 @@ impl ChromeHeadless {
                 &mut ws_stream,
                 4,
                 "Page.captureScreenshot",
-                Some(json!({ "format": "png", "captureBeyondViewport": true })),
+                Some(json!({ "format": "png", "captureBeyondViewport": viewport.full_page })),
                 true,
             )
             .await?;
@@ -212,6 +249,37 @@ impl ChromeHeadless {
         Ok(bytes)
     }
 
+    /// Prints the page with `Page.printToPDF`, `params` are passed on as they are
+    /// (paper size and margins in inches, header / footer templates..).
+    pub async fn print_to_pdf(&self, params: Value) -> Result<Vec<u8>> {
+        let mut ws_stream = self.open_page().await?;
+
+        let response = self
+            .send_command(&mut ws_stream, 2, "Page.printToPDF", Some(params), true)
+            .await?;
+        let pdf_data = response["data"].as_str().context("failed to get pdf")?;
+        let bytes = general_purpose::STANDARD.decode(pdf_data)?;
+
+        {
+            let mut process = self.process.lock().unwrap();
+            let _ = process.kill();
+        }
+
+        tracing::info!(size = bytes.len(), "printed pdf via headless chrome");
+        Ok(bytes)
+    }
+
+    // connects to the page and waits for it to load
+    async fn open_page(&self) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+        let endpoint = self.get_websocket_endpoint().await?;
+        let (mut ws_stream, _) = tokio_tungstenite::connect_async(&endpoint).await?;
+
+        self.send_command(&mut ws_stream, 1, "Page.enable", None, true)
+            .await?;
+        self.wait_for_load_event(&mut ws_stream).await?;
+        Ok(ws_stream)
+    }
+
     async fn get_websocket_endpoint(&self) -> Result<String> {
         // shouldn't really go over 1 and even
         let max_attempts = 10;
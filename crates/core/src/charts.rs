@@ -0,0 +1,532 @@
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::{
+    diagrams::{FONTS, escape_xml},
+    markdown_viewer::render::RESET,
+    themes::{CustomTheme, ThemeColor},
+};
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 360.0;
+const MARGIN_LEFT: f64 = 56.0;
+const MARGIN_RIGHT: f64 = 16.0;
+const MARGIN_BOTTOM: f64 = 36.0;
+const FONT_SIZE: f64 = 12.0;
+const TICKS: f64 = 5.0;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Fence info strings that hold a chart.
+pub const LANGS: &[&str] = &["chart", "vega-lite", "vegalite"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartKind {
+    Bar,
+    Line,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+/// A small dataset, one value per label in every series.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chart {
+    pub kind: ChartKind,
+    pub title: Option<String>,
+    pub labels: Vec<String>,
+    pub series: Vec<Series>,
+}
+
+impl Chart {
+    /// Parses a fence body, a vega-lite subset when it's json and csv otherwise.
+    pub fn parse(source: &str) -> Result<Self> {
+        let chart = if source.trim_start().starts_with('{') {
+            Self::from_vega_lite(source)?
+        } else {
+            Self::from_csv(source)?
+        };
+        anyhow::ensure!(
+            !chart.labels.is_empty() && !chart.series.is_empty(),
+            "the chart has no data"
+        );
+        Ok(chart)
+    }
+
+    /// `type:` and `title:` lines, then a csv (or tsv) with the labels in the first column and a
+    /// series per other column, named by the header row.
+    fn from_csv(source: &str) -> Result<Self> {
+        let mut kind = ChartKind::Bar;
+        let mut title = None;
+        let mut lines = source.lines().map(str::trim).filter(|l| !l.is_empty());
+        let header = loop {
+            let line = lines.next().context("the chart has no header row")?;
+            match line.split_once(':') {
+                Some((key, value)) if key.trim() == "type" => kind = parse_kind(value.trim())?,
+                Some((key, value)) if key.trim() == "title" => {
+                    title = Some(value.trim().to_owned())
+                }
+                _ => break line,
+            }
+        };
+
+        let sep = if header.contains('\t') { '\t' } else { ',' };
+        let split = |line: &str| line.split(sep).map(|c| c.trim().to_owned()).collect();
+        let header: Vec<String> = split(header);
+        let mut series: Vec<Series> = header
+            .iter()
+            .skip(1)
+            .map(|name| Series {
+                name: name.clone(),
+                values: Vec::new(),
+            })
+            .collect();
+        let mut labels = Vec::new();
+        for line in lines {
+            let cells: Vec<String> = split(line);
+            labels.push(cells.first().cloned().unwrap_or_default());
+            for (i, s) in series.iter_mut().enumerate() {
+                let cell = cells.get(i + 1).map(String::as_str).unwrap_or("");
+                let value = cell
+                    .parse()
+                    .with_context(|| format!("{cell:?} isn't a number ({})", s.name))?;
+                s.values.push(value);
+            }
+        }
+
+        Ok(Self {
+            kind,
+            title,
+            labels,
+            series,
+        })
+    }
+
+    /// `mark`, `title`, inline `data.values` and the `x`, `y` and `color` encodings.
+    fn from_vega_lite(source: &str) -> Result<Self> {
+        let spec: Value = serde_json::from_str(source)?;
+        let mark = match &spec["mark"] {
+            Value::Object(mark) => mark.get("type").and_then(Value::as_str),
+            mark => mark.as_str(),
+        };
+        let kind = parse_kind(mark.unwrap_or("bar"))?;
+        let title = match &spec["title"] {
+            Value::Object(title) => title.get("text").and_then(Value::as_str),
+            title => title.as_str(),
+        }
+        .map(str::to_owned);
+
+        let field = |channel: &str| spec["encoding"][channel]["field"].as_str();
+        let x = field("x").context("the spec has no x field")?;
+        let y = field("y").context("the spec has no y field")?;
+        let color = field("color");
+        let rows = spec["data"]["values"]
+            .as_array()
+            .context("only inline data.values is supported")?;
+
+        let text = |v: &Value| match v {
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+        let mut labels: Vec<String> = Vec::new();
+        let mut series: Vec<Series> = Vec::new();
+        for row in rows {
+            let label = text(&row[x]);
+            let name = color.map(|c| text(&row[c])).unwrap_or_else(|| y.to_owned());
+            let value = row[y]
+                .as_f64()
+                .with_context(|| format!("{} isn't a number ({y})", row[y]))?;
+
+            let at = labels.iter().position(|l| *l == label).unwrap_or_else(|| {
+                labels.push(label);
+                labels.len() - 1
+            });
+            let s = match series.iter().position(|s| s.name == name) {
+                Some(i) => &mut series[i],
+                None => {
+                    series.push(Series {
+                        name,
+                        values: Vec::new(),
+                    });
+                    series.last_mut().unwrap()
+                }
+            };
+            if s.values.len() <= at {
+                s.values.resize(at + 1, 0.0);
+            }
+            s.values[at] = value;
+        }
+        for s in &mut series {
+            s.values.resize(labels.len(), 0.0);
+        }
+
+        Ok(Self {
+            kind,
+            title,
+            labels,
+            series,
+        })
+    }
+
+    fn range(&self) -> (f64, f64) {
+        let values = self.series.iter().flat_map(|s| s.values.iter().copied());
+        let (min, max) = values.fold((0.0_f64, 0.0_f64), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        if min == max {
+            (min, min + 1.0)
+        } else {
+            (min, max)
+        }
+    }
+
+    /// Draws the chart with axes, grid lines and a legend when there's more than one series.
+    pub fn to_svg(&self, theme: &CustomTheme) -> String {
+        let colors = palette(theme);
+        let top = 16.0
+            + if self.title.is_some() { 24.0 } else { 0.0 }
+            + if self.series.len() > 1 { 20.0 } else { 0.0 };
+        let (plot_w, plot_h) = (
+            WIDTH - MARGIN_LEFT - MARGIN_RIGHT,
+            HEIGHT - top - MARGIN_BOTTOM,
+        );
+
+        let (min, max) = self.range();
+        let step = nice_step((max - min) / TICKS);
+        let (lo, hi) = ((min / step).floor() * step, (max / step).ceil() * step);
+        let y_of = |v: f64| top + plot_h - (v - lo) / (hi - lo) * plot_h;
+        let slot = plot_w / self.labels.len() as f64;
+        let x_of = |i: usize| MARGIN_LEFT + slot * (i as f64 + 0.5);
+
+        let fg = format!("#{}", theme.foreground.hex());
+        let muted = format!("#{}", theme.comment.hex());
+        let grid = format!("#{}", theme.guide.hex());
+        let mut body = String::new();
+
+        if let Some(title) = &self.title {
+            let _ = write!(
+                body,
+                r#"<text x="{}" y="22" text-anchor="middle" font-size="16" font-weight="bold" fill="{fg}">{}</text>"#,
+                WIDTH / 2.0,
+                escape_xml(title)
+            );
+        }
+        if self.series.len() > 1 {
+            let y = top - 14.0;
+            let mut x = MARGIN_LEFT;
+            for (s, color) in self.series.iter().zip(colors.iter().cycle()) {
+                let _ = write!(
+                    body,
+                    r#"<rect x="{x}" y="{}" width="10" height="10" rx="2" fill="{color}"/><text x="{}" y="{y}" fill="{fg}">{}</text>"#,
+                    y - 9.0,
+                    x + 14.0,
+                    escape_xml(&s.name)
+                );
+                x += 24.0 + s.name.chars().count() as f64 * FONT_SIZE * 0.6;
+            }
+        }
+
+        // grid lines with their values, and the zero line drawn solid
+        let mut tick = lo;
+        while tick <= hi + step / 2.0 {
+            let y = y_of(tick);
+            let _ = write!(
+                body,
+                r#"<line x1="{MARGIN_LEFT}" y1="{y}" x2="{}" y2="{y}" stroke="{grid}" stroke-width="1"/><text x="{}" y="{}" text-anchor="end" fill="{muted}">{}</text>"#,
+                WIDTH - MARGIN_RIGHT,
+                MARGIN_LEFT - 6.0,
+                y + 4.0,
+                format_value(tick)
+            );
+            tick += step;
+        }
+        let _ = write!(
+            body,
+            r#"<line x1="{MARGIN_LEFT}" y1="{0}" x2="{1}" y2="{0}" stroke="{muted}" stroke-width="1"/>"#,
+            y_of(0.0_f64.clamp(lo, hi)),
+            WIDTH - MARGIN_RIGHT,
+        );
+        for (i, label) in self.labels.iter().enumerate() {
+            let _ = write!(
+                body,
+                r#"<text x="{}" y="{}" text-anchor="middle" fill="{muted}">{}</text>"#,
+                x_of(i),
+                top + plot_h + 18.0,
+                escape_xml(label)
+            );
+        }
+
+        match self.kind {
+            ChartKind::Bar => {
+                let bar = slot * 0.8 / self.series.len() as f64;
+                let zero = y_of(0.0_f64.clamp(lo, hi));
+                for (n, (s, color)) in self.series.iter().zip(colors.iter().cycle()).enumerate() {
+                    for (i, v) in s.values.iter().enumerate() {
+                        let x = x_of(i) - slot * 0.4 + bar * n as f64;
+                        let y = y_of(*v);
+                        let _ = write!(
+                            body,
+                            r#"<rect x="{x}" y="{}" width="{}" height="{}" rx="2" fill="{color}"/>"#,
+                            y.min(zero),
+                            (bar - 2.0).max(1.0),
+                            (zero - y).abs()
+                        );
+                    }
+                }
+            }
+            ChartKind::Line => {
+                for (s, color) in self.series.iter().zip(colors.iter().cycle()) {
+                    let points: Vec<String> = s
+                        .values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| format!("{},{}", x_of(i), y_of(*v)))
+                        .collect();
+                    let _ = write!(
+                        body,
+                        r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="2.5" stroke-linejoin="round"/>"#,
+                        points.join(" ")
+                    );
+                    for (i, v) in s.values.iter().enumerate() {
+                        let _ = write!(
+                            body,
+                            r#"<circle cx="{}" cy="{}" r="3.5" fill="{color}"/>"#,
+                            x_of(i),
+                            y_of(*v)
+                        );
+                    }
+                }
+            }
+        }
+
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}"><g font-family="{FONTS}" font-size="{FONT_SIZE}">{body}</g></svg>"#
+        )
+    }
+
+    /// The text fallback: horizontal bars in eighth blocks, or a sparkline per series.
+    pub fn to_unicode(&self, theme: &CustomTheme, width: usize) -> String {
+        let colors: Vec<&ThemeColor> = palette_colors(theme);
+        let muted = &theme.comment.fg;
+        let mut out = String::new();
+        if let Some(title) = &self.title {
+            let _ = writeln!(out, "{}{title}{RESET}", theme.foreground.fg);
+        }
+
+        match self.kind {
+            ChartKind::Bar => {
+                if self.series.len() > 1 {
+                    let legend: Vec<String> = self
+                        .series
+                        .iter()
+                        .zip(colors.iter().cycle())
+                        .map(|(s, c)| format!("{}■{RESET} {}", c.fg, s.name))
+                        .collect();
+                    let _ = writeln!(out, "{}", legend.join("  "));
+                }
+                let label_w = self
+                    .labels
+                    .iter()
+                    .map(|l| l.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let values: Vec<String> = self
+                    .series
+                    .iter()
+                    .flat_map(|s| s.values.iter().map(|v| format_value(*v)))
+                    .collect();
+                let value_w = values.iter().map(String::len).max().unwrap_or(0);
+                let bar_w = width.saturating_sub(label_w + value_w + 2).max(8);
+                let (_, max) = self.range();
+
+                for (i, label) in self.labels.iter().enumerate() {
+                    for (n, (s, color)) in self.series.iter().zip(colors.iter().cycle()).enumerate()
+                    {
+                        let label = if n == 0 { label.as_str() } else { "" };
+                        let v = s.values[i];
+                        let eighths = (v.max(0.0) / max * bar_w as f64 * 8.0).round() as usize;
+                        let mut bar = "█".repeat(eighths / 8);
+                        if !eighths.is_multiple_of(8) {
+                            bar.push(EIGHTHS[eighths % 8]);
+                        }
+                        let _ = writeln!(
+                            out,
+                            "{muted}{label:>label_w$}{RESET} {}{bar}{RESET} {}",
+                            color.fg,
+                            format_value(v)
+                        );
+                    }
+                }
+            }
+            ChartKind::Line => {
+                let name_w = self.series.iter().map(|s| s.name.chars().count()).max();
+                let name_w = name_w.unwrap_or(0);
+                for (s, color) in self.series.iter().zip(colors.iter().cycle()) {
+                    let (lo, hi) = s
+                        .values
+                        .iter()
+                        .fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+                    let spark: String = s
+                        .values
+                        .iter()
+                        .map(|v| {
+                            let at = if hi > lo {
+                                (v - lo) / (hi - lo) * 7.0
+                            } else {
+                                3.0
+                            };
+                            SPARKS[at.round() as usize]
+                        })
+                        .collect();
+                    let _ = writeln!(
+                        out,
+                        "{muted}{:>name_w$}{RESET} {}{spark}{RESET} {muted}{} – {}{RESET}",
+                        s.name,
+                        color.fg,
+                        format_value(lo),
+                        format_value(hi)
+                    );
+                }
+                if let (Some(first), Some(last)) = (self.labels.first(), self.labels.last()) {
+                    let _ = writeln!(out, "{muted}{:name_w$} {first} → {last}{RESET}", "");
+                }
+            }
+        }
+
+        out.trim_end().to_owned()
+    }
+}
+
+fn parse_kind(s: &str) -> Result<ChartKind> {
+    match s.to_lowercase().as_str() {
+        "bar" | "bars" | "column" => Ok(ChartKind::Bar),
+        "line" | "lines" => Ok(ChartKind::Line),
+        other => anyhow::bail!("unsupported chart type {other:?}, use bar or line"),
+    }
+}
+
+fn palette_colors(theme: &CustomTheme) -> Vec<&ThemeColor> {
+    vec![
+        &theme.blue,
+        &theme.green,
+        &theme.yellow,
+        &theme.magenta,
+        &theme.cyan,
+        &theme.red,
+        &theme.keyword,
+    ]
+}
+
+fn palette(theme: &CustomTheme) -> Vec<String> {
+    palette_colors(theme)
+        .into_iter()
+        .map(|c| format!("#{}", c.hex()))
+        .collect()
+}
+
+// 1, 2 or 5 times a power of ten, so the axis reads nicely
+fn nice_step(raw: f64) -> f64 {
+    let raw = raw.max(f64::EPSILON);
+    let pow = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .find(|m| m * pow >= raw)
+        .unwrap_or(10.0);
+    step * pow
+}
+
+fn format_value(v: f64) -> String {
+    if v.fract().abs() < 1e-9 {
+        format!("{v:.0}")
+    } else {
+        let s = format!("{v:.2}");
+        s.trim_end_matches('0').trim_end_matches('.').to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_with_options() {
+        let chart =
+            Chart::parse("type: line\ntitle: Visits\nday,api,web\nmon,1,2\ntue,3,4.5\n").unwrap();
+        assert_eq!(chart.kind, ChartKind::Line);
+        assert_eq!(chart.title.as_deref(), Some("Visits"));
+        assert_eq!(chart.labels, vec!["mon", "tue"]);
+        assert_eq!(chart.series[1].name, "web");
+        assert_eq!(chart.series[1].values, vec![2.0, 4.5]);
+    }
+
+    #[test]
+    fn vega_lite_splits_series_by_color() {
+        let spec = r#"{
+            "mark": {"type": "bar"},
+            "data": {"values": [
+                {"m": "jan", "v": 1, "k": "a"},
+                {"m": "jan", "v": 2, "k": "b"},
+                {"m": "feb", "v": 3, "k": "a"}
+            ]},
+            "encoding": {"x": {"field": "m"}, "y": {"field": "v"}, "color": {"field": "k"}}
+        }"#;
+        let chart = Chart::parse(spec).unwrap();
+        assert_eq!(chart.labels, vec!["jan", "feb"]);
+        assert_eq!(chart.series[0].values, vec![1.0, 3.0]);
+        assert_eq!(chart.series[1].values, vec![2.0, 0.0]);
+    }
+
+    #[test]
+    fn bad_cells_are_errors() {
+        assert!(Chart::parse("x,y\na,oops\n").is_err());
+        assert!(Chart::parse("type: pie\nx,y\na,1\n").is_err());
+    }
+
+    #[test]
+    fn bars_are_drawn_in_eighths() {
+        let theme = CustomTheme::catppuccin();
+        let chart = Chart::parse("x,y\na,8\nb,3\n").unwrap();
+        let text = chart.to_unicode(&theme, 13);
+        assert!(text.contains(&theme.blue.fg));
+        // 13 columns less the label, the value and two spaces leave 9 for the bar
+        assert_eq!(
+            strip_ansi_escapes::strip_str(&text),
+            "a █████████ 8\nb ███▍ 3"
+        );
+    }
+
+    #[test]
+    fn lines_are_drawn_as_sparklines() {
+        let theme = CustomTheme::catppuccin();
+        let chart = Chart::parse("type: line\nday,y\nmon,1\ntue,5\nwed,3\nthu,9\n").unwrap();
+        let text = chart.to_unicode(&theme, 40);
+        assert_eq!(
+            strip_ansi_escapes::strip_str(&text),
+            "y ▁▅▃█ 1 – 9\n  mon → thu"
+        );
+    }
+
+    #[test]
+    fn svgs_use_the_theme_colours() {
+        let theme = CustomTheme::catppuccin();
+        let chart = Chart::parse("title: A & <B>\nx,api,web\nmon,1,2\ntue,3,4\n").unwrap();
+        let svg = chart.to_svg(&theme);
+        assert!(svg.contains(&format!(
+            r##"fill="#{}">A &amp; &lt;B&gt;</text>"##,
+            theme.foreground.hex()
+        )));
+        assert!(svg.contains(&format!(r##"fill="#{}"/>"##, theme.blue.hex())));
+        assert!(svg.contains(&format!(r##"fill="#{}"/>"##, theme.green.hex())));
+        assert!(svg.contains(&format!(r##"stroke="#{}""##, theme.guide.hex())));
+    }
+
+    #[test]
+    fn nice_steps() {
+        assert_eq!(nice_step(0.7), 1.0);
+        assert_eq!(nice_step(13.0), 20.0);
+        assert_eq!(nice_step(260.0), 500.0);
+    }
+}
@@ -10,12 +10,14 @@ use clap::{
 use clap_complete::Shell;
 use crossterm::tty::IsTty;
 use rasteroid::{
-    RasterEncoder,
+    RasterEncoder, ascii_encoder,
+    color::ColorDepth,
+    multiplexer::Multiplexer,
     term_misc::{self, EnvIdentifiers, Wininfo},
 };
 use tracing::debug;
 
-use crate::{prompter::MultiBar, scrapy, themes::CustomTheme};
+use crate::{cache, mcat_file::McatKind, prompter::MultiBar, scrapy, themes::CustomTheme};
 
 #[derive(Parser)]
 #[command(
@@ -77,6 +79,24 @@ pub struct McatConfig {
     #[arg(long, help_heading = "Core Options")]
     pub report: bool,
 
+    /// Show a report of the image instead (format, EXIF, ICC profile, DPI, histogram)
+    #[arg(long, help_heading = "Core Options")]
+    pub info: bool,
+
+    /// Compare two files, a line diff of their markdown or a pixel heatmap of two images
+    #[arg(long, help_heading = "Core Options")]
+    pub diff: bool,
+
+    /// Layout of --diff, side-by-side once the terminal is wide enough by default
+    #[arg(
+        long,
+        value_name = "layout",
+        help_heading = "Core Options",
+        env = "MCAT_DIFF_LAYOUT",
+        default_value_t = DiffLayout::Auto
+    )]
+    pub diff_layout: DiffLayout,
+
     /// Extension hint for stdin input (e.g. csv, json, md)
     #[arg(
         short = 'e',
@@ -86,10 +106,35 @@ pub struct McatConfig {
     )]
     pub stdin_ext: Option<String>,
 
+    /// Colours the terminal supports, auto-detected by default
+    #[arg(
+        long,
+        value_name = "depth",
+        help_heading = "Core Options",
+        env = "MCAT_COLOR_DEPTH",
+        default_value_t = ColorDepthMode::Auto
+    )]
+    pub color_depth: ColorDepthMode,
+
     /// Remove loading bars
     #[arg(long, help_heading = "Core Options", env = "MCAT_SILENT")]
     pub silent: bool,
 
+    /// Don't read or write the thumbnail and remote image cache
+    #[arg(long, help_heading = "Core Options", env = "MCAT_NO_CACHE")]
+    pub no_cache: bool,
+
+    /// Max size of the cache, least recently used entries are evicted past it (e.g. 500M, 1G)
+    #[arg(
+        long,
+        value_name = "size",
+        value_parser = parse_file_size,
+        help_heading = "Core Options",
+        env = "MCAT_CACHE_SIZE",
+        default_value = "256M"
+    )]
+    pub cache_size: u64,
+
     /// Pixel bounding box for image rendering, auto-detected by default
     /// (e.g. 1920x1080, 1920xauto, autox1080)
     #[arg(
@@ -138,6 +183,14 @@ pub struct McatConfig {
     #[arg(long, help_heading = "Markdown Viewing", env = "MCAT_TOC")]
     pub toc: bool,
 
+    /// Open markdown in a view where headings and <details> blocks fold
+    #[arg(long, help_heading = "Markdown Viewing", env = "MCAT_FOLD")]
+    pub fold: bool,
+
+    /// Number links and list their urls at the end, for terminals without hyperlinks
+    #[arg(long, help_heading = "Markdown Viewing", env = "MCAT_LINK_REFS")]
+    pub link_refs: bool,
+
     /// Enable Experimental support for kitty text-sizing protocol
     #[arg(long, help_heading = "Markdown Viewing", env = "MCAT_MD_KITTY_HEADERS")]
     pub md_kitty_headers: bool,
@@ -146,6 +199,16 @@ pub struct McatConfig {
     #[arg(long, help_heading = "Markdown Viewing", env = "MCAT_NO_LINENUMBERS")]
     pub no_linenumbers: bool,
 
+    /// How tables wider than the terminal are drawn
+    #[arg(
+        long = "md-table",
+        value_name = "mode",
+        help_heading = "Markdown Viewing",
+        env = "MCAT_MD_TABLE",
+        default_value_t = MdTableMode::Auto
+    )]
+    pub md_table: MdTableMode,
+
     /// What images to render in the markdown
     #[arg(long = "md-image", value_name = "mode", help_heading = "Markdown Viewing",
         default_value_t = MdImageMode::Auto,
@@ -259,6 +322,24 @@ pub struct McatConfig {
     #[arg(long, help_heading = "Image/Video Viewing")]
     ascii: bool,
 
+    /// Cell renderer for ASCII output
+    #[arg(
+        long,
+        value_name = "mode",
+        help_heading = "Image/Video Viewing",
+        env = "MCAT_TEXT_MODE",
+        default_value_t = TextMode::Half
+    )]
+    pub text_mode: TextMode,
+
+    /// Don't apply EXIF orientation and ICC colour profiles when rendering images
+    #[arg(
+        long,
+        help_heading = "Image/Video Viewing",
+        env = "MCAT_NO_IMG_METADATA"
+    )]
+    pub no_img_metadata: bool,
+
     /// Disable centering the image in the terminal
     #[arg(long, help_heading = "Image/Video Viewing", env = "MCAT_NO_CENTER")]
     pub no_center: bool,
@@ -288,6 +369,75 @@ pub struct McatConfig {
     #[arg(long, help_heading = "Conversion", env = "MCAT_STYLE_HTML")]
     pub style_html: bool,
 
+    /// Width HTML (and rendered markdown) is laid out at before it's captured, in pixels.
+    /// the terminal's pixel width by default
+    #[arg(
+        long,
+        value_name = "px",
+        help_heading = "Conversion",
+        env = "MCAT_HTML_WIDTH"
+    )]
+    pub html_width: Option<u32>,
+
+    /// Device scale factor of HTML captures, picked so the image is at least about 1920x1080
+    /// by default
+    #[arg(
+        long,
+        value_name = "float",
+        value_parser = parse_scale,
+        help_heading = "Conversion",
+        env = "MCAT_HTML_SCALE"
+    )]
+    pub html_scale: Option<f64>,
+
+    /// Capture only the first screen of HTML instead of the whole page
+    #[arg(long, help_heading = "Conversion", env = "MCAT_HTML_VIEWPORT")]
+    pub html_viewport: bool,
+
+    /// Split tall HTML and markdown captures into screen sized tiles, paged through in -I
+    #[arg(long, help_heading = "Conversion", env = "MCAT_HTML_TILES")]
+    pub html_tiles: bool,
+
+    /// Page size of the -o pdf export
+    #[arg(
+        long,
+        value_name = "size",
+        help_heading = "Conversion",
+        env = "MCAT_PDF_PAGE",
+        default_value_t = PdfPageSize::A4
+    )]
+    pub pdf_page: PdfPageSize,
+
+    /// Margin around the pages of the -o pdf export (e.g. 15mm, 1cm, 0.5in)
+    #[arg(
+        long,
+        value_name = "length",
+        value_parser = parse_length,
+        help_heading = "Conversion",
+        env = "MCAT_PDF_MARGIN",
+        default_value = "1cm"
+    )]
+    pub pdf_margin: f64,
+
+    /// HTML template for the top of every pdf page, with pageNumber, totalPages, title and
+    /// date classes filled in (e.g. '<span class="title"></span>')
+    #[arg(
+        long,
+        value_name = "html",
+        help_heading = "Conversion",
+        env = "MCAT_PDF_HEADER"
+    )]
+    pub pdf_header: Option<String>,
+
+    /// HTML template for the bottom of every pdf page, same classes as --pdf-header
+    #[arg(
+        long,
+        value_name = "html",
+        help_heading = "Conversion",
+        env = "MCAT_PDF_FOOTER"
+    )]
+    pub pdf_footer: Option<String>,
+
     // ## Directory Listing ##
     /// Include hidden files
     #[arg(
@@ -302,6 +452,19 @@ pub struct McatConfig {
     #[arg(long, help_heading = "Directory Listing", env = "MCAT_HYPRLINK")]
     pub hyprlink: bool,
 
+    /// Show `ls` as a list with size, time, permissions, kind and media info
+    #[arg(
+        long,
+        short = 'l',
+        help_heading = "Directory Listing",
+        env = "MCAT_LONG"
+    )]
+    pub long: bool,
+
+    /// Show `ls` as a recursive tree with folder sizes, respects .gitignore and --max-depth
+    #[arg(long, help_heading = "Directory Listing", env = "MCAT_TREE")]
+    pub tree: bool,
+
     /// Sort method
     #[arg(long, help_heading = "Directory Listing",
         env = "MCAT_SORT",
@@ -387,6 +550,40 @@ pub struct McatConfig {
     )]
     pub ls_items_per_row: usize,
 
+    /// Show icons instead of previews for documents, archives and text files (faster)
+    #[arg(long, help_heading = "Directory Listing", env = "MCAT_LS_ICONS")]
+    pub ls_icons: bool,
+
+    /// Preview documents (office, markdown, html, csv) instead of showing their icon, slow as
+    /// each one is rendered by chromium
+    #[arg(long, help_heading = "Directory Listing", env = "MCAT_LS_DOCS")]
+    pub ls_docs: bool,
+
+    // ## Directory Input ##
+    /// Walk into subdirectories instead of prompting (honors .gitignore)
+    #[arg(long, short = 'R', help_heading = "Directory Input")]
+    pub recursive: bool,
+
+    /// Only take files matching the glob, can be repeated (e.g. '*.md')
+    #[arg(long, value_name = "glob", help_heading = "Directory Input")]
+    pub include: Vec<String>,
+
+    /// Skip files matching the glob, can be repeated (e.g. 'target/')
+    #[arg(long, value_name = "glob", help_heading = "Directory Input")]
+    pub exclude: Vec<String>,
+
+    /// Maximum directory depth to walk, implies --recursive (also limits `ls --tree`)
+    #[arg(long, value_name = "depth", help_heading = "Directory Input")]
+    pub max_depth: Option<usize>,
+
+    /// Skip files bigger than this (e.g. 500K, 10M, 1G)
+    #[arg(long, value_name = "size", value_parser = parse_file_size, help_heading = "Directory Input")]
+    pub max_size: Option<u64>,
+
+    /// Only take files of these kinds, can be repeated
+    #[arg(long, value_name = "kind", help_heading = "Directory Input")]
+    pub only: Vec<KindFilter>,
+
     // ## System Operations ##
     /// Generate shell completions
     #[arg(long, value_name = "shell", help_heading = "System Operations")]
@@ -400,7 +597,7 @@ pub struct McatConfig {
     #[arg(long, help_heading = "System Operations")]
     pub fetch_ffmpeg: bool,
 
-    /// Clean up local binaries
+    /// Clean up local binaries and the cache
     #[arg(long, help_heading = "System Operations")]
     pub fetch_clean: bool,
 
@@ -426,24 +623,53 @@ pub struct McatConfig {
 }
 
 impl McatConfig {
+    /// `true` if any of the directory input flags were given, directories then skip the prompt.
+    pub fn has_dir_filters(&self) -> bool {
+        self.recursive
+            || !self.include.is_empty()
+            || !self.exclude.is_empty()
+            || self.max_depth.is_some()
+            || self.max_size.is_some()
+            || !self.only.is_empty()
+    }
+
     pub fn finalize(&mut self) -> anyhow::Result<()> {
         let env = term_misc::EnvIdentifiers::new();
         let spx = Some(self.spx.as_ref());
         let sc = Some(self.sc.as_ref());
-        let wininfo = Wininfo::new(spx, sc, Some(self.scalex), Some(self.scaley), &env)?;
-        let encoder = if self.kitty {
-            RasterEncoder::Kitty
+        let mut wininfo = Wininfo::new(spx, sc, Some(self.scalex), Some(self.scaley), &env)?;
+        wininfo.text_mode = self.text_mode.into();
+        if let Some(depth) = self.color_depth.to_depth() {
+            wininfo.color_depth = depth;
+        }
+        let forced = if self.kitty {
+            Some(RasterEncoder::Kitty)
         } else if self.iterm {
-            RasterEncoder::Iterm
+            Some(RasterEncoder::Iterm)
         } else if self.sixel {
-            RasterEncoder::Sixel
+            Some(RasterEncoder::Sixel)
         } else if self.ascii {
-            RasterEncoder::Ascii
+            Some(RasterEncoder::Ascii)
         } else {
-            RasterEncoder::auto_detect(&env)
+            None
+        };
+        let encoder = match forced {
+            Some(forced) => {
+                let encoder = forced.or_supported(&env);
+                if encoder != forced && !self.silent {
+                    eprintln!(
+                        "warning: {forced:?} images can't pass through {}, using {encoder:?}",
+                        wininfo.multiplexer
+                    );
+                }
+                encoder
+            }
+            None => RasterEncoder::auto_detect(&env),
         };
 
+        // a pdf is usually redirected to a file, but its images still have to be inside it
         self.inline_images_in_md = self.force_embed_images
+            || self.output == Some(OutputFormat::Pdf)
             || (self
                 .output
                 .as_ref()
@@ -454,6 +680,7 @@ impl McatConfig {
 
         debug!(
             ?encoder,
+            ?self.text_mode,
             ?self.output,
             ?self.theme,
             ?self.md_image,
@@ -463,12 +690,13 @@ impl McatConfig {
             sc_height = wininfo.sc_height,
             spx_width = wininfo.spx_width,
             spx_height = wininfo.spx_height,
-            is_tmux = wininfo.is_tmux,
+            multiplexer = ?wininfo.multiplexer,
+            color_depth = ?wininfo.color_depth,
             needs_inline = wininfo.needs_inline,
             "config"
         );
         if !self.silent && stderr().is_tty() {
-            self.bar = if env.term_contains("ghostty") && !wininfo.is_tmux {
+            self.bar = if env.term_contains("ghostty") && wininfo.multiplexer == Multiplexer::None {
                 Some(MultiBar::ghostty())
             } else {
                 Some(MultiBar::indicatif())
@@ -481,6 +709,8 @@ impl McatConfig {
         scrapy::TIMEOUT
             .set(Duration::from_secs(self.timeout as u64))
             .ok();
+        cache::ENABLED.set(!self.no_cache).ok();
+        cache::MAX_SIZE.set(self.cache_size).ok();
 
         Ok(())
     }
@@ -537,6 +767,7 @@ pub enum OutputFormat {
     Html,
     Md,
     Image,
+    Pdf,
     Inline,
     Interactive,
 }
@@ -569,6 +800,68 @@ impl std::fmt::Display for PagingMode {
     }
 }
 
+#[derive(ValueEnum, Clone, Copy, PartialEq, Default, Debug)]
+pub enum PdfPageSize {
+    #[default]
+    A4,
+    A3,
+    A5,
+    Letter,
+    Legal,
+}
+
+impl PdfPageSize {
+    /// Width and height in inches, what chromium's print takes.
+    pub fn inches(self) -> (f64, f64) {
+        match self {
+            Self::A4 => (8.27, 11.69),
+            Self::A3 => (11.69, 16.54),
+            Self::A5 => (5.83, 8.27),
+            Self::Letter => (8.5, 11.0),
+            Self::Legal => (8.5, 14.0),
+        }
+    }
+}
+
+impl std::fmt::Display for PdfPageSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.to_possible_value().unwrap().get_name().fmt(f)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Default, Debug)]
+pub enum DiffLayout {
+    Unified,
+    SideBySide,
+    #[default]
+    Auto,
+}
+
+impl std::fmt::Display for DiffLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.to_possible_value().unwrap().get_name().fmt(f)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Default, PartialEq, Debug)]
+pub enum MdTableMode {
+    /// Wrap cells within capped column widths
+    Wrap,
+    /// Stack the columns that don't fit in more tables, repeating the first column
+    Split,
+    /// Draw each row as a card of its fields
+    Transpose,
+    /// Squeeze the columns, splitting or transposing when they'd get unreadable
+    #[default]
+    Auto,
+}
+
+impl std::fmt::Display for MdTableMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.to_possible_value().unwrap().get_name().fmt(f)
+    }
+}
+
 #[derive(ValueEnum, Clone, Default, PartialEq, Debug)]
 pub enum MdImageMode {
     All,
@@ -600,6 +893,115 @@ impl std::fmt::Display for ImageProtocol {
     }
 }
 
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub enum ColorDepthMode {
+    #[default]
+    Auto,
+    Truecolor,
+    #[value(name = "256")]
+    Ansi256,
+    #[value(name = "16")]
+    Ansi16,
+}
+
+impl ColorDepthMode {
+    /// `None` means auto, keep whatever was detected.
+    fn to_depth(self) -> Option<ColorDepth> {
+        match self {
+            ColorDepthMode::Auto => None,
+            ColorDepthMode::Truecolor => Some(ColorDepth::TrueColor),
+            ColorDepthMode::Ansi256 => Some(ColorDepth::Ansi256),
+            ColorDepthMode::Ansi16 => Some(ColorDepth::Ansi16),
+        }
+    }
+}
+
+impl std::fmt::Display for ColorDepthMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.to_possible_value().unwrap().get_name().fmt(f)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub enum TextMode {
+    /// Half blocks, 1x2 pixels per cell
+    #[default]
+    Half,
+    /// Quadrant blocks, 2x2 pixels per cell
+    Quadrant,
+    /// Sextant blocks, 2x3 pixels per cell (needs Unicode 13 fonts)
+    Sextant,
+    /// Braille dots, 2x4 pixels per cell, dithered
+    Braille,
+    /// Half blocks with the 256 colour palette
+    #[value(name = "256")]
+    Ansi256,
+    /// Half blocks with the 16 basic colours
+    #[value(name = "16")]
+    Ansi16,
+}
+
+impl std::fmt::Display for TextMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.to_possible_value().unwrap().get_name().fmt(f)
+    }
+}
+
+impl From<TextMode> for ascii_encoder::TextMode {
+    fn from(mode: TextMode) -> Self {
+        match mode {
+            TextMode::Half => ascii_encoder::TextMode::Half,
+            TextMode::Quadrant => ascii_encoder::TextMode::Quadrant,
+            TextMode::Sextant => ascii_encoder::TextMode::Sextant,
+            TextMode::Braille => ascii_encoder::TextMode::Braille,
+            TextMode::Ansi256 => ascii_encoder::TextMode::Ansi256,
+            TextMode::Ansi16 => ascii_encoder::TextMode::Ansi16,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum KindFilter {
+    /// Images, svgs and animations
+    Image,
+    Video,
+    /// Pdf, markdown, html, tex and typst
+    Doc,
+    /// Anything converted to markdown (code, office files, data..)
+    Text,
+}
+
+impl KindFilter {
+    pub fn matches(&self, kind: &McatKind) -> bool {
+        match self {
+            KindFilter::Image => matches!(
+                kind,
+                McatKind::Image | McatKind::Svg | McatKind::JpegXL | McatKind::Gif
+            ),
+            KindFilter::Video => *kind == McatKind::Video,
+            KindFilter::Doc => matches!(
+                kind,
+                McatKind::Pdf
+                    | McatKind::Markdown
+                    | McatKind::Html
+                    | McatKind::Tex
+                    | McatKind::Typst
+                    | McatKind::Mermaid
+                    | McatKind::Dot
+                    | McatKind::PlantUml
+                    | McatKind::D2
+            ),
+            KindFilter::Text => *kind == McatKind::PreMarkdown,
+        }
+    }
+}
+
+impl std::fmt::Display for KindFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.to_possible_value().unwrap().get_name().fmt(f)
+    }
+}
+
 #[derive(ValueEnum, Clone, Default, Debug)]
 pub enum SortMode {
     #[default]
@@ -615,6 +1017,63 @@ impl std::fmt::Display for SortMode {
     }
 }
 
+fn parse_file_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: u64 = num
+        .parse()
+        .map_err(|_| format!("expected a size like 500K or 10M, got {s:?}"))?;
+    let mult: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1024,
+        "m" | "mb" => 1024 * 1024,
+        "g" | "gb" => 1024 * 1024 * 1024,
+        _ => return Err(format!("unknown size unit {unit:?}, use K, M or G")),
+    };
+
+    num.checked_mul(mult)
+        .ok_or_else(|| "size too large".to_owned())
+}
+
+// a css length, in inches
+fn parse_length(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: f64 = num
+        .parse()
+        .map_err(|_| format!("expected a length like 15mm or 0.5in, got {s:?}"))?;
+    let per_inch = match unit.trim().to_lowercase().as_str() {
+        "in" => 1.0,
+        "cm" => 2.54,
+        "mm" => 25.4,
+        "pt" => 72.0,
+        "px" => 96.0,
+        "" if num == 0.0 => 1.0,
+        _ => {
+            return Err(format!(
+                "unknown length unit {unit:?}, use mm, cm, in, pt or px"
+            ));
+        }
+    };
+
+    Ok(num / per_inch)
+}
+
+fn parse_scale(s: &str) -> Result<f64, String> {
+    let scale: f64 = s
+        .trim()
+        .parse()
+        .map_err(|_| format!("expected a number like 1.5, got {s:?}"))?;
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(format!("the scale has to be above 0, got {s:?}"));
+    }
+    Ok(scale)
+}
+
 fn parse_syntax_map(s: &str) -> Result<(String, String), String> {
     let (ext, syn) = s
         .split_once(':')
@@ -628,3 +1087,61 @@ fn parse_syntax_map(s: &str) -> Result<(String, String), String> {
 
     Ok((ext, syn))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inches(s: &str) -> f64 {
+        (parse_length(s).unwrap() * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn lengths_convert_to_inches() {
+        assert_eq!(inches("1in"), 1.0);
+        assert_eq!(inches("25.4mm"), 1.0);
+        assert_eq!(inches("15mm"), 0.591);
+        assert_eq!(inches("2.54cm"), 1.0);
+        assert_eq!(inches("1cm"), 0.394);
+        assert_eq!(inches("36pt"), 0.5);
+        assert_eq!(inches("48px"), 0.5);
+        assert_eq!(inches(" 10 MM "), 0.394);
+        assert_eq!(inches("0"), 0.0);
+    }
+
+    #[test]
+    fn bad_lengths_are_errors() {
+        assert!(
+            parse_length("10")
+                .unwrap_err()
+                .contains("unknown length unit")
+        );
+        assert!(
+            parse_length("10em")
+                .unwrap_err()
+                .contains("unknown length unit")
+        );
+        assert!(
+            parse_length("mm")
+                .unwrap_err()
+                .contains("expected a length")
+        );
+        assert!(parse_length("-5mm").is_err());
+        assert!(parse_length("").is_err());
+    }
+
+    #[test]
+    fn scales_must_be_positive() {
+        assert_eq!(parse_scale("1.5"), Ok(1.5));
+        assert_eq!(parse_scale(" 2 "), Ok(2.0));
+        assert!(parse_scale("0").unwrap_err().contains("above 0"));
+        assert!(parse_scale("-1").unwrap_err().contains("above 0"));
+        assert!(parse_scale("inf").unwrap_err().contains("above 0"));
+        assert!(parse_scale("NaN").unwrap_err().contains("above 0"));
+        assert!(
+            parse_scale("big")
+                .unwrap_err()
+                .contains("expected a number")
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    fs,
+    io::Write,
+    process::{Command, Stdio},
+    sync::LazyLock,
+};
+
+use anyhow::{Context, Result};
+use layout::{
+    backends::svg::SVGWriter,
+    gv::{DotParser, GraphBuilder},
+};
+use regex::Regex;
+use tracing::debug;
+
+use crate::{charts::Chart, themes::CustomTheme};
+
+/// Turns the source of a diagram into an svg, in the colours of the theme.
+pub trait DiagramRenderer: Sync {
+    /// Fence info strings (and file extensions) it handles.
+    fn langs(&self) -> &'static [&'static str];
+
+    /// Whether it can run, external renderers need their tool on the PATH.
+    fn available(&self) -> bool {
+        true
+    }
+
+    fn render(&self, source: &str, theme: &CustomTheme) -> Result<String>;
+}
+
+/// Fonts for the svgs drawn here, resvg only knows what's installed.
+pub const FONTS: &str = "DejaVu Sans, Helvetica, Arial, sans-serif";
+
+/// Escapes text going into an svg's `<text>` elements.
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// the first available renderer for a lang wins, so external tools go before native fallbacks
+static RENDERERS: &[&dyn DiagramRenderer] = &[
+    &Mermaid, &Graphviz, &NativeDot, &PlantUml, &D2, &WaveDrom, &Charts,
+];
+
+/// The renderer for a fence info string, `None` when nothing available handles it.
+pub fn find(lang: &str) -> Option<&'static dyn DiagramRenderer> {
+    let lang = lang.trim().to_lowercase();
+    RENDERERS
+        .iter()
+        .copied()
+        .find(|r| r.langs().contains(&lang.as_str()) && r.available())
+}
+
+/// Renders a diagram with the renderer for `lang`.
+pub fn render(lang: &str, source: &str, theme: &CustomTheme) -> Result<String> {
+    let renderer = find(lang).with_context(|| {
+        format!("no renderer for {lang} diagrams, is its tool installed and on the PATH?")
+    })?;
+    renderer.render(source, theme)
+}
+
+struct Mermaid;
+
+impl DiagramRenderer for Mermaid {
+    fn langs(&self) -> &'static [&'static str] {
+        &["mermaid", "mmd"]
+    }
+
+    fn render(&self, source: &str, theme: &CustomTheme) -> Result<String> {
+        let mut opts = mermaid_rs_renderer::RenderOptions::modern();
+        opts.theme = theme.to_mermaid_theme();
+        mermaid_rs_renderer::render_with_options(source, opts)
+    }
+}
+
+/// Graphviz's own `dot`, the theme goes in as default graph / node / edge attributes.
+struct Graphviz;
+
+impl DiagramRenderer for Graphviz {
+    fn langs(&self) -> &'static [&'static str] {
+        &["dot", "graphviz", "gv"]
+    }
+
+    fn available(&self) -> bool {
+        which::which("dot").is_ok()
+    }
+
+    fn render(&self, source: &str, theme: &CustomTheme) -> Result<String> {
+        let fg = theme.foreground.hex();
+        let args = [
+            "-Tsvg".to_owned(),
+            "-Gbgcolor=transparent".to_owned(),
+            format!("-Gfontcolor=#{fg}"),
+            format!("-Ncolor=#{fg}"),
+            format!("-Nfontcolor=#{fg}"),
+            format!("-Ecolor=#{fg}"),
+            format!("-Efontcolor=#{fg}"),
+        ];
+        run_tool("dot", &args, source)
+    }
+}
+
+/// A pure rust layout for when graphviz isn't installed, it doesn't know every attribute.
+struct NativeDot;
+
+impl DiagramRenderer for NativeDot {
+    fn langs(&self) -> &'static [&'static str] {
+        &["dot", "graphviz", "gv"]
+    }
+
+    fn render(&self, source: &str, theme: &CustomTheme) -> Result<String> {
+        let mut parser = DotParser::new(source);
+        let graph = parser
+            .process()
+            .map_err(|e| anyhow::anyhow!("failed to parse dot: {e}"))?;
+        let mut builder = GraphBuilder::new();
+        builder.visit_graph(&graph);
+        let mut visual = builder.get();
+        anyhow::ensure!(visual.num_nodes() > 0, "the graph has no nodes");
+
+        let mut svg = SVGWriter::new();
+        visual.do_it(false, false, false, &mut svg);
+
+        // the writer draws in black on white, in times, with text and arrow heads left to the
+        // default fill
+        let fg = theme.foreground.hex();
+        Ok(svg
+            .finalize()
+            .replacen(
+                "<defs>",
+                &format!("<style>text {{ fill: #{fg}; }}</style>\n<defs>"),
+                1,
+            )
+            .replace("<polygon ", &format!("<polygon fill=\"#{fg}\" "))
+            .replace(
+                "font-family: Times, serif",
+                &format!("font-family: {FONTS}"),
+            )
+            .replace("#000000ff", &format!("#{fg}ff"))
+            .replace("#ffffffff", &format!("#{}ff", theme.surface.hex())))
+    }
+}
+
+struct PlantUml;
+
+impl DiagramRenderer for PlantUml {
+    fn langs(&self) -> &'static [&'static str] {
+        &["plantuml", "puml", "pu"]
+    }
+
+    fn available(&self) -> bool {
+        which::which("plantuml").is_ok()
+    }
+
+    fn render(&self, source: &str, theme: &CustomTheme) -> Result<String> {
+        let mut args = vec![
+            "-tsvg".to_owned(),
+            "-pipe".to_owned(),
+            "-SbackgroundColor=transparent".to_owned(),
+        ];
+        // the default look is dark text on pale boxes, which gets lost on a dark terminal
+        if theme.background.is_dark() {
+            args.extend(["-theme".to_owned(), "cyborg".to_owned()]);
+        }
+        // fences usually skip the @startuml / @enduml wrapper
+        if source.trim_start().starts_with("@start") {
+            run_tool("plantuml", &args, source)
+        } else {
+            run_tool(
+                "plantuml",
+                &args,
+                &format!("@startuml\n{source}\n@enduml\n"),
+            )
+        }
+    }
+}
+
+struct D2;
+
+impl DiagramRenderer for D2 {
+    fn langs(&self) -> &'static [&'static str] {
+        &["d2"]
+    }
+
+    fn available(&self) -> bool {
+        which::which("d2").is_ok()
+    }
+
+    fn render(&self, source: &str, theme: &CustomTheme) -> Result<String> {
+        // 200 is d2's "dark mauve", 0 its default light theme
+        let theme_id = if theme.background.is_dark() {
+            "200"
+        } else {
+            "0"
+        };
+        let args = [
+            format!("--theme={theme_id}"),
+            "-".to_owned(),
+            "-".to_owned(),
+        ];
+        run_tool("d2", &args, source)
+    }
+}
+
+/// WaveDrom timing diagrams, the cli only works on files.
+struct WaveDrom;
+
+impl DiagramRenderer for WaveDrom {
+    fn langs(&self) -> &'static [&'static str] {
+        &["wavedrom"]
+    }
+
+    fn available(&self) -> bool {
+        which::which("wavedrom-cli").is_ok()
+    }
+
+    fn render(&self, source: &str, theme: &CustomTheme) -> Result<String> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("wave.json");
+        let svg_path = dir.path().join("wave.svg");
+        fs::write(&input, source)?;
+        let output = Command::new("wavedrom-cli")
+            .arg("-i")
+            .arg(&input)
+            .arg("-s")
+            .arg(&svg_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .context("failed to run wavedrom-cli")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "wavedrom-cli failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(recolor_wavedrom(&fs::read_to_string(&svg_path)?, theme))
+    }
+}
+
+static WAVEDROM_BLACK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"#000000\b|#000\b|\bblack\b").unwrap());
+static WAVEDROM_WHITE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"#ffffff\b|#fff\b|\bwhite\b").unwrap());
+
+// the default skin draws black on a transparent background, with white filled bricks, and
+// leaves text to the default fill
+fn recolor_wavedrom(svg: &str, theme: &CustomTheme) -> String {
+    let fg = format!("#{}", theme.foreground.hex());
+    let surface = format!("#{}", theme.surface.hex());
+    let svg = WAVEDROM_BLACK_RE.replace_all(svg, fg.as_str());
+    let svg = WAVEDROM_WHITE_RE.replace_all(&svg, surface.as_str());
+    match svg
+        .find("<svg")
+        .and_then(|i| svg[i..].find('>').map(|end| i + end + 1))
+    {
+        Some(end) => format!(
+            "{}<style>text {{ fill: {fg}; }}</style>{}",
+            &svg[..end],
+            &svg[end..]
+        ),
+        None => svg.into_owned(),
+    }
+}
+
+/// Bar and line charts from csv or vega-lite fences, drawn here.
+struct Charts;
+
+impl DiagramRenderer for Charts {
+    fn langs(&self) -> &'static [&'static str] {
+        crate::charts::LANGS
+    }
+
+    fn render(&self, source: &str, theme: &CustomTheme) -> Result<String> {
+        Ok(Chart::parse(source)?.to_svg(theme))
+    }
+}
+
+// pipes the source into the tool and reads the svg from its stdout
+fn run_tool(program: &str, args: &[String], source: &str) -> Result<String> {
+    debug!(program, ?args, "rendering diagram");
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {program}"))?;
+    child
+        .stdin
+        .take()
+        .context("no stdin")?
+        .write_all(source.as_bytes())?;
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        anyhow::bail!(
+            "{program} failed to render the diagram:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Theme;
+
+    #[test]
+    fn wavedrom_takes_the_theme_colours() {
+        let theme = Theme::default().to_custom();
+        let fg = theme.foreground.hex();
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="10"><style>.s1{stroke:#000;fill:none}.s2{fill:#fff}.info{fill:#0041c4}</style><path stroke="black" fill="#000000"/><text>clk</text></svg>"##;
+        let svg = recolor_wavedrom(svg, &theme);
+        assert!(!svg.contains("#000;") && !svg.contains("black") && !svg.contains("#fff}"));
+        assert!(svg.contains(&format!("stroke:#{fg};")));
+        assert!(svg.contains(&format!("fill=\"#{fg}\"")));
+        assert!(svg.contains(&format!("<style>text {{ fill: #{fg}; }}</style>")));
+        // other colours are the skin's own
+        assert!(svg.contains("#0041c4"));
+    }
+}
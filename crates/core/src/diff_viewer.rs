@@ -0,0 +1,517 @@
+use std::{fmt::Write as _, io::Write, ops::Range};
+
+use anyhow::{Context, Result};
+use crossterm::tty::IsTty;
+use image::{DynamicImage, Rgba, RgbaImage, imageops::FilterType};
+use itertools::{EitherOrBoth, Itertools};
+use similar::{ChangeTag, DiffOp, DiffTag, InlineChange, TextDiff};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Color, Theme},
+    util::LinesWithEndings,
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    catter,
+    config::{ColorMode, DiffLayout, McatConfig},
+    markdown_viewer::{
+        render::RESET,
+        utils::{SYNTAXES, resolve_syntax},
+    },
+    mcat_file::{McatFile, McatKind},
+    themes::CustomTheme,
+};
+
+// unchanged lines shown around each change
+const CONTEXT: usize = 3;
+// narrower than this and side by side columns get too cramped to read
+const SIDE_BY_SIDE_WIDTH: u16 = 160;
+
+/// Compares two files. Images get a heatmap of the pixels that changed, anything else is
+/// converted to markdown and shown as a line diff.
+pub fn diff(
+    old: &McatFile,
+    new: &McatFile,
+    out: &mut impl Write,
+    config: &McatConfig,
+) -> Result<()> {
+    match (is_image(old), is_image(new)) {
+        (true, true) => diff_images(old, new, out, config),
+        (false, false) => diff_text(old, new, out, config),
+        _ => anyhow::bail!("can't diff an image against a document"),
+    }
+}
+
+fn is_image(file: &McatFile) -> bool {
+    matches!(
+        file.kind,
+        McatKind::Image | McatKind::Gif | McatKind::Svg | McatKind::JpegXL
+    )
+}
+
+fn label(file: &McatFile) -> String {
+    match (&file.path, &file.id) {
+        (Some(path), _) => path.display().to_string(),
+        (None, Some(id)) => id.clone(),
+        (None, None) => "stdin".to_owned(),
+    }
+}
+
+// markdownify wraps code in a single fence, diffing what's inside keeps its language
+fn to_text(file: &McatFile) -> Result<(String, String)> {
+    let md = file.to_markdown_input(false)?.convert()?;
+    let fenced = md
+        .strip_prefix("```")
+        .and_then(|rest| rest.split_once('\n'))
+        .and_then(|(lang, body)| Some((lang, body.strip_suffix("\n```")?)))
+        .filter(|(_, body)| !body.contains("\n```"));
+    let (lang, mut text) = match fenced {
+        Some((lang, body)) => (lang.to_owned(), body.to_owned()),
+        None => ("markdown".to_owned(), md),
+    };
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok((lang, text))
+}
+
+fn diff_text(
+    old: &McatFile,
+    new: &McatFile,
+    out: &mut impl Write,
+    config: &McatConfig,
+) -> Result<()> {
+    let wininfo = config
+        .wininfo
+        .as_ref()
+        .context("this is likely a bug, wininfo isn't set at the diff")?;
+    let (lang, old_text) = to_text(old)?;
+    let (_, new_text) = to_text(new)?;
+    let diff = TextDiff::from_lines(&old_text, &new_text);
+    if diff.ops().iter().all(|op| op.tag() == DiffTag::Equal) {
+        return Ok(());
+    }
+
+    let is_tty = std::io::stdout().is_tty();
+    let use_color = match config.color {
+        ColorMode::Never => false,
+        ColorMode::Always => true,
+        ColorMode::Auto => is_tty,
+    };
+    if !use_color {
+        let patch = diff
+            .unified_diff()
+            .context_radius(CONTEXT)
+            .header(&label(old), &label(new))
+            .to_string();
+        out.write_all(patch.as_bytes())?;
+        return Ok(());
+    }
+
+    let theme = config.theme.to_custom();
+    let syntect_theme = theme.to_syntect_theme();
+    let view = DiffView {
+        old: highlight(&old_text, &lang, &syntect_theme, config),
+        new: highlight(&new_text, &lang, &syntect_theme, config),
+        num_width: old_text
+            .lines()
+            .count()
+            .max(new_text.lines().count())
+            .to_string()
+            .len(),
+        width: wininfo.sc_width as usize,
+        theme,
+    };
+    let side_by_side = match config.diff_layout {
+        DiffLayout::Unified => false,
+        DiffLayout::SideBySide => true,
+        DiffLayout::Auto => wininfo.sc_width >= SIDE_BY_SIDE_WIDTH,
+    };
+
+    let mut content = view.header(&label(old), &label(new));
+    for group in diff.grouped_ops(CONTEXT) {
+        content.push_str(&view.hunk_header(&group));
+        for op in &group {
+            let changes = diff.iter_inline_changes(op).collect::<Vec<_>>();
+            if side_by_side {
+                content.push_str(&view.side_by_side(&changes));
+            } else {
+                content.push_str(&view.unified(&changes));
+            }
+        }
+    }
+    let content = wininfo.color_depth.downsample(&content);
+    catter::write_paged(&content, out, config, is_tty)
+}
+
+type Highlighted = Vec<Vec<(Color, String)>>;
+
+// every line highlighted up front, hunks skip lines and syntect needs to see them all
+fn highlight(text: &str, lang: &str, theme: &Theme, config: &McatConfig) -> Highlighted {
+    let mut highlighter =
+        HighlightLines::new(resolve_syntax(&config.syntax_map, lang, text), theme);
+    LinesWithEndings::from(text)
+        .map(|line| {
+            highlighter
+                .highlight_line(line, &SYNTAXES)
+                .map(|ranges| {
+                    ranges
+                        .into_iter()
+                        .map(|(style, piece)| (style.foreground, piece.to_owned()))
+                        .collect()
+                })
+                .unwrap_or_else(|_| vec![(theme_fg(theme), line.to_owned())])
+        })
+        .collect()
+}
+
+fn theme_fg(theme: &Theme) -> Color {
+    theme.settings.foreground.unwrap_or(Color::WHITE)
+}
+
+/// A run of a highlighted line, `changed` for words that differ from the other side.
+struct Piece {
+    fg: Color,
+    changed: bool,
+    text: String,
+}
+
+struct DiffView {
+    old: Highlighted,
+    new: Highlighted,
+    num_width: usize,
+    width: usize,
+    theme: CustomTheme,
+}
+
+impl DiffView {
+    fn header(&self, old: &str, new: &str) -> String {
+        format!(
+            "{}--- {old}{RESET}\n{}+++ {new}{RESET}\n",
+            self.theme.red.fg, self.theme.green.fg
+        )
+    }
+
+    fn hunk_header(&self, group: &[DiffOp]) -> String {
+        let range = |r: fn(&DiffOp) -> Range<usize>| {
+            let start = group.first().map_or(0, |op| r(op).start);
+            let end = group.last().map_or(0, |op| r(op).end);
+            format!("{},{}", start + 1, end - start)
+        };
+        format!(
+            "{}@@ -{} +{} @@{RESET}\n",
+            self.theme.cyan.fg,
+            range(DiffOp::old_range),
+            range(DiffOp::new_range)
+        )
+    }
+
+    fn unified(&self, changes: &[InlineChange<'_, str>]) -> String {
+        let text_width = self.width.saturating_sub(self.num_width * 2 + 4).max(1);
+        let mut buffer = String::new();
+        for change in changes {
+            let rows = self.rows(change, text_width);
+            let number = |n: Option<usize>| match n {
+                Some(n) => format!("{:>w$}", n + 1, w = self.num_width),
+                None => " ".repeat(self.num_width),
+            };
+            let gutter = format!(
+                "{}{} {} {}",
+                self.theme.comment.fg,
+                number(change.old_index()),
+                number(change.new_index()),
+                self.sign(change.tag())
+            );
+            let blank = " ".repeat(self.num_width * 2 + 3);
+            for (i, row) in rows.iter().enumerate() {
+                let gutter = if i == 0 { &gutter } else { &blank };
+                let _ = writeln!(buffer, "{gutter}{RESET} {row}");
+            }
+        }
+        buffer
+    }
+
+    fn side_by_side(&self, changes: &[InlineChange<'_, str>]) -> String {
+        let half = self.width.saturating_sub(1) / 2;
+        let text_width = half.saturating_sub(self.num_width + 3).max(1);
+        let (left, right): (Vec<_>, Vec<_>) = changes
+            .iter()
+            .partition(|c| c.tag() == ChangeTag::Delete || c.tag() == ChangeTag::Equal);
+        let pairs: Vec<_> = if left.iter().all(|c| c.tag() == ChangeTag::Equal) && right.is_empty()
+        {
+            // unchanged lines sit on both sides
+            left.iter().map(|c| EitherOrBoth::Both(*c, *c)).collect()
+        } else {
+            left.into_iter().zip_longest(right).collect()
+        };
+
+        let mut buffer = String::new();
+        let separator = format!("{}│{RESET}", self.theme.border.fg);
+        for pair in pairs {
+            let (old, new) = pair.map_any(Some, Some).or(None, None);
+            let left = self.column(old, change_old_index, text_width);
+            let right = self.column(new, change_new_index, text_width);
+            let empty = " ".repeat(half);
+            for row in left.iter().zip_longest(right.iter()) {
+                let (l, r) = row.or(&empty, &empty);
+                let _ = writeln!(buffer, "{l}{separator}{r}");
+            }
+        }
+        buffer
+    }
+
+    // one side of a side by side row, each row `num_width + 3 + text_width` wide
+    fn column(
+        &self,
+        change: Option<&InlineChange<'_, str>>,
+        index: fn(&InlineChange<'_, str>) -> Option<usize>,
+        text_width: usize,
+    ) -> Vec<String> {
+        let blank = " ".repeat(self.num_width + 2);
+        let Some(change) = change else {
+            return vec![format!("{blank} {}", " ".repeat(text_width))];
+        };
+        let number = index(change).map_or(" ".repeat(self.num_width), |n| {
+            format!("{:>w$}", n + 1, w = self.num_width)
+        });
+        let gutter = format!(
+            "{}{number} {}",
+            self.theme.comment.fg,
+            self.sign(change.tag())
+        );
+        self.rows(change, text_width)
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let gutter = if i == 0 { &gutter } else { &blank };
+                format!("{gutter}{RESET} {row}")
+            })
+            .collect()
+    }
+
+    fn sign(&self, tag: ChangeTag) -> String {
+        match tag {
+            ChangeTag::Delete => format!("{}-", self.theme.red.fg),
+            ChangeTag::Insert => format!("{}+", self.theme.green.fg),
+            ChangeTag::Equal => " ".to_owned(),
+        }
+    }
+
+    // the line wrapped to `width`, each row padded so its background spans the column
+    fn rows(&self, change: &InlineChange<'_, str>, width: usize) -> Vec<String> {
+        let (added, removed) = self.theme.diff_bgs();
+        let (added_word, removed_word) = self.theme.diff_word_bgs();
+        let (line_bg, word_bg) = match change.tag() {
+            ChangeTag::Delete => (removed, removed_word),
+            ChangeTag::Insert => (added, added_word),
+            ChangeTag::Equal => (String::new(), String::new()),
+        };
+
+        let mut rows = Vec::new();
+        let mut row = line_bg.clone();
+        let mut used = 0;
+        for piece in self.pieces(change) {
+            let bg = if piece.changed { &word_bg } else { &line_bg };
+            let fg = format!("\x1b[38;2;{};{};{}m", piece.fg.r, piece.fg.g, piece.fg.b);
+            row.push_str(&format!("{bg}{fg}"));
+            // tabs are 4 spaces that wrap like any other, wide chars a narrow column can't fit
+            // at all are cut down to an ellipsis
+            let chars = piece.text.chars().flat_map(|c| match c {
+                '\t' => vec![' '; 4],
+                c if c.width().unwrap_or(0) > width => vec!['…'],
+                c => vec![c],
+            });
+            for c in chars {
+                let w = c.width().unwrap_or(0);
+                if used + w > width {
+                    row.push_str(&format!(
+                        "{line_bg}{}{RESET}",
+                        " ".repeat(width.saturating_sub(used))
+                    ));
+                    rows.push(row);
+                    row = format!("{bg}{fg}");
+                    used = 0;
+                }
+                row.push(c);
+                used += w;
+            }
+        }
+        row.push_str(&format!(
+            "{line_bg}{}{RESET}",
+            " ".repeat(width.saturating_sub(used))
+        ));
+        rows.push(row);
+        rows
+    }
+
+    // the highlighted line, split where the changed words start and end
+    fn pieces(&self, change: &InlineChange<'_, str>) -> Vec<Piece> {
+        let highlighted = match change.tag() {
+            ChangeTag::Delete => change.old_index().and_then(|i| self.old.get(i)),
+            _ => change.new_index().and_then(|i| self.new.get(i)),
+        };
+        let mut changed = Vec::new();
+        for (emphasized, text) in change.iter_strings_lossy() {
+            changed.extend(std::iter::repeat_n(emphasized, text.len()));
+        }
+
+        let mut pieces: Vec<Piece> = Vec::new();
+        let mut offset = 0;
+        for (fg, text) in highlighted.into_iter().flatten() {
+            for (i, c) in text.char_indices() {
+                let is_changed = changed.get(offset + i).copied().unwrap_or(false);
+                if matches!(c, '\n' | '\r') {
+                    continue;
+                }
+                match pieces.last_mut() {
+                    Some(last) if last.fg == *fg && last.changed == is_changed => last.text.push(c),
+                    _ => pieces.push(Piece {
+                        fg: *fg,
+                        changed: is_changed,
+                        text: c.to_string(),
+                    }),
+                }
+            }
+            offset += text.len();
+        }
+        pieces
+    }
+}
+
+fn change_old_index(change: &InlineChange<'_, str>) -> Option<usize> {
+    change.old_index()
+}
+
+fn change_new_index(change: &InlineChange<'_, str>) -> Option<usize> {
+    change.new_index()
+}
+
+fn diff_images(
+    old: &McatFile,
+    new: &McatFile,
+    out: &mut impl Write,
+    config: &McatConfig,
+) -> Result<()> {
+    let old_img = old.to_image(config, false, false)?.to_rgba8();
+    let mut new_img = new.to_image(config, false, false)?.to_rgba8();
+    if new_img.dimensions() != old_img.dimensions() {
+        eprintln!(
+            "the images differ in size ({}x{} and {}x{}), comparing {} scaled to fit",
+            old_img.width(),
+            old_img.height(),
+            new_img.width(),
+            new_img.height(),
+            label(new),
+        );
+        new_img = image::imageops::resize(
+            &new_img,
+            old_img.width(),
+            old_img.height(),
+            FilterType::Triangle,
+        );
+    }
+
+    let (map, changed) = heatmap(&old_img, &new_img, &config.theme.to_custom());
+    let total = (map.width() as usize * map.height() as usize).max(1);
+    eprintln!(
+        "{changed} of {total} pixels differ ({:.2}%)",
+        changed as f64 * 100.0 / total as f64
+    );
+    let file = McatFile::from_image(DynamicImage::ImageRgba8(map), None, Some("diff".to_owned()));
+    catter::cat(vec![file], out, config)
+}
+
+/// Pixels that changed over a dimmed greyscale of the old image, from the theme's yellow for
+/// slight changes to its red for the biggest ones. Also returns how many changed.
+fn heatmap(old: &RgbaImage, new: &RgbaImage, theme: &CustomTheme) -> (RgbaImage, usize) {
+    let (low, high) = (theme.yellow.rgb(), theme.red.rgb());
+    let mut changed = 0;
+    let map = RgbaImage::from_fn(old.width(), old.height(), |x, y| {
+        let (a, b) = (old.get_pixel(x, y), new.get_pixel(x, y));
+        let luma =
+            (0.299 * a[0] as f32 + 0.587 * a[1] as f32 + 0.114 * a[2] as f32) * a[3] as f32 / 255.0;
+        let grey = luma * 0.35;
+        let delta =
+            a.0.iter()
+                .zip(b.0)
+                .map(|(p, q)| p.abs_diff(q))
+                .max()
+                .unwrap_or(0);
+        if delta == 0 {
+            let grey = grey.round() as u8;
+            return Rgba([grey, grey, grey, 255]);
+        }
+
+        changed += 1;
+        let t = delta as f32 / 255.0;
+        let strength = 0.4 + 0.6 * t;
+        let channel = |i: usize| {
+            let heat = low[i] as f32 + (high[i] as f32 - low[i] as f32) * t;
+            (grey + (heat - grey) * strength).round() as u8
+        };
+        Rgba([channel(0), channel(1), channel(2), 255])
+    });
+    (map, changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Theme, markdown_viewer::utils::string_len};
+
+    #[test]
+    fn code_fences_are_unwrapped() {
+        let file = McatFile::from_bytes(
+            b"fn main() {}".to_vec(),
+            None,
+            Some("rs".to_owned()),
+            None,
+            false,
+        )
+        .unwrap();
+        let (lang, text) = to_text(&file).unwrap();
+        assert_eq!(lang, "rs");
+        assert_eq!(text, "fn main() {}\n");
+    }
+
+    #[test]
+    fn narrow_columns_fit_tabs_and_wide_chars() {
+        let theme = Theme::default().to_custom();
+        let syntect_theme = theme.to_syntect_theme();
+        let config = McatConfig::default();
+        let (old, new) = ("a\n", "\t漢字b\n");
+        let view = DiffView {
+            old: highlight(old, "", &syntect_theme, &config),
+            new: highlight(new, "", &syntect_theme, &config),
+            num_width: 1,
+            width: 1,
+            theme,
+        };
+        let diff = TextDiff::from_lines(old, new);
+        for op in diff.ops() {
+            for change in diff.iter_inline_changes(op) {
+                for width in 1..=3 {
+                    for row in view.rows(&change, width) {
+                        assert!(string_len(&row) <= width, "{row:?} is wider than {width}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn heatmap_marks_changed_pixels() {
+        let old = RgbaImage::from_pixel(4, 4, Rgba([10, 10, 10, 255]));
+        let mut new = old.clone();
+        new.put_pixel(1, 2, Rgba([250, 10, 10, 255]));
+        let theme = Theme::default().to_custom();
+        let (map, changed) = heatmap(&old, &new, &theme);
+        assert_eq!(changed, 1);
+        // unchanged pixels are dimmed grey, the changed one leans towards the theme's red
+        let [r, g, b] = theme.red.rgb();
+        let hot = map.get_pixel(1, 2);
+        assert!(hot[0].abs_diff(r) < 40 && hot[1].abs_diff(g) < 40 && hot[2].abs_diff(b) < 40);
+        assert_eq!(map.get_pixel(0, 0)[0], map.get_pixel(0, 0)[1]);
+    }
+}
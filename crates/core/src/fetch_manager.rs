@@ -176,6 +176,13 @@ pub fn get_cache_path() -> PathBuf {
     base_dir.join("mcat")
 }
 
+/// Where state the user chose is kept, unlike the cache it's never evicted or cleaned.
+pub fn get_data_path() -> PathBuf {
+    let base_dir = dirs::data_dir().unwrap_or_else(std::env::temp_dir);
+
+    base_dir.join("mcat")
+}
+
 pub fn get_ffmpeg() -> Option<FfmpegCommand> {
     if ffmpeg_sidecar::command::ffmpeg_is_installed() {
         info!("using system ffmpeg");
@@ -0,0 +1,486 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Result;
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+
+use tracing::warn;
+
+use crate::{
+    config::{McatConfig, MdImageMode},
+    fetch_manager::get_data_path,
+    image_viewer::draw_frame,
+    markdown_viewer::{
+        self,
+        folding::{self, Fold},
+        render::{FAINT, RESET},
+    },
+    picker::fit,
+};
+
+enum Action {
+    Redraw,
+    Quit,
+    None,
+}
+
+struct FoldViewer {
+    config: McatConfig,
+    md: String,
+    path: Option<PathBuf>,
+    /// where the collapsed folds are persisted, `None` for stdin and urls
+    key: Option<String>,
+    folds: Vec<Fold>,
+    collapsed: Vec<bool>,
+    lines: Vec<String>,
+    /// the rendered line of every visible fold header, with its fold
+    headers: Vec<(usize, usize)>,
+    scroll: usize,
+    /// the fold that `Enter` toggles
+    selected: Option<usize>,
+}
+
+/// A scrollable view of rendered markdown where headings and `<details>` blocks fold.
+///
+/// `n`/`N` select the next and previous fold, `Enter` toggles it, `C` and `E` collapse and
+/// expand everything. what's collapsed is remembered per file.
+pub fn run(md: &str, path: Option<&Path>, out: &mut impl Write, config: McatConfig) -> Result<()> {
+    let mut viewer = FoldViewer::new(md, path, config)?;
+
+    enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+    let result = viewer.run(out);
+    execute!(out, Show, LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+
+    result
+}
+
+impl FoldViewer {
+    fn new(md: &str, path: Option<&Path>, config: McatConfig) -> Result<Self> {
+        let mut config = config;
+        // images don't survive being scrolled, they'd have to be encoded again on every frame
+        config.md_image = MdImageMode::None;
+
+        let path = path.map(|p| p.canonicalize().unwrap_or_else(|_| p.to_path_buf()));
+        let key = path.as_ref().map(|p| p.display().to_string());
+        let folds = folding::find_folds(md);
+        let saved = key
+            .as_deref()
+            .map(|key| load_folds(&folds_dir(), key))
+            .unwrap_or_default();
+        let collapsed = folds.iter().map(|f| saved.contains(&f.key)).collect();
+
+        let mut viewer = FoldViewer {
+            config,
+            md: md.to_owned(),
+            path,
+            key,
+            folds,
+            collapsed,
+            lines: Vec::new(),
+            headers: Vec::new(),
+            scroll: 0,
+            selected: None,
+        };
+        viewer.render()?;
+        viewer.selected = viewer.headers.first().map(|(_, fold)| *fold);
+        Ok(viewer)
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> Result<()> {
+        self.draw(out)?;
+        loop {
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Resize(width, height) => {
+                    self.resize(width, height)?;
+                    self.draw(out)?;
+                    continue;
+                }
+                _ => continue,
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match self.handle_key(key)? {
+                Action::Redraw => {}
+                Action::Quit => return Ok(()),
+                Action::None => continue,
+            }
+            self.draw(out)?;
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Result<Action> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let page = self.page_height() as isize;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(Action::Quit),
+            KeyCode::Char('c') if ctrl => return Ok(Action::Quit),
+
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_by(page),
+            KeyCode::PageUp | KeyCode::Char('b') => self.scroll_by(-page),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_by(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.scroll_by(isize::MAX / 2),
+
+            KeyCode::Tab | KeyCode::Char('n') => self.select_by(1),
+            KeyCode::BackTab | KeyCode::Char('N') => self.select_by(-1),
+            KeyCode::Enter | KeyCode::Char('o') => {
+                let Some(fold) = self.selected else {
+                    return Ok(Action::None);
+                };
+                self.collapsed[fold] = !self.collapsed[fold];
+                self.refold()?;
+            }
+            KeyCode::Char('C') => {
+                self.collapsed.fill(true);
+                self.refold()?;
+            }
+            KeyCode::Char('E') => {
+                self.collapsed.fill(false);
+                self.refold()?;
+            }
+            _ => return Ok(Action::None),
+        }
+        Ok(Action::Redraw)
+    }
+
+    /// Renders the markdown with the current folds and finds the header lines by their markers.
+    fn render(&mut self) -> Result<()> {
+        let source = folding::fold_source(&self.md, &self.folds, &self.collapsed);
+        let content =
+            markdown_viewer::md_to_ansi(&source, self.config.clone(), self.path.as_deref(), None)?;
+
+        self.lines.clear();
+        self.headers.clear();
+        let mut pending = None;
+        let mut skip_blank = false;
+        for line in content.lines() {
+            let text = strip_ansi_escapes::strip_str(line);
+            let text = text.trim();
+            if let Some(id) = text.strip_prefix(folding::MARKER) {
+                pending = id.parse::<usize>().ok();
+                skip_blank = true;
+                continue;
+            }
+            if text.is_empty() && std::mem::take(&mut skip_blank) {
+                continue;
+            }
+            skip_blank = false;
+            if !text.is_empty()
+                && let Some(fold) = pending.take()
+            {
+                self.headers.push((self.lines.len(), fold));
+            }
+            self.lines.push(line.to_owned());
+        }
+        Ok(())
+    }
+
+    /// Re-renders after the folds changed and remembers them.
+    fn refold(&mut self) -> Result<()> {
+        self.rerender()?;
+        self.save();
+        Ok(())
+    }
+
+    /// Re-renders at the new terminal size, the markdown is wrapped to the width.
+    fn resize(&mut self, width: u16, height: u16) -> Result<()> {
+        if let Some(wininfo) = self.config.wininfo.as_mut() {
+            wininfo.sc_width = width;
+            wininfo.sc_height = height;
+        }
+        self.rerender()
+    }
+
+    /// Renders again, keeping the selected header on the same screen row.
+    fn rerender(&mut self) -> Result<()> {
+        let row = self
+            .selected_line()
+            .map(|line| line.saturating_sub(self.scroll));
+        self.render()?;
+
+        // a fold hidden by collapsing its parent hands the selection to the parent
+        self.selected = self.selected.and_then(|selected| {
+            self.headers
+                .iter()
+                .map(|(_, fold)| *fold)
+                .filter(|fold| *fold <= selected)
+                .max()
+                .or_else(|| self.headers.first().map(|(_, fold)| *fold))
+        });
+        match (row, self.selected_line()) {
+            (Some(row), Some(line)) => self.scroll = line.saturating_sub(row),
+            _ => self.scroll = 0,
+        }
+        self.scroll_by(0);
+        Ok(())
+    }
+
+    fn save(&self) {
+        let Some(key) = &self.key else {
+            return;
+        };
+        let collapsed: Vec<String> = self
+            .folds
+            .iter()
+            .zip(&self.collapsed)
+            .filter(|(_, collapsed)| **collapsed)
+            .map(|(fold, _)| fold.key.clone())
+            .collect();
+        store_folds(&folds_dir(), key, &collapsed);
+    }
+
+    fn selected_line(&self) -> Option<usize> {
+        let selected = self.selected?;
+        self.headers
+            .iter()
+            .find(|(_, fold)| *fold == selected)
+            .map(|(line, _)| *line)
+    }
+
+    /// Scrolls, moving the selection onto the screen when it scrolled off.
+    fn scroll_by(&mut self, delta: isize) {
+        let max = self.lines.len().saturating_sub(self.page_height()) as isize;
+        self.scroll = (self.scroll as isize).saturating_add(delta).clamp(0, max) as usize;
+
+        let visible = self.scroll..self.scroll + self.page_height();
+        if self
+            .selected_line()
+            .is_some_and(|line| visible.contains(&line))
+        {
+            return;
+        }
+        if let Some((_, fold)) = self.headers.iter().find(|(line, _)| visible.contains(line)) {
+            self.selected = Some(*fold);
+        }
+    }
+
+    /// Selects the next or previous fold header, scrolling it into view.
+    fn select_by(&mut self, delta: isize) {
+        if self.headers.is_empty() {
+            return;
+        }
+        let current = self
+            .selected
+            .and_then(|selected| self.headers.iter().position(|(_, f)| *f == selected));
+        let next = match current {
+            Some(i) => (i as isize + delta).clamp(0, self.headers.len() as isize - 1) as usize,
+            None => 0,
+        };
+        let (line, fold) = self.headers[next];
+        self.selected = Some(fold);
+
+        let page = self.page_height();
+        if line < self.scroll || line >= self.scroll + page {
+            // a few lines of context above the header
+            self.scroll = line.saturating_sub(2);
+            self.scroll_by(0);
+        }
+    }
+
+    fn size(&self) -> (u16, u16) {
+        self.config
+            .wininfo
+            .as_ref()
+            .map(|w| (w.sc_width, w.sc_height.max(3)))
+            .unwrap_or((80, 24))
+    }
+
+    // the last row is the help
+    fn page_height(&self) -> usize {
+        let (_, height) = self.size();
+        (height - 1) as usize
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<()> {
+        let (width, height) = self.size();
+        let marker = &self.config.theme.to_custom().keyword.fg;
+        let selected_line = self.selected_line();
+
+        let mut buf: Vec<u8> = Vec::new();
+        for (row, line) in self
+            .lines
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(self.page_height())
+        {
+            let y = (row - self.scroll) as u16;
+            queue!(buf, MoveTo(0, y), Print(line), Print(RESET))?;
+            if selected_line == Some(row) {
+                queue!(buf, MoveTo(0, y), Print(format!("{marker}▌{RESET}")))?;
+            }
+        }
+
+        let position = match self.lines.len() {
+            0 => 100,
+            total => (self.scroll + self.page_height()).min(total) * 100 / total,
+        };
+        let title = self
+            .selected
+            .map(|fold| {
+                let triangle = if self.collapsed[fold] { "▶" } else { "▼" };
+                format!("{triangle} {}  ", self.folds[fold].title)
+            })
+            .unwrap_or_default();
+        let footer = fit(
+            &format!(
+                "{title}{position}%  [n/N] Select  [Enter] Toggle  [C/E] Collapse/Expand all  [j/k] Scroll  [q] Quit"
+            ),
+            width as usize,
+        );
+        queue!(
+            buf,
+            MoveTo(0, height - 1),
+            Print(format!("{FAINT}{footer}{RESET}"))
+        )?;
+
+        // the lines are downsampled already, the marker isn't
+        let frame = match &self.config.wininfo {
+            Some(wininfo) => wininfo
+                .color_depth
+                .downsample(&String::from_utf8_lossy(&buf))
+                .into_owned()
+                .into_bytes(),
+            None => buf,
+        };
+        draw_frame(out, frame)?;
+        Ok(())
+    }
+}
+
+fn folds_dir() -> PathBuf {
+    get_data_path().join("folds")
+}
+
+fn folds_file(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{:x}", md5::compute(key.as_bytes())))
+}
+
+/// The sections left collapsed, keyed by the file they were folded in.
+fn load_folds(dir: &Path, key: &str) -> Vec<String> {
+    fs::read_to_string(folds_file(dir, key))
+        .map(|folds| folds.lines().map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+fn store_folds(dir: &Path, key: &str, folds: &[String]) {
+    let path = folds_file(dir, key);
+    let result = if folds.is_empty() {
+        // nothing folded is the default, no need to keep a file around for it
+        fs::remove_file(&path).or_else(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        })
+    } else {
+        fs::create_dir_all(dir).and_then(|_| fs::write(&path, folds.join("\n")))
+    };
+
+    if let Err(e) = result {
+        warn!(error = %e, "failed to save the folded sections");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rasteroid::{
+        RasterEncoder, color::ColorDepth, multiplexer::Multiplexer, term_misc::Wininfo,
+    };
+
+    use super::*;
+
+    const DOC: &str = "# Title\n\n<details>\n<summary>More</summary>\n\nhidden\n\n</details>\n";
+
+    fn viewer(color_depth: ColorDepth) -> FoldViewer {
+        viewer_of(DOC, color_depth)
+    }
+
+    fn viewer_of(md: &str, color_depth: ColorDepth) -> FoldViewer {
+        let mut config = McatConfig::default();
+        config.encoder = Some(RasterEncoder::Ascii);
+        config.wininfo = Some(Wininfo {
+            sc_width: 60,
+            sc_height: 20,
+            spx_width: 1920,
+            spx_height: 1080,
+            multiplexer: Multiplexer::None,
+            needs_inline: false,
+            text_mode: Default::default(),
+            color_depth,
+        });
+        FoldViewer::new(md, None, config).unwrap()
+    }
+
+    // the styling in front of the summary's triangle
+    fn summary_style(viewer: &FoldViewer, triangle: &str) -> String {
+        let line = viewer
+            .lines
+            .iter()
+            .find(|line| line.contains(&format!("{triangle} More")))
+            .unwrap();
+        line[..line.find(triangle).unwrap()].to_owned()
+    }
+
+    #[test]
+    fn the_selection_marker_is_downsampled() {
+        let mut viewer = viewer(ColorDepth::Ansi16);
+        let mut out = Vec::new();
+        viewer.draw(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("▌"));
+        assert!(!out.contains("38;2;"));
+    }
+
+    #[test]
+    fn collapsed_details_keep_the_summary_style() {
+        let mut viewer = viewer(ColorDepth::TrueColor);
+        let open = summary_style(&viewer, "▼");
+        viewer.collapsed[1] = true;
+        viewer.render().unwrap();
+        assert_eq!(summary_style(&viewer, "▶"), open);
+        assert!(!viewer.lines.iter().any(|line| line.contains("hidden")));
+    }
+
+    #[test]
+    fn folds_are_stored_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().join("folds");
+        let folds = ["h1:Title".to_owned(), "details:More".to_owned()];
+        store_folds(&dir, "/a.md", &folds);
+        assert_eq!(load_folds(&dir, "/a.md"), folds);
+        assert!(load_folds(&dir, "/b.md").is_empty());
+
+        store_folds(&dir, "/a.md", &[]);
+        assert!(load_folds(&dir, "/a.md").is_empty());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn resizing_wraps_to_the_new_width() {
+        let md = format!("# Title\n\n{}\n", "word ".repeat(40));
+        let mut viewer = viewer_of(&md, ColorDepth::TrueColor);
+        let wide = viewer.lines.len();
+
+        viewer.resize(30, 20).unwrap();
+        assert!(viewer.lines.len() > wide);
+        assert_eq!(viewer.size(), (30, 20));
+        assert_eq!(viewer.selected_line(), Some(0));
+    }
+}
@@ -0,0 +1,310 @@
+use std::{fmt::Write, io::Cursor};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use exif::{Exif, In, Tag, Value};
+use image::{
+    DynamicImage, ExtendedColorType, ImageDecoder, ImageReader, Rgba, RgbaImage,
+    codecs::png::PngEncoder,
+};
+
+use crate::{
+    lsix,
+    mcat_file::{self, McatFile, McatKind},
+};
+
+const HISTOGRAM_WIDTH: u32 = 256;
+const HISTOGRAM_HEIGHT: u32 = 96;
+
+struct Decoded {
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+    icc: Option<Vec<u8>>,
+    exif: Option<Vec<u8>>,
+    img: DynamicImage,
+}
+
+impl Decoded {
+    fn new(mut decoder: impl ImageDecoder) -> Result<Self> {
+        let (width, height) = decoder.dimensions();
+        let color = decoder.original_color_type();
+        let icc = decoder.icc_profile().ok().flatten();
+        let exif = decoder.exif_metadata().ok().flatten();
+        let img = DynamicImage::from_decoder(decoder)?;
+        Ok(Self {
+            width,
+            height,
+            color,
+            icc,
+            exif,
+            img,
+        })
+    }
+}
+
+/// Replaces an image with its `--info` report, a markdown file so it goes through the usual
+/// output (rendered on a tty, plain markdown when piped).
+pub fn report_file(file: &McatFile) -> Result<McatFile> {
+    let report = report(file)?;
+    McatFile::from_bytes(
+        report.into_bytes(),
+        None,
+        Some("md".to_owned()),
+        file.id.clone(),
+        false,
+    )
+}
+
+/// Builds the markdown report of an image: format, dimensions, colour type, bit depth, frames,
+/// dpi, icc profile, exif fields and an rgb histogram.
+pub fn report(file: &McatFile) -> Result<String> {
+    let name = file
+        .path
+        .as_ref()
+        .and_then(|p| p.file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .or_else(|| file.id.clone())
+        .unwrap_or_else(|| "image".to_owned());
+    let bytes = file.bytes.as_slice();
+
+    let (format, decoded) = match file.kind {
+        McatKind::JpegXL => {
+            let decoder = jxl_oxide::integration::JxlDecoder::new(Cursor::new(bytes))?;
+            ("JpegXL".to_owned(), Decoded::new(decoder)?)
+        }
+        McatKind::Image | McatKind::Gif => {
+            let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+            let format = reader.format().context("unknown image format")?;
+            (format!("{format:?}"), Decoded::new(reader.into_decoder()?)?)
+        }
+        ref kind => anyhow::bail!("{name}: --info only works with images, got {kind:?}"),
+    };
+    let exif = decoded.exif.as_deref().and_then(parse_exif);
+
+    let mut md = String::new();
+    writeln!(md, "# {}\n", escape(&name))?;
+    writeln!(md, "| Property | Value |\n| --- | --- |")?;
+    let mut row = |key: &str, value: &str| writeln!(md, "| {key} | {} |", escape(value));
+    row("Format", &format)?;
+    row(
+        "Dimensions",
+        &format!("{}x{}", decoded.width, decoded.height),
+    )?;
+    row("Color type", &format!("{:?}", decoded.color))?;
+    let channels = decoded.color.channel_count().max(1) as u16;
+    row(
+        "Bit depth",
+        &format!(
+            "{} bits per channel",
+            decoded.color.bits_per_pixel() / channels
+        ),
+    )?;
+    if file.kind == McatKind::Gif
+        && let Ok(frames) = mcat_file::frame_count(bytes)
+    {
+        row("Frames", &frames.to_string())?;
+    }
+    if let Some((x, y)) = exif
+        .as_ref()
+        .and_then(exif_dpi)
+        .or_else(|| container_dpi(bytes))
+    {
+        row("DPI", &format!("{x:.0}x{y:.0}"))?;
+    }
+    if let Some(icc) = &decoded.icc {
+        let profile = icc_description(icc).unwrap_or_else(|| "unnamed".to_owned());
+        row("ICC profile", &profile)?;
+    }
+    row("File size", &lsix::format_size(bytes.len() as u64))?;
+
+    if let Some(exif) = &exif {
+        let rows = exif_rows(exif);
+        if !rows.is_empty() {
+            writeln!(md, "\n## EXIF\n\n| Field | Value |\n| --- | --- |")?;
+            for (key, value) in rows {
+                writeln!(md, "| {key} | {} |", escape(&value))?;
+            }
+        }
+    }
+
+    let mut png = Vec::new();
+    histogram(&decoded.img).write_with_encoder(PngEncoder::new(&mut png))?;
+    let png = base64::engine::general_purpose::STANDARD.encode(png);
+    writeln!(
+        md,
+        "\n## Histogram\n\n![histogram](data:image/png;base64,{png})"
+    )?;
+
+    Ok(md)
+}
+
+fn parse_exif(raw: &[u8]) -> Option<Exif> {
+    // jpeg keeps the app1 header in front of the tiff data
+    let raw = raw.strip_prefix(b"Exif\0\0").unwrap_or(raw);
+    exif::Reader::new().read_raw(raw.to_vec()).ok()
+}
+
+fn exif_rows(exif: &Exif) -> Vec<(&'static str, String)> {
+    let text = |tag: Tag| {
+        exif.get_field(tag, In::PRIMARY).map(|f| {
+            f.display_value()
+                .with_unit(exif)
+                .to_string()
+                .trim_matches('"')
+                .trim()
+                .to_owned()
+        })
+    };
+
+    let mut rows = Vec::new();
+    let camera = [text(Tag::Make), text(Tag::Model)]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !camera.is_empty() {
+        rows.push(("Camera", camera));
+    }
+    for (key, tag) in [
+        ("Lens", Tag::LensModel),
+        ("Exposure", Tag::ExposureTime),
+        ("Aperture", Tag::FNumber),
+        ("ISO", Tag::PhotographicSensitivity),
+        ("Focal length", Tag::FocalLength),
+        ("Taken", Tag::DateTimeOriginal),
+        ("Orientation", Tag::Orientation),
+    ] {
+        if let Some(value) = text(tag).filter(|v| !v.is_empty()) {
+            rows.push((key, value));
+        }
+    }
+
+    let coord = |tag: Tag, ref_tag: Tag| {
+        let Value::Rational(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+            return None;
+        };
+        let [deg, min, sec] = parts.as_slice() else {
+            return None;
+        };
+        let value = deg.to_f64() + min.to_f64() / 60.0 + sec.to_f64() / 3600.0;
+        let negative = text(ref_tag).is_some_and(|r| r.starts_with(['S', 'W']));
+        Some(if negative { -value } else { value })
+    };
+    if let (Some(lat), Some(lon)) = (
+        coord(Tag::GPSLatitude, Tag::GPSLatitudeRef),
+        coord(Tag::GPSLongitude, Tag::GPSLongitudeRef),
+    ) {
+        let mut gps = format!("{lat:.6}, {lon:.6}");
+        if let Some(alt) = text(Tag::GPSAltitude) {
+            gps.push_str(&format!(" ({alt})"));
+        }
+        rows.push(("GPS", gps));
+    }
+
+    rows
+}
+
+fn exif_dpi(exif: &Exif) -> Option<(f64, f64)> {
+    let rational = |tag: Tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(v) => v.first().map(|r| r.to_f64()),
+        _ => None,
+    };
+    let per_cm = exif
+        .get_field(Tag::ResolutionUnit, In::PRIMARY)
+        .and_then(|f| f.value.get_uint(0))
+        == Some(3);
+    let scale = if per_cm { 2.54 } else { 1.0 };
+    Some((
+        rational(Tag::XResolution)? * scale,
+        rational(Tag::YResolution)? * scale,
+    ))
+}
+
+// png's pHYs chunk or jpeg's JFIF header, for images without exif
+fn container_dpi(bytes: &[u8]) -> Option<(f64, f64)> {
+    match image::guess_format(bytes).ok()? {
+        image::ImageFormat::Png => {
+            let reader = png::Decoder::new(Cursor::new(bytes)).read_info().ok()?;
+            let dims = reader.info().pixel_dims?;
+            if dims.unit != png::Unit::Meter {
+                return None;
+            }
+            Some((dims.xppu as f64 * 0.0254, dims.yppu as f64 * 0.0254))
+        }
+        image::ImageFormat::Jpeg => {
+            let app0 = bytes.get(2..20)?;
+            if app0[..2] != [0xFF, 0xE0] || &app0[4..9] != b"JFIF\0" {
+                return None;
+            }
+            let x = u16::from_be_bytes([app0[12], app0[13]]) as f64;
+            let y = u16::from_be_bytes([app0[14], app0[15]]) as f64;
+            match app0[11] {
+                1 => Some((x, y)),
+                2 => Some((x * 2.54, y * 2.54)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// the `desc` tag of an icc profile, ascii in v2 profiles and utf-16 in v4 ones
+fn icc_description(icc: &[u8]) -> Option<String> {
+    let be32 = |b: &[u8], at: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+    let count = be32(icc, 128)?;
+    let entry = (0..count)
+        .map(|i| 132 + i * 12)
+        .find(|&at| icc.get(at..at + 4) == Some(b"desc"))?;
+    let (offset, size) = (be32(icc, entry + 4)?, be32(icc, entry + 8)?);
+    let tag = icc.get(offset..offset + size)?;
+
+    let text = match tag.get(..4)? {
+        b"desc" => {
+            let len = be32(tag, 8)?;
+            String::from_utf8_lossy(tag.get(12..12 + len)?).into_owned()
+        }
+        b"mluc" => {
+            let (len, at) = (be32(tag, 20)?, be32(tag, 24)?);
+            let units: Vec<u16> = tag
+                .get(at..at + len)?
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    let text = text.trim_end_matches('\0').trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+// overlapping r, g and b channels, where they overlap the colors add up
+fn histogram(img: &DynamicImage) -> RgbaImage {
+    let mut bins = [[0u64; 256]; 3];
+    for pixel in img.to_rgb8().pixels() {
+        for (channel, value) in pixel.0.iter().enumerate() {
+            bins[channel][*value as usize] += 1;
+        }
+    }
+    let max = bins.iter().flatten().copied().max().unwrap_or(0).max(1) as f64;
+
+    let mut out = RgbaImage::from_pixel(HISTOGRAM_WIDTH, HISTOGRAM_HEIGHT, Rgba([0, 0, 0, 0]));
+    for x in 0..HISTOGRAM_WIDTH {
+        for (channel, bin) in bins.iter().enumerate() {
+            let bar = (bin[x as usize] as f64 / max * HISTOGRAM_HEIGHT as f64).round() as u32;
+            for y in HISTOGRAM_HEIGHT - bar.min(HISTOGRAM_HEIGHT)..HISTOGRAM_HEIGHT {
+                let pixel = out.get_pixel_mut(x, y);
+                pixel.0[channel] = 255;
+                pixel.0[3] = 255;
+            }
+        }
+    }
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
@@ -0,0 +1,226 @@
+use std::{
+    fs::{self, Metadata},
+    io::{Read, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Result;
+use crossterm::tty::IsTty;
+use rayon::prelude::*;
+use tracing::info;
+
+use crate::{
+    config::McatConfig,
+    lsix::{self, LsEntry},
+    markdown_viewer::{
+        render::{BOLD_BLUE, FAINT, RESET},
+        utils::string_len,
+    },
+    mcat_file::{self, McatKind},
+};
+
+struct LongRow {
+    perms: String,
+    size: u64,
+    mtime: u64,
+    kind: String,
+    info: String,
+    icon: String,
+}
+
+/// Long version of [`lsix::lsix`], a row per entry with a small thumbnail and the metadata
+/// (size, time, permissions, kind, and dimensions / pages / duration where it makes sense).
+/// when piped it's tab separated, with raw sizes and unix times and without the thumbnails.
+pub fn long(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -> Result<()> {
+    let dir = Path::new(input.as_ref());
+    let is_tty = std::io::stdout().is_tty();
+    if is_tty {
+        lsix::set_inline_icon_size(&mut ctx)?;
+    }
+
+    let entries = lsix::read_entries(dir, &ctx);
+    info!(dir = %dir.display(), entry_count = entries.len(), "long listing");
+    let rows: Vec<LongRow> = entries
+        .par_iter()
+        .map(|entry| {
+            let meta = fs::metadata(&entry.path).ok();
+            let kind = detect_kind(entry);
+            LongRow {
+                perms: meta.as_ref().map(permissions).unwrap_or_default(),
+                size: meta.as_ref().map(|m| m.len()).unwrap_or(0),
+                mtime: meta
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                info: kind
+                    .as_ref()
+                    .and_then(|k| media_info(entry, k))
+                    .unwrap_or_default(),
+                kind: match &kind {
+                    Some(kind) => format!("{kind:?}"),
+                    None => "Dir".to_owned(),
+                },
+                icon: if is_tty {
+                    lsix::inline_icon(entry, &ctx)
+                } else {
+                    String::new()
+                },
+            }
+        })
+        .collect();
+
+    let mut buf = Vec::new();
+    if !is_tty {
+        for (entry, row) in entries.iter().zip(&rows) {
+            let info = if row.info.is_empty() { "-" } else { &row.info };
+            writeln!(
+                buf,
+                "{}\t{}\t{}\t{}\t{info}\t{}",
+                row.perms, row.size, row.mtime, row.kind, entry.filename
+            )?;
+        }
+        out.write_all(&buf)?;
+        out.flush()?;
+        return Ok(());
+    }
+
+    let kind_width = rows.iter().map(|r| r.kind.len()).max().unwrap_or(0);
+    let info_width = rows.iter().map(|r| string_len(&r.info)).max().unwrap_or(0);
+    for (entry, row) in entries.iter().zip(rows) {
+        let name = if entry.path.is_dir() {
+            format!("{BOLD_BLUE}{}/{RESET}", entry.filename)
+        } else {
+            entry.filename.clone()
+        };
+        writeln!(
+            buf,
+            "{FAINT}{}{RESET} {:>6} {FAINT}{}{RESET}  {:<kind_width$}  {:<info_width$}  {} {name}",
+            row.perms,
+            lsix::format_size(row.size),
+            format_time(row.mtime),
+            row.kind,
+            row.info,
+            row.icon,
+        )?;
+    }
+
+    out.write_all(&buf)?;
+    out.flush()?;
+    Ok(())
+}
+
+// only the start of the file is read, enough for the magic bytes
+fn detect_kind(entry: &LsEntry) -> Option<McatKind> {
+    if entry.path.is_dir() {
+        return None;
+    }
+    let mut head = Vec::with_capacity(8192);
+    if let Ok(file) = fs::File::open(&entry.path) {
+        let _ = file.take(8192).read_to_end(&mut head);
+    }
+    let ext = entry.path.extension().map(|e| e.to_string_lossy());
+    Some(McatKind::detect(Some(&head), ext.as_deref()).unwrap_or_default())
+}
+
+fn media_info(entry: &LsEntry, kind: &McatKind) -> Option<String> {
+    match kind {
+        McatKind::Image | McatKind::Gif => {
+            let (w, h) = image::ImageReader::open(&entry.path)
+                .ok()?
+                .with_guessed_format()
+                .ok()?
+                .into_dimensions()
+                .ok()?;
+            Some(format!("{w}x{h}"))
+        }
+        McatKind::Pdf => {
+            let bytes = fs::read(&entry.path).ok()?;
+            let pages = mcat_file::pdf_page_count(&bytes).ok()?;
+            Some(format!("{pages} page{}", if pages == 1 { "" } else { "s" }))
+        }
+        McatKind::Video => {
+            let secs = mcat_file::video_duration(&entry.path).ok()? as u64;
+            let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+            if h > 0 {
+                Some(format!("{h}:{m:02}:{s:02}"))
+            } else {
+                Some(format!("{m}:{s:02}"))
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn permissions(meta: &Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = meta.permissions().mode();
+    let kind = if meta.is_dir() { 'd' } else { '-' };
+    let bits: String = [
+        (0o400, 'r'),
+        (0o200, 'w'),
+        (0o100, 'x'),
+        (0o040, 'r'),
+        (0o020, 'w'),
+        (0o010, 'x'),
+        (0o004, 'r'),
+        (0o002, 'w'),
+        (0o001, 'x'),
+    ]
+    .iter()
+    .map(|(bit, c)| if mode & bit != 0 { *c } else { '-' })
+    .collect();
+    format!("{kind}{bits}")
+}
+
+#[cfg(not(unix))]
+fn permissions(meta: &Metadata) -> String {
+    let kind = if meta.is_dir() { 'd' } else { '-' };
+    let write = if meta.permissions().readonly() {
+        '-'
+    } else {
+        'w'
+    };
+    format!("{kind}r{write}")
+}
+
+// `YYYY-MM-DD HH:MM` in local time, marked ` UTC` where the local offset isn't known
+fn format_time(secs: u64) -> String {
+    let (secs, zone) = match utc_offset(secs) {
+        Some(offset) => (secs as i64 + offset, ""),
+        None => (secs as i64, " UTC"),
+    };
+    let days = secs.div_euclid(86400);
+    let (hour, minute) = (secs.rem_euclid(86400) / 3600, secs.rem_euclid(3600) / 60);
+
+    // days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{year}-{month:02}-{day:02} {hour:02}:{minute:02}{zone}")
+}
+
+// the offset in effect at that moment (dst included), from the TZ / localtime the libc sees
+#[cfg(unix)]
+fn utc_offset(secs: u64) -> Option<i64> {
+    let time = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::localtime_r(&time, &mut tm) };
+    (!res.is_null()).then_some(tm.tm_gmtoff as i64)
+}
+
+#[cfg(not(unix))]
+fn utc_offset(_secs: u64) -> Option<i64> {
+    None
+}
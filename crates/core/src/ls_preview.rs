@@ -0,0 +1,254 @@
+use std::{
+    fmt::Write,
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use image::DynamicImage;
+use lzma_rust2::XzReader;
+use markdownify::archives::{self, FileTree};
+use rasteroid::{RasterEncoder, image_extended::InlineImage, term_misc::SizeDirection};
+use syntect::{easy::HighlightLines, highlighting::Color, util::LinesWithEndings};
+
+use crate::{
+    config::McatConfig,
+    diagrams::escape_xml,
+    lsix::LsEntry,
+    markdown_viewer::utils::{SYNTAXES, resolve_syntax},
+    mcat_file::{self, McatFile, McatKind},
+};
+
+const COLS: usize = 48;
+const LINES: usize = 24;
+const FONT_SIZE: f32 = 14.0;
+const LINE_HEIGHT: f32 = 18.0;
+const PADDING: f32 = 12.0;
+const FONTS: &str = "DejaVu Sans Mono, Menlo, Consolas, Liberation Mono, Courier New, monospace";
+// only the start of a text file is ever shown
+const TEXT_HEAD: usize = 64 * 1024;
+// more than a thumbnail fits, the listing stops there
+const ARCHIVE_ENTRIES: usize = 64;
+
+// every document is rendered by its own chromium, so `ls` renders them one at a time
+static DOCUMENT_LOCK: Mutex<()> = Mutex::new(());
+
+/// The preview `ls` renders for files that aren't images.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preview {
+    /// office documents, markdown and html, rendered the way `mcat -o image` does.
+    /// only with `--ls-docs`
+    Document,
+    /// a small tree of the files inside
+    Archive,
+    /// the start of the file, syntax highlighted
+    Text,
+}
+
+impl Preview {
+    /// Picks the preview from the extension, text files are confirmed once read.
+    /// `None` when previews are off or it's a document and `--ls-docs` isn't set.
+    pub fn from_entry(entry: &LsEntry, ctx: &McatConfig) -> Option<Self> {
+        if ctx.ls_icons {
+            return None;
+        }
+        Self::from_ext(&entry.ext).filter(|p| *p != Self::Document || ctx.ls_docs)
+    }
+
+    pub fn from_ext(ext: &str) -> Option<Self> {
+        match ext {
+            "IAMADIR" => None,
+            "docx" | "docm" | "dotx" | "dotm" | "pptx" | "pptm" | "potx" | "potm" | "ppsx"
+            | "ppsm" | "odt" | "ott" | "odm" | "oth" | "odp" | "otp" | "ods" | "ots" | "xlsx"
+            | "xls" | "xlsm" | "csv" | "tsv" => Some(Self::Document),
+            "zip" | "tar" | "tgz" | "txz" | "gz" | "xz" => Some(Self::Archive),
+            ext => match McatKind::from_ext(ext) {
+                Some(McatKind::Markdown | McatKind::Html) => Some(Self::Document),
+                None => Some(Self::Text),
+                Some(_) => None,
+            },
+        }
+    }
+}
+
+/// Renders the preview of `file`, cropped from the top so it fills the thumbnail.
+pub fn render(file: &McatFile, preview: Preview, ctx: &McatConfig) -> Result<DynamicImage> {
+    let img = match preview {
+        Preview::Document => {
+            let _lock = DOCUMENT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            file.to_image(ctx, false, false)?
+        }
+        Preview::Archive => {
+            let path = file
+                .path
+                .as_deref()
+                .context("archives are listed from their file")?;
+            return render_archive(path, ctx);
+        }
+        Preview::Text => {
+            let head = &file.bytes[..file.bytes.len().min(TEXT_HEAD)];
+            anyhow::ensure!(!head.contains(&0), "not a text file");
+            let text = String::from_utf8_lossy(head);
+            text_to_image(&text, file.ext.as_deref().unwrap_or(""), ctx)?
+        }
+    };
+
+    fit_top(img, ctx)
+}
+
+/// Renders the file tree of the archive at `path`, without reading it whole.
+pub fn render_archive(path: &Path, ctx: &McatConfig) -> Result<DynamicImage> {
+    let mut tree = FileTree::default();
+    for name in list_archive(path)? {
+        tree.add_file(name, String::new());
+    }
+    fit_top(text_to_image(&tree.tree(), "", ctx)?, ctx)
+}
+
+// a zip is listed from its central directory, tars are streamed through the decompressor
+fn list_archive(path: &Path) -> Result<Vec<String>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 8];
+    let read = file.read(&mut magic)?;
+    file.rewind()?;
+    let magic = &magic[..read];
+
+    let names = if infer::archive::is_zip(magic) {
+        archives::list_zip(BufReader::new(file), ARCHIVE_ENTRIES)?
+    } else if infer::archive::is_gz(magic) {
+        archives::list_tar(GzDecoder::new(BufReader::new(file)), ARCHIVE_ENTRIES)?
+    } else if infer::archive::is_xz(magic) {
+        archives::list_tar(XzReader::new(BufReader::new(file), true), ARCHIVE_ENTRIES)?
+    } else {
+        archives::list_tar(BufReader::new(file), ARCHIVE_ENTRIES)?
+    };
+    Ok(names)
+}
+
+// a page of highlighted text, drawn as an svg so it stays sharp at any thumbnail size
+fn text_to_image(text: &str, ext: &str, ctx: &McatConfig) -> Result<DynamicImage> {
+    let wininfo = ctx
+        .wininfo
+        .as_ref()
+        .context("this is likely a bug, wininfo wasn't set at the ls preview")?;
+    let theme = ctx.theme.to_custom().to_syntect_theme();
+    let fg = theme.settings.foreground.unwrap_or(Color::WHITE);
+    let bg = theme.settings.background.unwrap_or(Color::BLACK);
+    let mut highlighter = HighlightLines::new(resolve_syntax(&ctx.syntax_map, ext, text), &theme);
+
+    let mut body = String::new();
+    for (i, line) in LinesWithEndings::from(text).take(LINES).enumerate() {
+        let y = PADDING + (i + 1) as f32 * LINE_HEIGHT - (LINE_HEIGHT - FONT_SIZE);
+        write!(body, r#"<text x="{PADDING}" y="{y}" xml:space="preserve">"#)?;
+        let mut cols = 0;
+        for (style, piece) in highlighter.highlight_line(line, &SYNTAXES)? {
+            let piece: String = piece
+                .trim_end_matches(['\n', '\r'])
+                .replace('\t', "    ")
+                .chars()
+                .take(COLS - cols)
+                .collect();
+            if piece.is_empty() {
+                continue;
+            }
+            cols += piece.chars().count();
+            write!(
+                body,
+                r#"<tspan fill="{}">{}</tspan>"#,
+                hex(style.foreground),
+                escape_xml(&piece)
+            )?;
+        }
+        body.push_str("</text>");
+    }
+
+    let width = PADDING * 2.0 + COLS as f32 * FONT_SIZE * 0.6;
+    let height = PADDING * 2.0 + LINES as f32 * LINE_HEIGHT;
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}"><rect width="100%" height="100%" rx="8" fill="{}"/><g font-family="{FONTS}" font-size="{FONT_SIZE}" fill="{}">{body}</g></svg>"#,
+        hex(bg),
+        hex(fg),
+    );
+    mcat_file::svg_to_image(svg.as_bytes(), wininfo, None, None, false, false, false)
+}
+
+// keeps the top of the page, as much of it as fits the thumbnail's aspect ratio
+fn fit_top(img: DynamicImage, ctx: &McatConfig) -> Result<DynamicImage> {
+    let wininfo = ctx
+        .wininfo
+        .as_ref()
+        .context("this is likely a bug, wininfo wasn't set at the ls preview")?;
+    let box_width = wininfo.dim_to_px(&ctx.img_width, SizeDirection::Width)? as f32;
+    let box_height = wininfo.dim_to_px(&ctx.img_height, SizeDirection::Height)? as f32;
+    let height = (img.width() as f32 * box_height / box_width.max(1.0)).round() as u32;
+    let img = img.crop_imm(0, 0, img.width(), height.clamp(1, img.height()));
+
+    let is_ascii = ctx.encoder == Some(RasterEncoder::Ascii);
+    Ok(img.resize_plus(
+        wininfo,
+        Some(&ctx.img_width),
+        Some(&ctx.img_height),
+        is_ascii,
+        true,
+    )?)
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+
+    fn tar_gz(files: usize) -> tempfile::NamedTempFile {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for i in 0..files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("dir/file{i}.txt"), &b"data"[..])
+                .unwrap();
+        }
+        let bytes = builder.into_inner().unwrap().finish().unwrap();
+        let mut file = tempfile::Builder::new().suffix(".tgz").tempfile().unwrap();
+        file.write_all(&bytes).unwrap();
+        file
+    }
+
+    #[test]
+    fn archives_list_only_what_fits() {
+        let small = tar_gz(3);
+        assert_eq!(
+            list_archive(small.path()).unwrap(),
+            ["dir/file0.txt", "dir/file1.txt", "dir/file2.txt"]
+        );
+        let big = tar_gz(ARCHIVE_ENTRIES * 2);
+        assert_eq!(list_archive(big.path()).unwrap().len(), ARCHIVE_ENTRIES);
+    }
+
+    #[test]
+    fn documents_need_ls_docs() {
+        let entry = LsEntry::new("notes.md".into());
+        let mut ctx = McatConfig::default();
+        assert_eq!(Preview::from_entry(&entry, &ctx), None);
+        ctx.ls_docs = true;
+        assert_eq!(Preview::from_entry(&entry, &ctx), Some(Preview::Document));
+        ctx.ls_icons = true;
+        assert_eq!(Preview::from_entry(&entry, &ctx), None);
+
+        let entry = LsEntry::new("data.tgz".into());
+        assert_eq!(
+            Preview::from_entry(&entry, &McatConfig::default()),
+            Some(Preview::Archive)
+        );
+    }
+}
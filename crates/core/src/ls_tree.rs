@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use ignore::WalkBuilder;
+use rayon::prelude::*;
+use tracing::{info, warn};
+
+use crate::{
+    config::McatConfig,
+    lsix::{self, LsEntry},
+    markdown_viewer::render::{BOLD_BLUE, FAINT, RESET},
+};
+
+struct TreeLine<'a> {
+    prefix: String,
+    entry: &'a LsEntry,
+}
+
+/// Tree version of [`lsix::lsix`], every entry gets a small inline thumbnail and folders show
+/// the total size of what's in them. respects gitignore, and `--max-depth` limits what's shown
+/// (sizes still count everything below).
+pub fn tree(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -> Result<()> {
+    let root = Path::new(input.as_ref());
+    lsix::set_inline_icon_size(&mut ctx)?;
+
+    let walker = WalkBuilder::new(root)
+        .standard_filters(!ctx.hidden)
+        .hidden(!ctx.hidden)
+        .follow_links(true)
+        .build();
+
+    let mut children: HashMap<PathBuf, Vec<LsEntry>> = HashMap::new();
+    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!(error = %e, "failed to walk entry");
+                continue;
+            }
+        };
+        let path = entry.path();
+        if path == root {
+            continue;
+        }
+        let Some(parent) = path.parent() else {
+            continue;
+        };
+        if entry.file_type().is_some_and(|t| t.is_file()) {
+            let len = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            for ancestor in path.ancestors() {
+                *sizes.entry(ancestor.to_path_buf()).or_default() += len;
+                if ancestor == root {
+                    break;
+                }
+            }
+        }
+        children
+            .entry(parent.to_path_buf())
+            .or_default()
+            .push(LsEntry::new(path.to_path_buf()));
+    }
+    for entries in children.values_mut() {
+        lsix::sort_entries(entries, &ctx);
+    }
+
+    let mut lines = Vec::new();
+    collect_lines(root, &children, String::new(), 1, ctx.max_depth, &mut lines);
+    info!(dir = %root.display(), entry_count = lines.len(), "listing tree");
+
+    let icons: Vec<String> = lines
+        .par_iter()
+        .map(|line| lsix::inline_icon(line.entry, &ctx))
+        .collect();
+
+    let size_of = |path: &Path| lsix::format_size(sizes.get(path).copied().unwrap_or(0));
+    let mut buf = Vec::new();
+    writeln!(
+        buf,
+        "{BOLD_BLUE}{}{RESET}  {FAINT}{}{RESET}",
+        root.display(),
+        size_of(root)
+    )?;
+
+    let (mut dirs, mut files) = (0, 0);
+    for (line, icon) in lines.iter().zip(icons) {
+        let is_dir = line.entry.path.is_dir();
+        let name = if is_dir {
+            dirs += 1;
+            format!("{BOLD_BLUE}{}/{RESET}", line.entry.filename)
+        } else {
+            files += 1;
+            line.entry.filename.clone()
+        };
+        writeln!(
+            buf,
+            "{FAINT}{}{RESET}{icon} {name}  {FAINT}{}{RESET}",
+            line.prefix,
+            size_of(&line.entry.path)
+        )?;
+    }
+    writeln!(buf, "\n{dirs} directories, {files} files")?;
+
+    out.write_all(&buf)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn collect_lines<'a>(
+    dir: &Path,
+    children: &'a HashMap<PathBuf, Vec<LsEntry>>,
+    prefix: String,
+    depth: usize,
+    max_depth: Option<usize>,
+    lines: &mut Vec<TreeLine<'a>>,
+) {
+    let Some(entries) = children.get(dir) else {
+        return;
+    };
+
+    for (idx, entry) in entries.iter().enumerate() {
+        let is_last = idx == entries.len() - 1;
+        let connector = if is_last { "└── " } else { "├── " };
+        lines.push(TreeLine {
+            prefix: format!("{prefix}{connector}"),
+            entry,
+        });
+
+        if max_depth.is_none_or(|max| depth < max) {
+            let extension = if is_last { "    " } else { "│   " };
+            collect_lines(
+                &entry.path,
+                children,
+                format!("{prefix}{extension}"),
+                depth + 1,
+                max_depth,
+                lines,
+            );
+        }
+    }
+}
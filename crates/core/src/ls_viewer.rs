@@ -0,0 +1,598 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use image::DynamicImage;
+use rasteroid::{Encoder, RasterEncoder, term_misc::SizeDirection};
+use rayon::prelude::*;
+use tracing::info;
+
+use crate::{
+    catter::{Pager, interact_with_image},
+    config::{McatConfig, SortMode},
+    image_viewer::draw_frame,
+    lsix::{self, LsEntry, LsLayout},
+    markdown_viewer::{
+        self,
+        render::{FAINT, RESET, REVERSE},
+    },
+    mcat_file::{McatFile, McatKind},
+    picker::fit,
+};
+
+enum Action {
+    Redraw,
+    Open,
+    Parent,
+    Quit,
+    None,
+}
+
+struct LsViewer {
+    config: McatConfig,
+    /// same as config, with the thumbnail size applied
+    thumb_config: McatConfig,
+    layout: LsLayout,
+    thumb_height: u16,
+    dir: PathBuf,
+    entries: Vec<LsEntry>,
+    cursor: usize,
+    /// `None` when the thumbnail failed to render
+    thumbnails: HashMap<PathBuf, Option<DynamicImage>>,
+    /// encoded rows, by row index
+    rows: HashMap<usize, Vec<u8>>,
+    status: Option<String>,
+}
+
+/// Interactive version of [`lsix::lsix`], a navigable thumbnail grid.
+///
+/// `Enter` descends into folders and opens files in the interactive viewer (or the pager for
+/// documents), `Backspace` goes up. only the thumbnails of the visible page are rendered.
+pub fn run(input: impl AsRef<str>, out: &mut impl Write, config: McatConfig) -> Result<()> {
+    let mut viewer = LsViewer::new(Path::new(input.as_ref()), config)?;
+
+    enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+    let result = viewer.run(out);
+    execute!(out, Show, LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+
+    result
+}
+
+impl LsViewer {
+    fn new(dir: &Path, config: McatConfig) -> Result<Self> {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+
+        let mut thumb_config = config.clone();
+        let layout = LsLayout::new(&mut thumb_config)?;
+        let wininfo = config
+            .wininfo
+            .as_ref()
+            .context("this is likely a bug, wininfo wasn't set at the ls viewer")?;
+        let thumb_height = wininfo
+            .dim_to_cells(&config.ls_height, SizeDirection::Height)?
+            .max(1) as u16;
+
+        Ok(LsViewer {
+            entries: lsix::read_entries(&dir, &config),
+            config,
+            thumb_config,
+            layout,
+            thumb_height,
+            dir,
+            cursor: 0,
+            thumbnails: HashMap::new(),
+            rows: HashMap::new(),
+            status: None,
+        })
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> Result<()> {
+        self.draw(out)?;
+        loop {
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            self.status = None;
+            match self.handle_key(key) {
+                Action::Redraw => {}
+                Action::Open => self.open_selected(out)?,
+                Action::Parent => {
+                    let Some(parent) = self.dir.parent().map(Path::to_path_buf) else {
+                        continue;
+                    };
+                    let from = std::mem::replace(&mut self.dir, parent);
+                    self.reload(Some(&from));
+                }
+                Action::Quit => return Ok(()),
+                Action::None => continue,
+            }
+            self.draw(out)?;
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let per_row = self.layout.items_per_row as isize;
+        let per_page = self.items_per_page() as isize;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Char('c') if ctrl => return Action::Quit,
+            KeyCode::Enter => return Action::Open,
+            KeyCode::Backspace | KeyCode::Char('-') => return Action::Parent,
+
+            KeyCode::Left | KeyCode::Char('h') => self.move_cursor(-1),
+            KeyCode::Right | KeyCode::Char('l') => self.move_cursor(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-per_row),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(per_row),
+            KeyCode::PageUp => self.move_cursor(-per_page),
+            KeyCode::PageDown => self.move_cursor(per_page),
+            KeyCode::Home | KeyCode::Char('g') => self.cursor = 0,
+            KeyCode::End | KeyCode::Char('G') => self.move_cursor(isize::MAX / 2),
+
+            KeyCode::Char('s') => {
+                self.config.sort = match self.config.sort {
+                    SortMode::Name => SortMode::Size,
+                    SortMode::Size => SortMode::Time,
+                    SortMode::Time => SortMode::Type,
+                    SortMode::Type => SortMode::Name,
+                };
+                self.reload(None);
+            }
+            KeyCode::Char('r') => {
+                self.config.reverse = !self.config.reverse;
+                self.reload(None);
+            }
+            KeyCode::Char('.') => {
+                self.config.hidden = !self.config.hidden;
+                self.reload(None);
+            }
+            _ => return Action::None,
+        }
+        Action::Redraw
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let max = self.entries.len().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize).saturating_add(delta).clamp(0, max) as usize;
+    }
+
+    /// Re-reads the current folder, keeping the cursor on `focus` (or the current entry).
+    fn reload(&mut self, focus: Option<&Path>) {
+        let focus = focus
+            .map(Path::to_path_buf)
+            .or_else(|| self.entries.get(self.cursor).map(|e| e.path.clone()));
+
+        self.entries = lsix::read_entries(&self.dir, &self.config);
+        self.rows.clear();
+        self.cursor = focus
+            .and_then(|focus| self.entries.iter().position(|e| e.path == focus))
+            .unwrap_or(0);
+        info!(dir = %self.dir.display(), entry_count = self.entries.len(), "listing directory");
+    }
+
+    fn open_selected(&mut self, out: &mut impl Write) -> Result<()> {
+        let Some(entry) = self.entries.get(self.cursor) else {
+            return Ok(());
+        };
+        if entry.path.is_dir() {
+            self.dir = entry.path.clone();
+            self.reload(None);
+            return Ok(());
+        }
+
+        let path = entry.path.clone();
+        self.clear_kitty_images(out)?;
+        if let Err(e) = self.open_file(&path, out) {
+            self.status = Some(format!("{}: {e}", path.display()));
+        }
+        // the viewers leave raw mode on their way out, and the pager the alternate screen
+        enable_raw_mode()?;
+        restore_screen(out)?;
+        Ok(())
+    }
+
+    fn open_file(&self, path: &Path, out: &mut impl Write) -> Result<()> {
+        let file = McatFile::from_path(path, true)?;
+        match file.kind {
+            McatKind::Video => anyhow::bail!("videos can't be opened from the grid"),
+            McatKind::PreMarkdown | McatKind::Markdown | McatKind::Html => {
+                let md = file
+                    .to_markdown_input(self.config.inline_images_in_md)?
+                    .convert()?;
+                let content =
+                    markdown_viewer::md_to_ansi(&md, self.config.clone(), Some(path), None)?;
+                match Pager::new(&self.config.pager) {
+                    Some(pager) => {
+                        disable_raw_mode()?;
+                        pager.page(&content)?;
+                    }
+                    None => self.show_text(&content, out)?,
+                }
+            }
+            _ => {
+                let images = file.to_album(&self.config)?;
+                interact_with_image(images, &self.config, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows the first screen of `content` until a key is pressed, for when there's no pager.
+    fn show_text(&self, content: &str, out: &mut impl Write) -> Result<()> {
+        let (width, height) = self.size();
+        let mut buf = Vec::new();
+        for (i, line) in content.lines().take(height as usize - 1).enumerate() {
+            queue!(buf, MoveTo(0, i as u16), Print(line), Print(RESET))?;
+        }
+        let help = fit("pager not found, press any key to go back", width as usize);
+        queue!(
+            buf,
+            MoveTo(0, height - 1),
+            Print(format!("{FAINT}{help}{RESET}"))
+        )?;
+        draw_frame(out, buf)?;
+
+        loop {
+            if let Event::Key(key) = event::read()?
+                && key.kind != KeyEventKind::Release
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    fn clear_kitty_images(&self, out: &mut impl Write) -> Result<()> {
+        // kitty images survive the screen clear
+        if self.config.encoder == Some(RasterEncoder::Kitty)
+            && let Some(wininfo) = &self.config.wininfo
+        {
+            out.write_all(wininfo.multiplexer.wrap("\x1b_Ga=d,d=A\x1b\\").as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> (u16, u16) {
+        self.config
+            .wininfo
+            .as_ref()
+            .map(|w| (w.sc_width, w.sc_height.max(3)))
+            .unwrap_or((80, 24))
+    }
+
+    // a row is the thumbnails, the names and then the padding
+    fn row_height(&self) -> u16 {
+        self.thumb_height + 1 + self.layout.y_padding
+    }
+
+    // first row is the header, last one the help
+    fn rows_per_page(&self) -> usize {
+        let (_, height) = self.size();
+        ((height - 2) / self.row_height()).max(1) as usize
+    }
+
+    fn items_per_page(&self) -> usize {
+        self.rows_per_page() * self.layout.items_per_row
+    }
+
+    /// Renders the missing thumbnails of the entries in `range`.
+    fn ensure_thumbnails(&mut self, range: std::ops::Range<usize>) {
+        let missing: Vec<&LsEntry> = self.entries[range]
+            .iter()
+            .filter(|e| !self.thumbnails.contains_key(&e.path))
+            .collect();
+        let rendered: Vec<_> = missing
+            .into_par_iter()
+            .map(|e| (e.path.clone(), lsix::thumbnail(e, &self.thumb_config)))
+            .collect();
+        self.thumbnails.extend(rendered);
+    }
+
+    fn encode_row(&self, row: usize, y: u16) -> Result<Vec<u8>> {
+        let per_row = self.layout.items_per_row;
+        let end = (row * per_row + per_row).min(self.entries.len());
+        let thumbs: Vec<Option<&DynamicImage>> = self.entries[row * per_row..end]
+            .iter()
+            .map(|e| self.thumbnails.get(&e.path).and_then(Option::as_ref))
+            .collect();
+
+        // failed thumbnails keep their slot, so the names stay aligned
+        let Some(size) = thumbs
+            .iter()
+            .flatten()
+            .map(|t| (t.width(), t.height()))
+            .next()
+        else {
+            return Ok(Vec::new());
+        };
+        let images = thumbs
+            .into_iter()
+            .map(|t| {
+                t.cloned()
+                    .unwrap_or_else(|| DynamicImage::new_rgba8(size.0, size.1))
+            })
+            .collect();
+        let image = lsix::combine_images_into_row(images, self.layout.img_padding)?;
+
+        let encoder = self
+            .config
+            .encoder
+            .context("this is likely a bug, encoder wasn't set at the ls viewer")?;
+        let wininfo = self
+            .config
+            .wininfo
+            .as_ref()
+            .context("this is likely a bug, wininfo wasn't set at the ls viewer")?;
+        let mut buf = Vec::new();
+        encoder.encode_image(&image, &mut buf, wininfo, None, Some((0, y)))?;
+        Ok(buf)
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<()> {
+        let (width, height) = self.size();
+        let per_row = self.layout.items_per_row;
+        let per_page = self.items_per_page();
+        let page = self.cursor / per_page;
+        let pages = self.entries.len().div_ceil(per_page).max(1);
+        let page_start = page * per_page;
+        let page_end = (page_start + per_page).min(self.entries.len());
+        self.ensure_thumbnails(page_start..page_end);
+
+        let mut buf: Vec<u8> = Vec::new();
+        self.clear_kitty_images(&mut buf)?;
+
+        let header = format!(
+            "{}  {FAINT}{} items  sort: {}{}  hidden: {}  page {}/{pages}{RESET}",
+            self.dir.display(),
+            self.entries.len(),
+            self.config.sort,
+            if self.config.reverse {
+                " (reversed)"
+            } else {
+                ""
+            },
+            if self.config.hidden { "shown" } else { "off" },
+            page + 1,
+        );
+        queue!(buf, MoveTo(0, 0), Print(header))?;
+
+        let pad_x = " ".repeat(self.layout.x_padding as usize);
+        for slot in 0..self.rows_per_page() {
+            let row = page * self.rows_per_page() + slot;
+            let start = row * per_row;
+            if start >= self.entries.len() {
+                break;
+            }
+            let y = 1 + slot as u16 * self.row_height();
+            let encoded = match self.rows.get(&row) {
+                Some(encoded) => encoded.clone(),
+                None => {
+                    let encoded = self.encode_row(row, y)?;
+                    self.rows.insert(row, encoded.clone());
+                    encoded
+                }
+            };
+            buf.extend_from_slice(&encoded);
+
+            let end = (start + per_row).min(self.entries.len());
+            let names: Vec<String> = (start..end)
+                .map(|i| {
+                    let entry = &self.entries[i];
+                    let name = lsix::truncate_filename(
+                        &entry.filename,
+                        self.layout.width,
+                        &entry.path,
+                        false,
+                    );
+                    if i == self.cursor {
+                        format!("{REVERSE}{name}{RESET}")
+                    } else {
+                        name
+                    }
+                })
+                .collect();
+            queue!(
+                buf,
+                MoveTo(0, y + self.thumb_height),
+                Print(format!("{pad_x}{}", names.join(&pad_x)))
+            )?;
+        }
+
+        let footer = match &self.status {
+            Some(status) => fit(status, width as usize),
+            None => fit(
+                "[hjkl/←↓↑→] Move  [Enter] Open  [Bksp] Up  [s] Sort  [r] Reverse  [.] Hidden  [q] Quit",
+                width as usize,
+            ),
+        };
+        queue!(
+            buf,
+            MoveTo(0, height - 1),
+            Print(format!("{FAINT}{footer}{RESET}"))
+        )?;
+
+        draw_frame(out, buf)?;
+        Ok(())
+    }
+}
+
+// back onto the alternate screen after a viewer or pager left it, else the grid fills the scrollback
+fn restore_screen(out: &mut impl Write) -> Result<()> {
+    execute!(out, EnterAlternateScreen, Hide)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rasteroid::{multiplexer::Multiplexer, term_misc::Wininfo};
+
+    use super::*;
+
+    fn make_viewer(dir: &Path) -> LsViewer {
+        let mut config = McatConfig::default();
+        config.encoder = Some(RasterEncoder::Ascii);
+        config.ls_icons = true;
+        config.ls_x_padding = "1c".to_owned();
+        config.ls_y_padding = "1c".to_owned();
+        config.ls_min_width = "8c".to_owned();
+        config.ls_max_width = "16c".to_owned();
+        config.ls_height = "4c".to_owned();
+        config.ls_items_per_row = 20;
+        config.wininfo = Some(Wininfo {
+            sc_width: 100,
+            sc_height: 20,
+            spx_width: 1000,
+            spx_height: 400,
+            multiplexer: Multiplexer::None,
+            needs_inline: false,
+            text_mode: Default::default(),
+            color_depth: Default::default(),
+        });
+        LsViewer::new(dir, config).unwrap()
+    }
+
+    // `count` files, each bigger than the one before
+    fn make_dir(count: usize) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..count {
+            std::fs::write(dir.path().join(format!("f{i:02}.txt")), "x".repeat(i + 1)).unwrap();
+        }
+        dir
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::from(code)
+    }
+
+    fn selected(viewer: &LsViewer) -> &str {
+        &viewer.entries[viewer.cursor].filename
+    }
+
+    #[test]
+    fn keys_move_through_the_grid() {
+        let dir = make_dir(30);
+        let mut viewer = make_viewer(dir.path());
+        let per_row = viewer.layout.items_per_row;
+        assert!(per_row > 1 && per_row < 30);
+
+        viewer.handle_key(key(KeyCode::Right));
+        assert_eq!(viewer.cursor, 1);
+        viewer.handle_key(key(KeyCode::Char('j')));
+        assert_eq!(viewer.cursor, 1 + per_row);
+        viewer.handle_key(key(KeyCode::Up));
+        viewer.handle_key(key(KeyCode::Left));
+        viewer.handle_key(key(KeyCode::Left));
+        assert_eq!(viewer.cursor, 0);
+
+        viewer.handle_key(key(KeyCode::Char('G')));
+        assert_eq!(viewer.cursor, 29);
+        viewer.handle_key(key(KeyCode::Down));
+        assert_eq!(viewer.cursor, 29);
+        viewer.handle_key(key(KeyCode::Char('g')));
+        assert_eq!(viewer.cursor, 0);
+
+        assert!(matches!(
+            viewer.handle_key(key(KeyCode::Enter)),
+            Action::Open
+        ));
+        assert!(matches!(
+            viewer.handle_key(key(KeyCode::Backspace)),
+            Action::Parent
+        ));
+        assert!(matches!(
+            viewer.handle_key(key(KeyCode::Char('q'))),
+            Action::Quit
+        ));
+        assert!(matches!(
+            viewer.handle_key(key(KeyCode::F(5))),
+            Action::None
+        ));
+    }
+
+    #[test]
+    fn thumbnails_render_a_page_at_a_time() {
+        let dir = make_dir(30);
+        let mut viewer = make_viewer(dir.path());
+        let per_page = viewer.items_per_page();
+        assert!(per_page < 30);
+
+        let mut out = Vec::new();
+        viewer.draw(&mut out).unwrap();
+        assert_eq!(viewer.thumbnails.len(), per_page);
+        assert_eq!(viewer.rows.len(), viewer.rows_per_page());
+        let pages = 30usize.div_ceil(per_page);
+        assert!(String::from_utf8_lossy(&out).contains(&format!("page 1/{pages}")));
+
+        viewer.handle_key(key(KeyCode::PageDown));
+        assert_eq!(viewer.cursor, per_page);
+        let mut out = Vec::new();
+        viewer.draw(&mut out).unwrap();
+        assert_eq!(viewer.thumbnails.len(), (per_page * 2).min(30));
+        assert!(String::from_utf8_lossy(&out).contains(&format!("page 2/{pages}")));
+
+        // going back reuses what was rendered
+        viewer.handle_key(key(KeyCode::PageUp));
+        viewer.draw(&mut Vec::new()).unwrap();
+        assert_eq!(viewer.thumbnails.len(), (per_page * 2).min(30));
+    }
+
+    #[test]
+    fn sorting_cycles_and_keeps_the_selection() {
+        let dir = make_dir(5);
+        let mut viewer = make_viewer(dir.path());
+        viewer.draw(&mut Vec::new()).unwrap();
+        assert!(!viewer.rows.is_empty());
+
+        viewer.handle_key(key(KeyCode::Right));
+        assert_eq!(selected(&viewer), "f01.txt");
+        let mut sorts = Vec::new();
+        for _ in 0..4 {
+            viewer.handle_key(key(KeyCode::Char('s')));
+            sorts.push(viewer.config.sort.to_string());
+            assert_eq!(selected(&viewer), "f01.txt");
+            // the rows were encoded for the old order
+            assert!(viewer.rows.is_empty());
+            viewer.draw(&mut Vec::new()).unwrap();
+        }
+        assert_eq!(sorts, ["size", "time", "type", "name"]);
+
+        viewer.handle_key(key(KeyCode::Char('r')));
+        assert_eq!(viewer.entries[0].filename, "f04.txt");
+        assert_eq!(selected(&viewer), "f01.txt");
+        assert!(viewer.rows.is_empty());
+    }
+
+    #[test]
+    fn going_up_selects_the_folder_we_came_from() {
+        let dir = make_dir(3);
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let mut viewer = make_viewer(dir.path());
+        let sub = viewer.dir.join("sub");
+
+        viewer.dir = sub.clone();
+        viewer.reload(None);
+        assert!(viewer.entries.is_empty());
+
+        viewer.dir = dir.path().canonicalize().unwrap();
+        viewer.reload(Some(&sub));
+        assert_eq!(selected(&viewer), "sub");
+    }
+}
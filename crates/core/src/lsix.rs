@@ -6,17 +6,19 @@ use rasteroid::{Encoder, term_misc};
 use rasteroid::{RasterEncoder, term_misc::SizeDirection};
 use rayon::prelude::*;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use tracing::{debug, info, warn};
 
+use crate::cache;
+use crate::ls_preview::{self, Preview};
 use crate::mcat_file::{McatFile, McatKind};
 use crate::{
     config::{McatConfig, SortMode},
     markdown_viewer::utils::string_len,
 };
 
-fn truncate_filename(name: &str, width: u16, lnk: &Path, create_hyprlink: bool) -> String {
+pub fn truncate_filename(name: &str, width: u16, lnk: &Path, create_hyprlink: bool) -> String {
     let width = width as usize;
 
     let osc8_start = if create_hyprlink {
@@ -156,49 +158,117 @@ fn ext_to_svg(ext: &str) -> &'static str {
     }
 }
 
-pub fn lsix(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -> Result<()> {
-    let dir_path = Path::new(input.as_ref());
+/// A single directory entry of the listing, `ext` is `IAMADIR` for folders.
+pub struct LsEntry {
+    pub path: PathBuf,
+    pub ext: String,
+    pub filename: String,
+}
+
+impl LsEntry {
+    pub fn new(path: PathBuf) -> Self {
+        let filename = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        if path.is_dir() {
+            return LsEntry {
+                path,
+                ext: "IAMADIR".to_owned(),
+                filename,
+            };
+        }
+        let ext = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        if ext.is_empty() && filename.contains(".") {
+            return LsEntry {
+                path,
+                ext: filename.replace(".", ""),
+                filename,
+            };
+        }
+        LsEntry {
+            path,
+            ext,
+            filename,
+        }
+    }
+}
+
+/// The grid layout, derived from the terminal size and the `ls` options.
+pub struct LsLayout {
+    pub items_per_row: usize,
+    pub x_padding: u16,
+    pub y_padding: u16,
+    /// width of a single item in cells
+    pub width: u16,
+    /// horizontal padding between the thumbnails, in pixels (or ascii cells)
+    pub img_padding: u32,
+}
+
+impl LsLayout {
+    /// Computes the layout, and sets the thumbnail size on `ctx` accordingly.
+    pub fn new(ctx: &mut McatConfig) -> Result<Self> {
+        let encoder = ctx
+            .encoder
+            .context("this is likely a bug, encoder wasn't set at lsix")?;
+        let wininfo = ctx
+            .wininfo
+            .as_ref()
+            .context("this is likely a bug, wininfo wasn't set at lsix")?;
+
+        let resize_for_ascii = encoder == RasterEncoder::Ascii;
+        let items_per_row = calculate_items_per_row(wininfo.sc_width, ctx)?;
+        let x_padding = wininfo.dim_to_cells(&ctx.ls_x_padding, SizeDirection::Width)? as u16;
+        let y_padding = wininfo.dim_to_cells(&ctx.ls_y_padding, SizeDirection::Height)? as u16;
+        let width =
+            (wininfo.sc_width as f32 / items_per_row as f32 + 0.1).round() as u16 - x_padding - 1;
+        debug!(
+            items_per_row,
+            ?encoder,
+            x_padding,
+            y_padding,
+            cell_width = width,
+            resize_for_ascii,
+            hidden = ctx.hidden,
+            ?ctx.sort,
+            reverse = ctx.reverse,
+            hyprlink = ctx.hyprlink,
+            "lsix layout"
+        );
+        let cell_px = wininfo.spx_width as f32 / wininfo.sc_width as f32;
+        let img_px_width = (cell_px * width as f32).round() as u32;
+        let img_padding = if resize_for_ascii {
+            x_padding as u32 * wininfo.text_mode.cell_size().0
+        } else {
+            (cell_px * x_padding as f32).round() as u32
+        };
+        ctx.img_width = format!("{img_px_width}px");
+        ctx.img_height = ctx.ls_height.clone();
+
+        Ok(Self {
+            items_per_row,
+            x_padding,
+            y_padding,
+            width,
+            img_padding,
+        })
+    }
+}
+
+/// Lists the direct children of `dir_path`, see [`sort_entries`] for the order.
+pub fn read_entries(dir_path: &Path, ctx: &McatConfig) -> Vec<LsEntry> {
     let walker = WalkBuilder::new(dir_path)
         .standard_filters(false)
         .hidden(!ctx.hidden)
         .max_depth(Some(1))
         .follow_links(true)
         .build();
-    let encoder = ctx
-        .encoder
-        .context("this is likely a bug, encoder wasn't set at lsix")?;
-    let wininfo = ctx
-        .wininfo
-        .as_ref()
-        .context("this is likely a bug, wininfo wasn't set at lsix")?;
 
-    let resize_for_ascii = encoder == RasterEncoder::Ascii;
-    let items_per_row = calculate_items_per_row(wininfo.sc_width, &ctx)?;
-    let x_padding = wininfo.dim_to_cells(&ctx.ls_x_padding, SizeDirection::Width)? as u16;
-    let y_padding = wininfo.dim_to_cells(&ctx.ls_y_padding, SizeDirection::Height)? as u16;
-    let width =
-        (wininfo.sc_width as f32 / items_per_row as f32 + 0.1).round() as u16 - x_padding - 1;
-    debug!(
-        items_per_row,
-        ?encoder,
-        x_padding,
-        y_padding,
-        cell_width = width,
-        resize_for_ascii,
-        hidden = ctx.hidden,
-        ?ctx.sort,
-        reverse = ctx.reverse,
-        hyprlink = ctx.hyprlink,
-        "lsix layout"
-    );
-    let cell_px = wininfo.spx_width as f32 / wininfo.sc_width as f32;
-    let img_px_width = (cell_px * width as f32).round() as u32;
-    let px_x_padding = (cell_px * x_padding as f32).round() as u32;
-    let width_formatted = format!("{img_px_width}px");
-    ctx.img_width = width_formatted;
-    ctx.img_height = ctx.ls_height.clone();
-
-    // Collect all valid paths first
     let mut paths: Vec<_> = walker
         .filter_map(|entry| {
             let entry = entry.ok()?;
@@ -206,28 +276,19 @@ pub fn lsix(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -
             if path == dir_path {
                 return None;
             }
-            let filename = path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .into_owned();
-            if path.is_dir() {
-                return Some((path, "IAMADIR".to_owned(), filename));
-            }
-            let ext = path
-                .extension()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_lowercase();
-            if ext.is_empty() && filename.contains(".") {
-                return Some((path, filename.replace(".", ""), filename));
-            }
-            Some((path, ext, filename))
+            Some(LsEntry::new(path))
         })
         .collect();
+    sort_entries(&mut paths, ctx);
+
+    paths
+}
+
+/// Orders entries the way `ls` shows them, folders first and then by `ctx.sort`.
+pub fn sort_entries(paths: &mut [LsEntry], ctx: &McatConfig) {
     paths.sort_by(|a, b| {
-        let a_is_dir = a.0.is_dir();
-        let b_is_dir = b.0.is_dir();
+        let a_is_dir = a.path.is_dir();
+        let b_is_dir = b.path.is_dir();
         let base_dir_order = b_is_dir.cmp(&a_is_dir);
         let dir_order = if ctx.reverse {
             base_dir_order.reverse()
@@ -239,36 +300,38 @@ pub fn lsix(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -
             std::cmp::Ordering::Equal => {
                 let order = match ctx.sort {
                     SortMode::Name => {
-                        let a_str = a.0.to_string_lossy().to_lowercase();
-                        let b_str = b.0.to_string_lossy().to_lowercase();
+                        let a_str = a.path.to_string_lossy().to_lowercase();
+                        let b_str = b.path.to_string_lossy().to_lowercase();
                         a_str.cmp(&b_str)
                     }
                     SortMode::Size => {
-                        let a_size = a.0.metadata().ok().map(|m| m.len()).unwrap_or(0);
-                        let b_size = b.0.metadata().ok().map(|m| m.len()).unwrap_or(0);
+                        let a_size = a.path.metadata().ok().map(|m| m.len()).unwrap_or(0);
+                        let b_size = b.path.metadata().ok().map(|m| m.len()).unwrap_or(0);
                         a_size.cmp(&b_size)
                     }
                     SortMode::Time => {
-                        let a_time = a.0.metadata().ok().and_then(|m| m.modified().ok());
-                        let b_time = b.0.metadata().ok().and_then(|m| m.modified().ok());
+                        let a_time = a.path.metadata().ok().and_then(|m| m.modified().ok());
+                        let b_time = b.path.metadata().ok().and_then(|m| m.modified().ok());
                         a_time.cmp(&b_time)
                     }
                     SortMode::Type => {
-                        let a_ext =
-                            a.0.extension()
-                                .and_then(|e| e.to_str())
-                                .unwrap_or("")
-                                .to_lowercase();
-                        let b_ext =
-                            b.0.extension()
-                                .and_then(|e| e.to_str())
-                                .unwrap_or("")
-                                .to_lowercase();
+                        let a_ext = a
+                            .path
+                            .extension()
+                            .and_then(|e| e.to_str())
+                            .unwrap_or("")
+                            .to_lowercase();
+                        let b_ext = b
+                            .path
+                            .extension()
+                            .and_then(|e| e.to_str())
+                            .unwrap_or("")
+                            .to_lowercase();
 
                         match a_ext.cmp(&b_ext) {
                             std::cmp::Ordering::Equal => {
-                                let a_str = a.0.to_string_lossy().to_lowercase();
-                                let b_str = b.0.to_string_lossy().to_lowercase();
+                                let a_str = a.path.to_string_lossy().to_lowercase();
+                                let b_str = b.path.to_string_lossy().to_lowercase();
                                 a_str.cmp(&b_str)
                             }
                             ext_order => ext_order,
@@ -281,86 +344,207 @@ pub fn lsix(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -
             dir_order => dir_order,
         }
     });
+}
+
+// the size a thumbnail is cached under, what `to_image` resizes to given the layout
+fn thumbnail_size(ctx: &McatConfig) -> Option<String> {
+    let wininfo = ctx.wininfo.as_ref()?;
+    let width = wininfo
+        .dim_to_px(&ctx.img_width, SizeDirection::Width)
+        .ok()?;
+    let height = wininfo
+        .dim_to_px(&ctx.img_height, SizeDirection::Height)
+        .ok()?;
+    let mut size = format!("{width}x{height}");
+    if ctx.encoder == Some(RasterEncoder::Ascii) {
+        let (cell_w, cell_h) = wininfo.text_mode.cell_size();
+        size.push_str(&format!("-ascii{cell_w}x{cell_h}"));
+    }
+    if ctx.no_img_metadata {
+        size.push_str("-raw");
+    }
+    Some(size)
+}
+
+/// Renders the thumbnail of an entry, archives and text files get a preview (see
+/// [`ls_preview`]) unless `--ls-icons` is set, documents only with `--ls-docs`.
+/// anything else falls back to an icon.
+/// rendered thumbnails are cached, see [`cache::load_thumbnail`].
+///
+/// [`ls_preview`]: crate::ls_preview
+pub fn thumbnail(entry: &LsEntry, ctx: &McatConfig) -> Option<DynamicImage> {
+    let path = &entry.path;
+    let preview = Preview::from_entry(entry, ctx);
+    // previews are drawn with the theme colors
+    let size = thumbnail_size(ctx).map(|size| match preview {
+        Some(_) => format!("{size}-{}", ctx.theme),
+        None => size,
+    });
+    if !path.is_dir()
+        && let Some(size) = &size
+        && let Some(img) = cache::load_thumbnail(path, size)
+    {
+        return Some(img);
+    }
+
+    let (img, kind) = if path.is_dir() {
+        (None, McatKind::PreMarkdown)
+    } else if preview == Some(Preview::Archive) {
+        // only the listing is read, never the whole archive
+        let img = ls_preview::render_archive(path, ctx)
+            .inspect_err(|e| debug!(path = %path.display(), error = %e, "no archive preview"))
+            .ok();
+        (img, McatKind::PreMarkdown)
+    } else {
+        let mcat_file = match McatFile::from_path(path, false) {
+            Ok(f) => f,
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "failed to read file");
+                return None;
+            }
+        };
+        let kind = mcat_file.kind.clone();
+        let img = match kind {
+            McatKind::Gif
+            | McatKind::Image
+            | McatKind::Svg
+            | McatKind::Url
+            | McatKind::Exe
+            | McatKind::Pdf
+            | McatKind::JpegXL
+            | McatKind::Mermaid
+            | McatKind::Dot
+            | McatKind::PlantUml
+            | McatKind::D2
+            | McatKind::Lnk => mcat_file.to_image(ctx, true, true).ok(),
+            McatKind::PreMarkdown
+            | McatKind::Markdown
+            | McatKind::Html
+            | McatKind::Video
+            | McatKind::Tex
+            | McatKind::Typst => None,
+        };
+        let img = img.or_else(|| {
+            let preview = preview?;
+            ls_preview::render(&mcat_file, preview, ctx)
+                .inspect_err(|e| debug!(path = %path.display(), ?preview, error = %e, "no preview"))
+                .ok()
+        });
+        (img, kind)
+    };
+
+    if let Some(img) = img {
+        if let Some(size) = &size {
+            cache::store_thumbnail(path, size, &img);
+        }
+        return Some(img);
+    }
+    let svg = if kind == McatKind::Video {
+        include_str!("../assets/video.svg")
+    } else {
+        ext_to_svg(&entry.ext)
+    };
+    let new_file = match McatFile::from_bytes(
+        svg.as_bytes().to_owned(),
+        None,
+        Some("svg".to_owned()),
+        None,
+        true,
+    ) {
+        Ok(f) => f,
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "failed to create svg fallback");
+            return None;
+        }
+    };
+    match new_file.to_image(ctx, true, true) {
+        Ok(img) => Some(img),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "failed to render svg fallback");
+            None
+        }
+    }
+}
+
+/// Formats a size the way `--max-size` takes it (1024 based).
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1}{}", UNITS[unit])
+}
+
+/// Sizes thumbnails to sit inside a line of text, a cell tall like markdown's small images.
+pub fn set_inline_icon_size(ctx: &mut McatConfig) -> Result<()> {
+    let wininfo = ctx
+        .wininfo
+        .as_mut()
+        .context("this is likely a bug, wininfo wasn't set at set_inline_icon_size")?;
+    wininfo.needs_inline = true;
+    let one_cell_px = wininfo
+        .dim_to_px("1c", SizeDirection::Height)?
+        .saturating_sub(1); // it ceils, so we must make sure 1c
+    ctx.img_width = "2c".to_owned();
+    ctx.img_height = format!("{one_cell_px}px");
+    // previews are unreadable at a cell tall, and documents are slow to render
+    ctx.ls_icons = true;
+    Ok(())
+}
+
+/// Encodes the thumbnail of `entry` as a single line, `ctx` should be set up with
+/// [`set_inline_icon_size`]. falls back to blank space.
+pub fn inline_icon(entry: &LsEntry, ctx: &McatConfig) -> String {
+    let blank = "  ".to_owned();
+    let (Some(encoder), Some(wininfo)) = (ctx.encoder, ctx.wininfo.as_ref()) else {
+        return blank;
+    };
+    let Some(img) = thumbnail(entry, ctx) else {
+        return blank;
+    };
+    let mut buf = Vec::new();
+    if let Err(e) = encoder.encode_image(&img, &mut buf, wininfo, None, None) {
+        warn!(path = %entry.path.display(), error = %e, "failed to encode icon");
+        return blank;
+    }
+    String::from_utf8_lossy(&buf).replace('\n', "")
+}
+
+pub fn lsix(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -> Result<()> {
+    let dir_path = Path::new(input.as_ref());
+    let layout = LsLayout::new(&mut ctx)?;
+    let encoder = ctx
+        .encoder
+        .context("this is likely a bug, encoder wasn't set at lsix")?;
+    let wininfo = ctx
+        .wininfo
+        .as_ref()
+        .context("this is likely a bug, wininfo wasn't set at lsix")?;
+
+    let paths = read_entries(dir_path, &ctx);
 
     info!(dir = %dir_path.display(), entry_count = paths.len(), "listing directory");
     // Process images in parallel
     let images: Vec<_> = paths
         .into_par_iter()
-        .filter_map(|(path, ext, filename)| {
-            let (img, kind) = if path.is_dir() {
-                (None, McatKind::PreMarkdown)
-            } else {
-                // compressed files rarely are the once we want, and they will be heavy cpu time.
-                let mcat_file = match McatFile::from_path(&path, false) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        warn!(path = %path.display(), error = %e, "failed to read file");
-                        return None;
-                    }
-                };
-                let kind = mcat_file.kind.clone();
-                let img = match kind {
-                    McatKind::Gif
-                    | McatKind::Image
-                    | McatKind::Svg
-                    | McatKind::Url
-                    | McatKind::Exe
-                    | McatKind::Pdf
-                    | McatKind::JpegXL
-                    | McatKind::Mermaid
-                    | McatKind::Lnk => mcat_file.to_image(&ctx, true, true).ok(),
-                    McatKind::PreMarkdown |
-                    McatKind::Markdown |
-                    McatKind::Html |
-                    McatKind::Video |
-                    McatKind::Tex |
-                    McatKind::Typst => None
-                };
-                (img, kind)
-            };
-
-            match img {
-                Some(img) => Some((img, filename, path)),
-                None => {
-                    let svg = if kind == McatKind::Video {
-                        include_str!("../assets/video.svg")
-                    } else {
-                        ext_to_svg(&ext)
-                    };
-                    let new_file = match McatFile::from_bytes(svg.as_bytes().to_owned(), None, Some("svg".to_owned()), None, true) {
-                        Ok(f) => f,
-                        Err(e) => {
-                            warn!(path = %path.display(), error = %e, "failed to create svg fallback");
-                            return None;
-                        }
-                    };
-                    let img = match new_file.to_image(&ctx, true, true) {
-                        Ok(img) => img,
-                        Err(e) => {
-                            warn!(path = %path.display(), error = %e, "failed to render svg fallback");
-                            return None;
-                        }
-                    };
-
-                    Some((img, filename, path))
-                }
-            }
+        .filter_map(|entry| {
+            let img = thumbnail(&entry, &ctx)?;
+            Some((img, entry.filename, entry.path))
         })
         .collect();
 
     let mut buf = Vec::new();
     buf.write_all(b"\n")?;
-    for chunk in &images.into_iter().chunks(items_per_row as usize) {
+    for chunk in &images.into_iter().chunks(layout.items_per_row) {
         let items: Vec<_> = chunk.collect();
         let images: Vec<DynamicImage> = items.iter().map(|f| f.0.clone()).collect();
-        let image = combine_images_into_row(
-            images,
-            if resize_for_ascii {
-                x_padding as u32
-            } else {
-                px_x_padding
-            },
-        )?;
+        let image = combine_images_into_row(images, layout.img_padding)?;
         let height = wininfo.dim_to_cells(&ctx.ls_height, SizeDirection::Height)?;
         term_misc::ensure_space(&mut buf, height as u16)?;
         // windows for some reason doesn't handle newlines as expected..
@@ -373,10 +557,10 @@ pub fn lsix(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -
         }
         let names: Vec<String> = items
             .iter()
-            .map(|f| truncate_filename(&f.1, width, &f.2, ctx.hyprlink))
+            .map(|f| truncate_filename(&f.1, layout.width, &f.2, ctx.hyprlink))
             .collect();
-        let pad_x = " ".repeat(x_padding as usize);
-        let pad_y = "\n".repeat(y_padding as usize);
+        let pad_x = " ".repeat(layout.x_padding as usize);
+        let pad_y = "\n".repeat(layout.y_padding as usize);
         let names_combined = names.join(&pad_x);
         write!(buf, "\n{pad_x}{names_combined}{pad_x}{pad_y}")?;
     }
@@ -386,7 +570,7 @@ pub fn lsix(input: impl AsRef<str>, out: &mut impl Write, mut ctx: McatConfig) -
     Ok(())
 }
 
-fn combine_images_into_row(images: Vec<DynamicImage>, padding: u32) -> Result<DynamicImage> {
+pub fn combine_images_into_row(images: Vec<DynamicImage>, padding: u32) -> Result<DynamicImage> {
     let background = Rgba([0, 0, 0, 0]);
     if images.is_empty() {
         return Ok(DynamicImage::new_rgba8(1, 1));
@@ -1,11 +1,22 @@
+mod cache;
 mod catter;
 mod cdp;
+mod charts;
 mod config;
+mod diagrams;
+mod diff_viewer;
 mod fetch_manager;
+mod fold_viewer;
+mod image_info;
 mod image_viewer;
+mod ls_long;
+mod ls_preview;
+mod ls_tree;
+mod ls_viewer;
 mod lsix;
 mod markdown_viewer;
 mod mcat_file;
+mod picker;
 mod prompter;
 mod scrapy;
 mod themes;
@@ -16,6 +27,7 @@ use clap_complete::{Generator, generate};
 use config::McatConfig;
 use crossterm::tty::IsTty;
 use dirs::home_dir;
+use rasteroid::multiplexer::Multiplexer;
 use scrapy::MediaScrapeOptions;
 use std::io::Write;
 use std::{
@@ -83,14 +95,12 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    if config
+    config
         .wininfo
         .as_ref()
         .context("this is likely a bug, wininfo is None")?
-        .is_tmux
-    {
-        rasteroid::set_tmux_passthrough(true);
-    }
+        .multiplexer
+        .set_passthrough(true);
 
     // if ls
     if config
@@ -100,7 +110,18 @@ fn main() -> Result<()> {
     {
         let d = ".".to_string();
         let input = config.input.get(1).cloned().unwrap_or(d);
-        lsix::lsix(input, &mut out, config)?;
+        let interactive = config.output == Some(config::OutputFormat::Interactive)
+            && std::io::stdin().is_tty()
+            && std::io::stdout().is_tty();
+        if config.tree {
+            ls_tree::tree(input, &mut out, config)?;
+        } else if config.long {
+            ls_long::long(input, &mut out, config)?;
+        } else if interactive {
+            ls_viewer::run(input, &mut out, config)?;
+        } else {
+            lsix::lsix(input, &mut out, config)?;
+        }
         return Ok(());
     }
 
@@ -141,7 +162,15 @@ fn main() -> Result<()> {
             }
 
             if path.is_dir() {
-                let mut selected_files = prompter::prompt_for_files(path, config.hidden)?;
+                // the picker draws on stderr, so `mcat . | pbcopy` still works
+                let interactive = !config.has_dir_filters()
+                    && std::io::stdin().is_tty()
+                    && std::io::stderr().is_tty();
+                let mut selected_files = if interactive {
+                    picker::pick_files(path, &config, &mut std::io::stderr())?
+                } else {
+                    prompter::collect_files(path, &config)?
+                };
                 selected_files.sort();
                 let new_files = selected_files
                     .iter()
@@ -154,6 +183,13 @@ fn main() -> Result<()> {
         }
     }
 
+    if config.info {
+        files = files
+            .iter()
+            .map(image_info::report_file)
+            .collect::<Result<Vec<_>>>()?;
+    }
+
     if config.testing {
         for file in &files {
             writeln!(out, "kind: {:?}", file.kind)?;
@@ -161,6 +197,14 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if config.diff {
+        let [old, new] = files.as_slice() else {
+            anyhow::bail!("--diff compares exactly two files, got {}", files.len());
+        };
+        diff_viewer::diff(old, new, &mut out, &config)?;
+        return Ok(());
+    }
+
     if !files.is_empty() {
         catter::cat(files, &mut out, &config)?;
     }
@@ -192,7 +236,8 @@ fn report_full(config: &McatConfig) -> Result<()> {
         .wininfo
         .as_ref()
         .context("this is likely a bug, wininfo is None")?;
-    let tmux = wininfo.is_tmux;
+    let multiplexer = wininfo.multiplexer;
+    let tmux = multiplexer == Multiplexer::Tmux;
     let inline = wininfo.needs_inline;
     let os = env.data.get("OS").map(|f| f.as_str()).unwrap_or("Unknown");
     let term = if tmux {
@@ -278,7 +323,8 @@ fn report_full(config: &McatConfig) -> Result<()> {
     // Others
     println!("├────────────────────────────────────────────────────┤");
     println!("│ Others:                                            │");
-    println!("│   Tmux:       {:<45} │", format_info(tmux));
+    println!("│   Multiplexer: {:<35} │", multiplexer);
+    println!("│   Colors:     {:<36} │", wininfo.color_depth);
     println!("│   Inline:     {:<45} │", format_info(inline));
     println!("│   OS:         {:<36} │", os);
     println!("│   TERM:       {:<36} │", term);
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use comrak::nodes::{AstNode, NodeHtmlBlock, NodeValue};
+use regex::Regex;
+
+use crate::markdown_viewer::{comrak_options, links::heading_text};
+
+/// What a fold marker renders to, the fold viewer finds the folds' lines by it.
+pub const MARKER: &str = "\u{E000}FOLD:";
+
+static DETAILS_TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)</?details\b[^>]*>").unwrap());
+static SUMMARY_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<summary[^>]*>(.*?)</summary>").unwrap());
+static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+static MARKER_COMMENT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*<!--FOLD:(\d+)-->\s*$").unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FoldKind {
+    Heading(u8),
+    Details,
+}
+
+/// A heading's section (up to the next heading of the same or a higher level) or a
+/// `<details>` block, something the fold viewer can collapse.
+#[derive(Debug, Clone)]
+pub struct Fold {
+    pub kind: FoldKind,
+    pub title: String,
+    /// What's persisted, the title so it survives edits elsewhere in the file.
+    pub key: String,
+    // 0 based source lines, the heading itself runs to `header_end` (setext ones are 2 lines)
+    start: usize,
+    header_end: usize,
+    end: usize,
+}
+
+/// The foldable sections of top level headings and `<details>` blocks, in document order.
+pub fn find_folds(md: &str) -> Vec<Fold> {
+    let arena = comrak::Arena::new();
+    let root = comrak::parse_document(&arena, md, &comrak_options());
+    let line_count = md.lines().count();
+    let mut folds = Vec::new();
+
+    let headings: Vec<(u8, usize, usize, &AstNode)> = root
+        .children()
+        .filter_map(|n| {
+            let data = n.data.borrow();
+            let NodeValue::Heading(heading) = &data.value else {
+                return None;
+            };
+            let sps = data.sourcepos;
+            Some((heading.level, sps.start.line - 1, sps.end.line - 1, n))
+        })
+        .collect();
+    for (i, &(level, start, header_end, node)) in headings.iter().enumerate() {
+        let end = headings[i + 1..]
+            .iter()
+            .find(|(l, ..)| *l <= level)
+            .map_or(line_count, |(_, s, ..)| *s);
+        folds.push(Fold {
+            kind: FoldKind::Heading(level),
+            title: heading_text(node),
+            key: String::new(),
+            start,
+            header_end,
+            end,
+        });
+    }
+
+    let lines: Vec<&str> = md.lines().collect();
+    let mut open = Vec::new();
+    for node in root.descendants() {
+        let data = node.data.borrow();
+        let NodeValue::HtmlBlock(NodeHtmlBlock { literal, .. }) = &data.value else {
+            continue;
+        };
+        for tag in DETAILS_TAG_RE.find_iter(literal) {
+            let line = data.sourcepos.start.line - 1 + literal[..tag.start()].matches('\n').count();
+            if !tag.as_str().starts_with("</") {
+                open.push(line);
+                continue;
+            }
+            let Some(start) = open.pop() else {
+                continue;
+            };
+            let source = lines[start..=line].join("\n");
+            let title = SUMMARY_RE
+                .captures(&source)
+                .map(|c| TAG_RE.replace_all(&c[1], "").trim().to_owned())
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| "Details".to_owned());
+            folds.push(Fold {
+                kind: FoldKind::Details,
+                title,
+                key: String::new(),
+                start,
+                header_end: start,
+                end: line + 1,
+            });
+        }
+    }
+
+    folds.sort_by_key(|f| (f.start, std::cmp::Reverse(f.end)));
+    folds.dedup_by_key(|f| f.start);
+
+    // repeated titles are told apart by how many came before them
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for fold in &mut folds {
+        let base = match fold.kind {
+            FoldKind::Heading(level) => format!("h{level}:{}", fold.title),
+            FoldKind::Details => format!("details:{}", fold.title),
+        };
+        let count = seen.entry(base.clone()).or_default();
+        fold.key = match *count {
+            0 => base,
+            n => format!("{base}#{n}"),
+        };
+        *count += 1;
+    }
+    folds
+}
+
+/// The markdown with the collapsed folds cut down to their heading or summary, and a marker
+/// comment before each fold so its line can be found once rendered.
+pub fn fold_source(md: &str, folds: &[Fold], collapsed: &[bool]) -> String {
+    let mut out = String::new();
+    let mut skip_until = 0;
+    for (i, line) in md.lines().enumerate() {
+        if i < skip_until {
+            continue;
+        }
+        let Some(id) = folds.iter().position(|f| f.start == i) else {
+            if folds.iter().any(|f| i > f.start && i <= f.header_end) {
+                // the underline of a setext heading, written with its first line
+                continue;
+            }
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+
+        let fold = &folds[id];
+        let is_collapsed = collapsed.get(id).copied().unwrap_or(false);
+        let triangle = if is_collapsed { "▶" } else { "▼" };
+        out.push_str(&format!("\n<!--FOLD:{id}-->\n"));
+        match fold.kind {
+            FoldKind::Heading(_) => {
+                let hashes = line.trim_start().chars().take_while(|c| *c == '#').count();
+                if hashes > 0 {
+                    let title = line.trim_start()[hashes..].trim_start();
+                    out.push_str(&format!("{} {triangle} {title}\n", "#".repeat(hashes)));
+                } else {
+                    out.push_str(&format!("{triangle} {line}\n"));
+                }
+                for underline in md.lines().skip(i + 1).take(fold.header_end - i) {
+                    out.push_str(underline);
+                    out.push('\n');
+                }
+                if is_collapsed {
+                    out.push('\n');
+                    skip_until = fold.end;
+                }
+            }
+            // quoted like the html preprocessor draws the summary of open ones
+            FoldKind::Details if is_collapsed => {
+                out.push_str(&format!("\n> {triangle} {}\n\n", fold.title));
+                skip_until = fold.end;
+            }
+            // the html preprocessor draws the summary with the ▼ already
+            FoldKind::Details => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// The fold id of a marker comment written by [`fold_source`].
+pub fn fold_marker(literal: &str) -> Option<usize> {
+    MARKER_COMMENT_RE.captures(literal)?[1].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = "# Title\n\nintro\n\n## Install\n\nsteps\n\n<details>\n<summary>More <b>info</b></summary>\n\nhidden\n\n</details>\n\nSetext\n------\n\nlast\n";
+
+    #[test]
+    fn finds_headings_and_details() {
+        let folds = find_folds(DOC);
+        let keys: Vec<&str> = folds.iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            ["h1:Title", "h2:Install", "details:More info", "h2:Setext"]
+        );
+        // the title's section runs to the end, install's stops at the next h2
+        assert_eq!((folds[0].start, folds[0].end), (0, 19));
+        assert_eq!((folds[1].start, folds[1].end), (4, 15));
+        assert_eq!((folds[2].start, folds[2].end), (8, 14));
+        assert_eq!((folds[3].start, folds[3].header_end), (15, 16));
+    }
+
+    #[test]
+    fn collapsed_folds_keep_their_header() {
+        let folds = find_folds(DOC);
+        let md = fold_source(DOC, &folds, &[false, true, false, true]);
+        assert!(md.contains("<!--FOLD:1-->\n## ▶ Install\n"));
+        assert!(!md.contains("steps") && !md.contains("hidden") && !md.contains("last"));
+        assert!(md.contains("<!--FOLD:3-->\n▶ Setext\n------\n"));
+        assert!(md.contains("# ▼ Title") && md.contains("intro"));
+
+        let md = fold_source(DOC, &folds, &[false, false, true, false]);
+        assert!(md.contains("steps") && md.contains("\n> ▶ More info\n"));
+        assert!(!md.contains("hidden"));
+        assert_eq!(fold_marker("<!--FOLD:2-->"), Some(2));
+    }
+}
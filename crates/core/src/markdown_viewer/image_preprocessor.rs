@@ -73,6 +73,26 @@ pub struct ImagePreprocessor {
     pub mapper: HashMap<String, ImageElement>,
 }
 
+/// How markdown images are rendered, `--md-image` or the encoder's default when it's auto.
+pub fn render_mode<'a>(conf: &'a McatConfig, encoder: &RasterEncoder) -> &'a MdImageMode {
+    if conf.md_image != MdImageMode::Auto {
+        return &conf.md_image;
+    }
+    match encoder {
+        RasterEncoder::Kitty => &MdImageMode::All,
+        RasterEncoder::Iterm => &MdImageMode::Small,
+        RasterEncoder::Sixel => &MdImageMode::Small,
+        RasterEncoder::Ascii => &MdImageMode::None,
+    }
+}
+
+/// Whether `md_to_ansi` shows images at all, diagrams and math stay as text when it doesn't.
+pub fn shows_images(conf: &McatConfig) -> bool {
+    conf.encoder
+        .as_ref()
+        .is_some_and(|encoder| render_mode(conf, encoder) != &MdImageMode::None)
+}
+
 impl ImagePreprocessor {
     pub fn new<'a>(
         node: &'a AstNode<'a>,
@@ -90,16 +110,7 @@ impl ImagePreprocessor {
         let mut urls = Vec::new();
         extract_image_urls(node, wininfo, &mut urls);
 
-        let render_mode = if conf.md_image != MdImageMode::Auto {
-            &conf.md_image
-        } else {
-            match *encoder {
-                RasterEncoder::Kitty => &MdImageMode::All,
-                RasterEncoder::Iterm => &MdImageMode::Small,
-                RasterEncoder::Sixel => &MdImageMode::Small,
-                RasterEncoder::Ascii => &MdImageMode::None,
-            }
-        };
+        let render_mode = render_mode(conf, encoder);
         info!(
             image_count = urls.len(),
             ?render_mode,
@@ -0,0 +1,238 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use comrak::nodes::{AstNode, NodeValue};
+use reqwest::Url;
+
+use crate::{
+    markdown_viewer::{render::RESET, utils::string_len},
+    themes::CustomTheme,
+};
+
+/// Links numbered in the order they're first seen, listed in a footer instead of being
+/// terminal hyperlinks, for terminals, pagers and pipes that don't support OSC 8.
+pub struct LinkRefs {
+    base_dir: Option<PathBuf>,
+    base_url: Option<Url>,
+    anchors: Vec<(String, String)>,
+    targets: Vec<String>,
+}
+
+impl LinkRefs {
+    /// Relative links resolve against the url the markdown came from, or else its directory.
+    pub fn new<'a>(
+        root: &'a AstNode<'a>,
+        markdown_file_path: Option<&Path>,
+        source_url: Option<&str>,
+    ) -> Self {
+        Self {
+            base_dir: markdown_file_path
+                .and_then(|p| p.parent())
+                .map(Path::to_path_buf),
+            base_url: source_url.and_then(|u| Url::parse(u).ok()),
+            anchors: heading_slugs(root),
+            targets: Vec::new(),
+        }
+    }
+
+    /// The footer number of a link, the same target always gets the same number.
+    pub fn number(&mut self, url: &str) -> usize {
+        let target = self.resolve(url);
+        match self.targets.iter().position(|t| *t == target) {
+            Some(i) => i + 1,
+            None => {
+                self.targets.push(target);
+                self.targets.len()
+            }
+        }
+    }
+
+    fn resolve(&self, url: &str) -> String {
+        if let Some(anchor) = url.strip_prefix('#') {
+            let anchor = anchor.to_lowercase();
+            return match self.anchors.iter().find(|(slug, _)| *slug == anchor) {
+                Some((slug, text)) => format!("{text} (#{slug})"),
+                None => url.to_owned(),
+            };
+        }
+        if Url::parse(url).is_ok() {
+            return url.to_owned();
+        }
+        if let Some(base) = &self.base_url
+            && let Ok(joined) = base.join(url)
+        {
+            return joined.to_string();
+        }
+        if let Some(dir) = &self.base_dir {
+            let (path, fragment) = match url.split_once('#') {
+                Some((path, fragment)) => (path, format!("#{fragment}")),
+                None => (url, String::new()),
+            };
+            let path = dir.join(path);
+            let path = path.canonicalize().unwrap_or(path);
+            return format!("{}{fragment}", path.display());
+        }
+        url.to_owned()
+    }
+
+    /// The "Links" section listing every numbered target, empty when there were no links.
+    pub fn footer(&self, theme: &CustomTheme, width: u16) -> String {
+        if self.targets.is_empty() {
+            return String::new();
+        }
+
+        let bg = &theme.keyword_bg.bg;
+        let main = &theme.keyword.fg;
+        let title = " \u{f0339} Links";
+        let padding = (width as usize).saturating_sub(string_len(title));
+        let mut out = format!("{main}{bg}{title}{}{RESET}\n\n", " ".repeat(padding));
+
+        let num_width = self.targets.len().to_string().len() + 2;
+        for (i, target) in self.targets.iter().enumerate() {
+            let num = format!("[{}]", i + 1);
+            out.push_str(&format!(
+                "{}{num:>num_width$}{RESET} {}{target}{RESET}\n",
+                theme.comment.fg, theme.cyan.fg
+            ));
+        }
+        out.trim_end().to_owned()
+    }
+}
+
+/// GitHub style anchors for the headings, `## Getting Started` is `#getting-started` and
+/// repeated headings get `-1`, `-2`.. appended. Paired with the heading's text.
+pub fn heading_slugs<'a>(root: &'a AstNode<'a>) -> Vec<(String, String)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    root.descendants()
+        .filter(|n| matches!(n.data.borrow().value, NodeValue::Heading(_)))
+        .map(|heading| {
+            let text = heading_text(heading);
+            let base = slugify(&text);
+            let count = seen.entry(base.clone()).or_default();
+            let slug = match *count {
+                0 => base,
+                n => format!("{base}-{n}"),
+            };
+            *count += 1;
+            (slug, text)
+        })
+        .collect()
+}
+
+/// The plain text of a heading, without its formatting.
+pub fn heading_text<'a>(heading: &'a AstNode<'a>) -> String {
+    heading
+        .descendants()
+        .filter_map(|n| match &n.data.borrow().value {
+            NodeValue::Text(t) => Some(t.to_string()),
+            NodeValue::Code(c) => Some(c.literal.clone()),
+            _ => None,
+        })
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rasteroid::{RasterEncoder, multiplexer::Multiplexer, term_misc::Wininfo};
+
+    use super::*;
+    use crate::{
+        config::McatConfig,
+        markdown_viewer::{comrak_options, md_to_ansi},
+    };
+
+    #[test]
+    fn slugs_match_github() {
+        let arena = comrak::Arena::new();
+        let md = "# Getting Started!\n\n## `mcat` & Friends\n\n## Getting Started\n";
+        let root = comrak::parse_document(&arena, md, &comrak_options());
+        let slugs = heading_slugs(root);
+        let slugs: Vec<&str> = slugs.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(
+            slugs,
+            ["getting-started", "mcat--friends", "getting-started-1"]
+        );
+    }
+
+    #[test]
+    fn links_resolve_and_share_numbers() {
+        let arena = comrak::Arena::new();
+        let root = comrak::parse_document(&arena, "# Install\n", &comrak_options());
+        let mut refs = LinkRefs::new(root, None, Some("https://example.com/docs/readme.md"));
+        assert_eq!(refs.number("guide.md#setup"), 1);
+        assert_eq!(refs.number("https://rust-lang.org"), 2);
+        assert_eq!(refs.number("./guide.md#setup"), 1);
+        assert_eq!(refs.number("#install"), 3);
+        assert_eq!(
+            refs.targets,
+            [
+                "https://example.com/docs/guide.md#setup",
+                "https://rust-lang.org",
+                "Install (#install)"
+            ]
+        );
+
+        let mut refs = LinkRefs::new(root, Some(Path::new("/docs/readme.md")), None);
+        refs.number("missing/page.md");
+        assert_eq!(refs.targets, ["/docs/missing/page.md"]);
+    }
+
+    #[test]
+    fn rendered_links_are_numbered_with_a_footer() {
+        let mut config = McatConfig::default();
+        config.encoder = Some(RasterEncoder::Ascii);
+        config.link_refs = true;
+        config.wininfo = Some(Wininfo {
+            sc_width: 60,
+            sc_height: 20,
+            spx_width: 1920,
+            spx_height: 1080,
+            multiplexer: Multiplexer::None,
+            needs_inline: false,
+            text_mode: Default::default(),
+            color_depth: Default::default(),
+        });
+        let md = "See [the guide](guide.md) and <https://rust-lang.org>.\n\n\
+                  [a long\\\nlink text](https://example.com) and [again](guide.md)\n";
+        let out = md_to_ansi(md, config, Some(Path::new("/docs/readme.md")), None).unwrap();
+        assert!(
+            !out.contains("\x1b]8;;"),
+            "numbered links shouldn't be osc 8 links"
+        );
+
+        let text = strip_ansi_escapes::strip_str(&out);
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines,
+            [
+                // the autolink already shows its url, so it gets no number
+                "See \u{f0339} the guide[1] and \u{f0339} https://rust-lang.org.",
+                "",
+                // the number goes after the last line of the link
+                "\u{f0339} a long",
+                "\u{a0}\u{a0}link text[2] and \u{f0339} again[1]",
+                "",
+                " \u{f0339} Links",
+                "",
+                "[1] /docs/guide.md",
+                "[2] https://example.com",
+            ]
+        );
+    }
+}
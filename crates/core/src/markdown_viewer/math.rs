@@ -0,0 +1,290 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use comrak::nodes::{AstNode, NodeLink, NodeValue};
+use tracing::debug;
+
+use crate::{cache, config::McatConfig, markdown_viewer::image_preprocessor};
+
+// latex body text is 10pt on a 12pt line, 16px once rasterized at 96 dpi
+const LINE_PX: f32 = 16.0;
+const MITEX: &str = "@preview/mitex:0.2.5";
+static MITEX_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// typst couldn't get the mitex package, usually because it isn't cached yet and there's no
+/// network. every formula would fail the same way, so it's reported once instead of per formula.
+#[derive(Debug)]
+struct PackageUnavailable;
+
+impl fmt::Display for PackageUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "typst couldn't download {MITEX}")
+    }
+}
+
+impl std::error::Error for PackageUnavailable {}
+
+/// What typesets the formula. latex and tectonic compile to dvi that dvisvgm turns into an
+/// svg, typst writes the svg itself (reading the latex through the mitex package).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Engine {
+    Latex,
+    Tectonic,
+    Typst,
+}
+
+impl Engine {
+    fn detect() -> Option<Self> {
+        let dvisvgm = which::which("dvisvgm").is_ok();
+        if dvisvgm && which::which("latex").is_ok() {
+            Some(Self::Latex)
+        } else if dvisvgm && which::which("tectonic").is_ok() {
+            Some(Self::Tectonic)
+        } else if which::which("typst").is_ok() {
+            Some(Self::Typst)
+        } else {
+            None
+        }
+    }
+
+    // returns the dvi (xdv for tectonic) written next to the tex file
+    fn compile(self, tex: &Path, out_dir: &Path) -> Result<PathBuf> {
+        let output = match self {
+            Engine::Latex => Command::new("latex")
+                .args([
+                    "-interaction=nonstopmode",
+                    "-halt-on-error",
+                    &format!("-output-directory={}", out_dir.to_string_lossy()),
+                    &tex.to_string_lossy(),
+                ])
+                .output()?,
+            Engine::Tectonic => Command::new("tectonic")
+                .args([
+                    "--outfmt",
+                    "xdv",
+                    "--outdir",
+                    &out_dir.to_string_lossy(),
+                    &tex.to_string_lossy(),
+                ])
+                .output()?,
+            Engine::Typst => anyhow::bail!("typst writes the svg itself, there's no dvi"),
+        };
+        let dvi = tex.with_extension(match self {
+            Engine::Tectonic => "xdv",
+            _ => "dvi",
+        });
+        if !output.status.success() || !dvi.exists() {
+            // latex reports errors on stdout, tectonic on stderr
+            let log = [output.stdout, output.stderr].concat();
+            anyhow::bail!(
+                "{self:?} failed to compile the formula:\n{}",
+                String::from_utf8_lossy(&log)
+            );
+        }
+        Ok(dvi)
+    }
+}
+
+/// Replaces display math and `math` fences with typeset svg images, the same way mermaid
+/// fences are. Needs latex (or tectonic) and dvisvgm, or typst, and is skipped when images won't be
+/// shown, so the unicode prettifier still covers ascii output and missing tools.
+pub fn typeset_math<'a>(root: &'a AstNode<'a>, conf: &McatConfig) {
+    let Some(wininfo) = &conf.wininfo else {
+        return;
+    };
+    if !image_preprocessor::shows_images(conf) {
+        return;
+    }
+    let Some(engine) = Engine::detect() else {
+        debug!("neither latex and dvisvgm nor typst found, display math stays as text");
+        return;
+    };
+
+    let cell_height = wininfo.spx_height as f32 / wininfo.sc_height.max(1) as f32;
+    let zoom = (cell_height / LINE_PX).max(0.5);
+    let fg = conf.theme.to_custom().foreground.hex();
+
+    for node in root.descendants() {
+        let latex = match &node.data.borrow().value {
+            NodeValue::Math(math) if math.display_math => math.literal.clone(),
+            NodeValue::CodeBlock(cb) if cb.info.trim() == "math" => cb.literal.clone(),
+            _ => continue,
+        };
+        match typeset(latex.trim(), engine, &fg, zoom) {
+            Ok(svg) => {
+                let b64 = base64::engine::general_purpose::STANDARD.encode(&svg);
+                node.data.borrow_mut().value = NodeValue::Image(Box::new(NodeLink {
+                    url: format!("data:image/svg+xml;base64,{b64}"),
+                    title: String::new(),
+                }));
+            }
+            Err(e) if e.is::<PackageUnavailable>() => {
+                if !conf.silent && !MITEX_WARNED.swap(true, Ordering::Relaxed) {
+                    eprintln!(
+                        "warning: {e} (it needs network access once), display math stays as text"
+                    );
+                }
+                return;
+            }
+            Err(e) => debug!(error = %e, "failed to typeset math"),
+        }
+    }
+}
+
+fn typeset(latex: &str, engine: Engine, fg: &str, zoom: f32) -> Result<Vec<u8>> {
+    let key = format!("{engine:?}:{fg}:{zoom:.3}:{latex}");
+    if let Some(svg) = cache::load_math(&key) {
+        return Ok(svg);
+    }
+
+    let dir = tempfile::tempdir()?;
+    if engine == Engine::Typst {
+        let svg = typst_svg(latex, fg, zoom, dir.path())?;
+        cache::store_math(&key, &svg);
+        return Ok(svg);
+    }
+    let tex = dir.path().join("math.tex");
+    fs::write(&tex, standalone_doc(latex, fg))?;
+    let dvi = engine.compile(&tex, dir.path())?;
+
+    let svg_path = dir.path().join("math.svg");
+    let output = Command::new("dvisvgm")
+        .args([
+            "--no-fonts",
+            "--exact-bbox",
+            &format!("--zoom={zoom:.3}"),
+            "-o",
+            &svg_path.to_string_lossy(),
+            &dvi.to_string_lossy(),
+        ])
+        .output()
+        .context("failed to run dvisvgm")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "dvisvgm failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let svg = fs::read(&svg_path)?;
+    cache::store_math(&key, &svg);
+    Ok(svg)
+}
+
+fn typst_svg(latex: &str, fg: &str, zoom: f32, dir: &Path) -> Result<Vec<u8>> {
+    let typ = dir.join("math.typ");
+    let svg_path = dir.join("math.svg");
+    fs::write(&typ, typst_doc(latex, fg, zoom))?;
+    let output = Command::new("typst")
+        .args(["compile", "--format", "svg"])
+        .arg(&typ)
+        .arg(&svg_path)
+        .output()
+        .context("failed to run typst")?;
+    if !output.status.success() || !svg_path.exists() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if package_unavailable(&stderr) {
+            debug!(%stderr, "typst failed to get mitex");
+            return Err(PackageUnavailable.into());
+        }
+        anyhow::bail!("typst failed to compile the formula:\n{stderr}");
+    }
+    Ok(fs::read(&svg_path)?)
+}
+
+// the same page as `standalone_doc`, the latex is read by mitex (fetched once by typst)
+fn typst_doc(latex: &str, fg: &str, zoom: f32) -> String {
+    let latex = latex.replace('\\', "\\\\").replace('"', "\\\"");
+    format!(
+        "#import \"{MITEX}\": mitex\n\
+         #set page(width: auto, height: auto, margin: 1pt, fill: none)\n\
+         #set text(size: {:.2}pt, fill: rgb(\"#{fg}\"))\n\
+         #mitex(\"{latex}\")\n",
+        10.0 * zoom
+    )
+}
+
+// typst's wording when the package isn't in its cache and can't be downloaded
+fn package_unavailable(stderr: &str) -> bool {
+    stderr.contains("failed to download package")
+        || (stderr.contains("package not found") && stderr.contains("@preview/mitex"))
+}
+
+// a page cropped to the formula, glyphs drawn in the theme's foreground
+fn standalone_doc(latex: &str, fg: &str) -> String {
+    format!(
+        "\\documentclass[preview,border=1pt]{{standalone}}\n\
+         \\usepackage{{amsmath,amssymb,xcolor}}\n\
+         \\begin{{document}}\n\
+         \\color[HTML]{{{fg}}}$\\displaystyle {latex}$\n\
+         \\end{{document}}\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standalone_doc_colours_the_formula() {
+        let doc = standalone_doc(r"\frac{a}{b}", "CDD6F4");
+        assert!(doc.contains(r"\color[HTML]{CDD6F4}$\displaystyle \frac{a}{b}$"));
+        assert!(doc.starts_with(r"\documentclass[preview,border=1pt]{standalone}"));
+    }
+
+    #[test]
+    fn typst_doc_reads_the_latex_with_mitex() {
+        let doc = typst_doc(r#"\text{"a"} \frac{a}{b}"#, "CDD6F4", 1.5);
+        assert!(doc.starts_with("#import \"@preview/mitex:"));
+        assert!(doc.contains(r##"#set text(size: 15.00pt, fill: rgb("#CDD6F4"))"##));
+        assert!(doc.contains(r#"#mitex("\\text{\"a\"} \\frac{a}{b}")"#));
+    }
+
+    #[test]
+    fn offline_package_fetches_are_recognized() {
+        let offline = "downloading @preview/mitex:0.2.5\n\
+                       error: failed to download package (Network Error: Dns Failed)\n";
+        assert!(package_unavailable(offline));
+        let not_cached = "error: package not found (searched for @preview/mitex:0.2.5)\n";
+        assert!(package_unavailable(not_cached));
+        let bad_formula = "error: unknown variable: fracc\n";
+        assert!(!package_unavailable(bad_formula));
+    }
+
+    #[test]
+    fn typesets_with_the_installed_engine() {
+        let Some(engine) = Engine::detect() else {
+            eprintln!("no math engine installed, skipping");
+            return;
+        };
+        let svg = match typeset(r"\frac{a}{b} + \sqrt{x}", engine, "CDD6F4", 1.0) {
+            Err(e) if e.is::<PackageUnavailable>() => {
+                eprintln!("{e}, skipping");
+                return;
+            }
+            res => res.unwrap(),
+        };
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.contains("<svg"), "{engine:?} didn't write an svg");
+    }
+
+    #[test]
+    fn math_left_alone_without_images() {
+        let arena = comrak::Arena::new();
+        let md = "$$\n\\frac{a}{b}\n$$\n\n```math\nx^2\n```\n";
+        let root = comrak::parse_document(&arena, md, &crate::markdown_viewer::comrak_options());
+        // a default config has no encoder, same as when images aren't shown
+        typeset_math(root, &McatConfig::default());
+        assert!(
+            !root
+                .descendants()
+                .any(|n| matches!(n.data.borrow().value, NodeValue::Image(_)))
+        );
+    }
+}
@@ -1,15 +1,19 @@
+pub mod folding;
 pub mod html_preprocessor;
 pub mod image_preprocessor;
+pub mod links;
+pub mod math;
 pub mod render;
 pub mod utils;
 
 use crate::{
-    markdown_viewer::{render::build_toc, utils::preprocess_ast},
+    markdown_viewer::{math::typeset_math, render::build_toc, utils::preprocess_ast},
     themes::CustomTheme,
 };
 use comrak::{Arena, format_html_with_plugins, options, plugins::syntect::SyntectAdapterBuilder};
-use image_preprocessor::ImagePreprocessor;
+use image_preprocessor::{ImagePreprocessor, shows_images};
 use itertools::Itertools;
+use links::LinkRefs;
 use render::{AnsiContext, RESET, parse_node};
 use syntect::highlighting::ThemeSet;
 
@@ -21,13 +25,15 @@ pub fn md_to_ansi(
     md: &str,
     mut config: McatConfig,
     markdown_file_path: Option<&Path>,
+    source_url: Option<&str>,
 ) -> Result<String> {
     let md = html_preprocessor::process(md);
 
     let arena = Arena::new();
     let opts = comrak_options();
     let root = comrak::parse_document(&arena, &md, &opts);
-    preprocess_ast(root, &config.theme);
+    preprocess_ast(root, &config.theme, shows_images(&config));
+    typeset_math(root, &config);
 
     let padding = config.padding as usize;
 
@@ -39,11 +45,9 @@ pub fn md_to_ansi(
     wininfo.needs_inline = true;
     wininfo.sc_width = wininfo.sc_width.saturating_sub((padding * 2) as u16);
 
-    let ps = two_face::syntax::extra_newlines();
     let theme = config.theme.to_custom();
     let image_preprocessor = ImagePreprocessor::new(root, &config, markdown_file_path)?;
     let mut ctx = AnsiContext {
-        ps,
         theme,
         wininfo: config.wininfo.unwrap(),
         hide_line_numbers: config.no_linenumbers,
@@ -52,6 +56,10 @@ pub fn md_to_ansi(
         image_preprocessor,
         show_frontmatter: config.header,
         syntax_map: config.syntax_map.clone(),
+        link_refs: config
+            .link_refs
+            .then(|| LinkRefs::new(root, markdown_file_path, source_url)),
+        table_mode: config.md_table,
 
         blockquote_fenced_offset: None,
         collecting_depth: 0,
@@ -73,8 +81,17 @@ pub fn md_to_ansi(
     }
     output.push_str(&ctx.theme.foreground.fg);
     output.push_str(&parse_node(root, &mut ctx));
+    if let Some(refs) = &ctx.link_refs {
+        let footer = refs.footer(&ctx.theme, ctx.wininfo.sc_width);
+        if !footer.is_empty() {
+            output.push_str("\n\n");
+            output.push_str(&footer);
+        }
+    }
 
-    let mut res = output.replace(RESET, &format!("{RESET}{}", ctx.theme.foreground.fg));
+    let res = output.replace(RESET, &format!("{RESET}{}", ctx.theme.foreground.fg));
+    // before the images, kitty placeholders encode the image id in their colour
+    let mut res = ctx.wininfo.color_depth.downsample(&res).into_owned();
 
     // replace images
     for (_, img) in ctx.image_preprocessor.mapper {
@@ -99,7 +116,7 @@ pub fn md_to_html(markdown: &str, theme: &Theme, style: bool) -> String {
 
     let arena = Arena::new();
     let root = comrak::parse_document(&arena, markdown, &options);
-    preprocess_ast(root, theme);
+    preprocess_ast(root, theme, true);
 
     let theme = CustomTheme::from(theme);
     let mut theme_set = ThemeSet::load_defaults();
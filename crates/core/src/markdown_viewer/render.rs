@@ -2,26 +2,30 @@ use std::ops::{Add, Mul};
 
 use comrak::nodes::{
     AstNode, NodeCode, NodeHeading, NodeHtmlBlock, NodeMath, NodeValue, NodeWikiLink,
+    TableAlignment,
 };
 use itertools::Itertools;
 use rasteroid::term_misc::Wininfo;
 use regex::Regex;
 use strip_ansi_escapes::strip_str;
-use syntect::parsing::SyntaxSet;
 
 use crate::{
+    charts::{self, Chart},
+    config::MdTableMode,
     markdown_viewer::utils::{
-        extract_span_color, prettify_latex, string_len, to_superscript, trim_ansi_string,
-        wrap_lines,
+        extract_span_color, is_numeric_cell, prettify_latex, string_len, to_superscript,
+        trim_ansi_string, wrap_lines,
     },
     themes::CustomTheme,
 };
 
 use super::{
+    folding,
     image_preprocessor::ImagePreprocessor,
+    links::LinkRefs,
     utils::{
-        format_code_box, format_code_full, format_code_simple, format_tb, wrap_char_based,
-        wrap_highlighted_line,
+        CodeInfo, format_code_box, format_code_full, format_code_simple, format_tb,
+        wrap_char_based, wrap_highlighted_line,
     },
 };
 
@@ -30,7 +34,10 @@ pub const BOLD: &str = "\x1B[1m";
 const ITALIC: &str = "\x1B[3m";
 const UNDERLINE: &str = "\x1B[4m";
 const STRIKETHROUGH: &str = "\x1B[9m";
-const FAINT: &str = "\x1b[2m";
+pub const FAINT: &str = "\x1b[2m";
+pub const REVERSE: &str = "\x1b[7m";
+// directories in the ls listings and the picker
+pub const BOLD_BLUE: &str = "\x1b[1m\x1b[34m";
 const NORMAL: &str = "\x1B[22m";
 const ITALIC_OFF: &str = "\x1B[23m";
 const STRIKETHROUGH_OFF: &str = "\x1B[29m";
@@ -38,7 +45,6 @@ pub const UNDERLINE_OFF: &str = "\x1B[24m";
 const INDENT: usize = 2;
 
 pub struct AnsiContext {
-    pub ps: SyntaxSet,
     pub theme: CustomTheme,
     pub wininfo: Wininfo,
     pub hide_line_numbers: bool,
@@ -47,6 +53,9 @@ pub struct AnsiContext {
     pub center: bool,
     pub image_preprocessor: ImagePreprocessor,
     pub syntax_map: Vec<(String, String)>,
+    /// `--link-refs`, numbered links with a footer instead of OSC 8 hyperlinks
+    pub link_refs: Option<LinkRefs>,
+    pub table_mode: MdTableMode,
 
     pub blockquote_fenced_offset: Option<usize>,
     pub collecting_depth: usize,
@@ -209,7 +218,7 @@ fn render_front_matter<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> Stri
         .unwrap_or(literal.trim())
         .trim();
 
-    format_code_box(content, "yaml", "Document Metadata", ctx)
+    format_code_box(content, &CodeInfo::parse("yaml"), "Document Metadata", ctx)
 }
 
 fn render_footnote_def<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String {
@@ -361,22 +370,39 @@ fn render_code_block<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String
         panic!()
     };
     let literal = &node_code_block.literal;
-    let info = if node_code_block.info.trim().is_empty() {
-        "text"
-    } else {
-        &node_code_block.info
-    };
+    let mut info = CodeInfo::parse(&node_code_block.info);
+    if info.lang.is_empty() {
+        info.lang = "text".to_owned();
+    }
+    let lines = literal.lines().count();
+    let numbered = ctx.force_simple_code_block == 0
+        && !ctx.hide_line_numbers
+        && (lines > 10 || info.start_line.is_some());
 
-    if info == "file-tree" {
+    if info.lang == "file-tree" {
         render_file_tree(literal, ctx)
-    } else if literal.lines().count() <= 10
-        || ctx.force_simple_code_block > 0
-        || ctx.hide_line_numbers
+    } else if charts::LANGS.contains(&info.lang.as_str())
+        && let Ok(chart) = Chart::parse(literal)
     {
-        let indent = ctx.indent();
-        format_code_simple(literal, info, ctx, indent)
+        // only reached when images aren't shown, otherwise the chart is an image by now
+        let width = (ctx.wininfo.sc_width as usize).saturating_sub(ctx.indent());
+        let out = chart.to_unicode(&ctx.theme, width.min(80));
+        let pad = " ".repeat(ctx.indent());
+        out.lines().map(|l| format!("{pad}{l}")).join("\n")
+    } else if numbered {
+        format_code_full(literal, &info, ctx)
+    } else if let Some(title) = info.title.clone()
+        && ctx.force_simple_code_block == 0
+    {
+        let pad = " ".repeat(ctx.indent());
+        format_code_box(literal, &info, &title, ctx)
+            .trim_end()
+            .lines()
+            .map(|l| format!("{pad}{l}"))
+            .join("\n")
     } else {
-        format_code_full(literal, info, ctx)
+        let indent = ctx.indent();
+        format_code_simple(literal, &info, ctx, indent)
     }
 }
 
@@ -442,6 +468,9 @@ fn render_html_block<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String
         ctx.center = false;
         return String::new();
     }
+    if let Some(id) = folding::fold_marker(literal) {
+        return format!("{}{id}", folding::MARKER);
+    }
 
     let comment = &ctx.theme.comment.fg;
     let result = literal
@@ -570,29 +599,33 @@ fn render_table<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String {
         panic!()
     };
 
-    let alignments = &table.alignments;
     let mut rows: Vec<Vec<Vec<String>>> = Vec::new();
-    let mut row_heights: Vec<usize> = Vec::new();
 
-    // collect all cell contents and calculate row heights
+    // collect all cell contents
     for child in node.children() {
         let mut row_cells: Vec<Vec<String>> = Vec::new();
-        let mut max_lines_in_row = 1;
-
         for cell_node in child.children() {
             let cell_content = collect(cell_node, ctx, "");
             let cell_lines: Vec<String> = cell_content
                 .lines()
                 .map(|s| trim_ansi_string(s.to_string()))
                 .collect();
-            max_lines_in_row = max_lines_in_row.max(cell_lines.len());
             row_cells.push(cell_lines);
         }
-
         rows.push(row_cells);
-        row_heights.push(max_lines_in_row);
     }
 
+    // columns without an explicit alignment are right aligned when they only hold numbers
+    let alignments: Vec<TableAlignment> = table
+        .alignments
+        .iter()
+        .enumerate()
+        .map(|(i, align)| match align {
+            TableAlignment::None if is_numeric_column(&rows, i) => TableAlignment::Right,
+            align => *align,
+        })
+        .collect();
+
     // Calculate column widths based on the longest line in any cell of the column
     let mut column_widths: Vec<usize> = vec![0; alignments.len()];
     for row in &rows {
@@ -617,60 +650,226 @@ fn render_table<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String {
         ctx.wininfo.sc_width as usize
     };
 
-    let border_overhead = 3 * column_widths.len() + 1;
-    let total_content_width: usize = column_widths.iter().sum();
-    let total_table_width = total_content_width + border_overhead;
+    let mode = match ctx.table_mode {
+        MdTableMode::Auto => auto_table_mode(&rows, &column_widths, available_width),
+        mode => mode,
+    };
+    let result = match mode {
+        MdTableMode::Split if column_widths.len() > 1 => {
+            split_table(&rows, &column_widths, &alignments, available_width, ctx)
+        }
+        MdTableMode::Transpose if rows.len() > 1 => transpose_table(&rows, available_width, ctx),
+        MdTableMode::Wrap => {
+            let capped: Vec<usize> = column_widths
+                .iter()
+                .map(|w| (*w).min(TABLE_COLUMN_CAP))
+                .collect();
+            let widths = fit_column_widths(&capped, available_width);
+            draw_table(&rows, &widths, &alignments, ctx)
+        }
+        _ => {
+            let widths = fit_column_widths(&column_widths, available_width);
+            draw_table(&rows, &widths, &alignments, ctx)
+        }
+    };
 
-    if total_table_width > available_width && total_content_width > 0 {
-        let target_content_width = available_width.saturating_sub(border_overhead);
-        // Waterfall algorithm: keep narrow columns at their natural width,
-        // shrink only the wider columns. Iterate from narrowest to widest:
-        // if a column fits within its equal share of remaining space, grant
-        // it its natural width; otherwise distribute remaining space equally
-        // among the remaining (wider) columns.
-        let mut indices: Vec<usize> = (0..column_widths.len()).collect();
-        indices.sort_by_key(|&i| column_widths[i]);
+    let sps = node.data.borrow().sourcepos;
+    if ctx.center {
+        let le = string_len(result.lines().nth(1).unwrap_or_default());
+        let offset = sps.start.column.saturating_sub(1);
+        let offset = (ctx.wininfo.sc_width as usize - offset)
+            .saturating_sub(le)
+            .saturating_div(2);
+
+        result
+            .lines()
+            .map(|line| format!("{}{line}", " ".repeat(offset)))
+            .join("\n")
+    } else if ctx.should_wrap() {
+        let indent = ctx.indent();
+        result
+            .lines()
+            .map(|line| format!("{}{line}", " ".repeat(indent)))
+            .join("\n")
+    } else {
+        result
+    }
+}
 
-        let mut new_widths: Vec<usize> = vec![0; column_widths.len()];
-        let mut remaining_budget = target_content_width;
-        let mut remaining_cols = column_widths.len();
+// `--md-table wrap` wraps cells longer than this even when the table would fit
+const TABLE_COLUMN_CAP: usize = 40;
+// squeezing a column below this many cells makes it unreadable
+const TABLE_MIN_COLUMN: usize = 8;
+// tables with at most this many data rows read better as cards than as stacked segments
+const TABLE_SHORT_ROWS: usize = 3;
+
+// squeezes wide tables as long as every column stays readable, otherwise splits them, or turns
+// short ones into cards
+fn auto_table_mode(
+    rows: &[Vec<Vec<String>>],
+    natural: &[usize],
+    available_width: usize,
+) -> MdTableMode {
+    let fitted = fit_column_widths(natural, available_width);
+    let squashed = natural
+        .iter()
+        .zip(&fitted)
+        .any(|(natural, fitted)| fitted < natural && *fitted < TABLE_MIN_COLUMN);
+    if !squashed || natural.len() < 2 {
+        MdTableMode::Auto
+    } else if rows.len().saturating_sub(1) <= TABLE_SHORT_ROWS {
+        MdTableMode::Transpose
+    } else {
+        MdTableMode::Split
+    }
+}
 
-        for &i in &indices {
-            let fair_share = match remaining_cols > 0 {
-                true => remaining_budget / remaining_cols,
-                false => 0,
-            };
-            if column_widths[i] <= fair_share {
-                // This column fits within its share; keep natural width
-                new_widths[i] = column_widths[i];
-            } else {
-                // This column (and all wider ones) must share the remaining budget
-                new_widths[i] = fair_share.max(1);
-            }
-            remaining_budget = remaining_budget.saturating_sub(new_widths[i]);
-            remaining_cols -= 1;
+fn is_numeric_column(rows: &[Vec<Vec<String>>], col: usize) -> bool {
+    let mut cells = rows
+        .iter()
+        .skip(1)
+        .filter_map(|row| row.get(col))
+        .map(|cell| cell.join(" "))
+        .filter(|cell| !cell.trim().is_empty())
+        .peekable();
+    cells.peek().is_some() && cells.all(|cell| is_numeric_cell(&cell))
+}
+
+/// The widths columns get to fit in `available_width`, their natural widths when they already do.
+fn fit_column_widths(natural: &[usize], available_width: usize) -> Vec<usize> {
+    let border_overhead = 3 * natural.len() + 1;
+    let total_content_width: usize = natural.iter().sum();
+    let total_table_width = total_content_width + border_overhead;
+    if total_table_width <= available_width || total_content_width == 0 {
+        return natural.to_vec();
+    }
+
+    let target_content_width = available_width.saturating_sub(border_overhead);
+    // Waterfall algorithm: keep narrow columns at their natural width,
+    // shrink only the wider columns. Iterate from narrowest to widest:
+    // if a column fits within its equal share of remaining space, grant
+    // it its natural width; otherwise distribute remaining space equally
+    // among the remaining (wider) columns.
+    let mut indices: Vec<usize> = (0..natural.len()).collect();
+    indices.sort_by_key(|&i| natural[i]);
+
+    let mut new_widths: Vec<usize> = vec![0; natural.len()];
+    let mut remaining_budget = target_content_width;
+    let mut remaining_cols = natural.len();
+
+    for &i in &indices {
+        let fair_share = match remaining_cols > 0 {
+            true => remaining_budget / remaining_cols,
+            false => 0,
+        };
+        if natural[i] <= fair_share {
+            // This column fits within its share; keep natural width
+            new_widths[i] = natural[i];
+        } else {
+            // This column (and all wider ones) must share the remaining budget
+            new_widths[i] = fair_share.max(1);
         }
+        remaining_budget = remaining_budget.saturating_sub(new_widths[i]);
+        remaining_cols -= 1;
+    }
 
-        // Distribute any leftover due to integer division
-        let assigned: usize = new_widths.iter().sum();
-        if assigned < target_content_width {
-            let mut remainder = target_content_width - assigned;
-            // Give extra to widest columns first
-            indices.sort_by(|&a, &b| column_widths[b].cmp(&column_widths[a]));
-            for &i in &indices {
-                if remainder == 0 {
-                    break;
-                }
-                new_widths[i] += 1;
-                remainder -= 1;
+    // Distribute any leftover due to integer division
+    let assigned: usize = new_widths.iter().sum();
+    if assigned < target_content_width {
+        let mut remainder = target_content_width - assigned;
+        // Give extra to widest columns first
+        indices.sort_by(|&a, &b| natural[b].cmp(&natural[a]));
+        for &i in &indices {
+            if remainder == 0 {
+                break;
             }
+            new_widths[i] += 1;
+            remainder -= 1;
         }
+    }
+
+    new_widths
+}
 
-        column_widths = new_widths;
+// the columns that don't fit go into more tables stacked below, each repeating the first column
+// so rows can still be matched up
+fn split_table(
+    rows: &[Vec<Vec<String>>],
+    natural: &[usize],
+    alignments: &[TableAlignment],
+    available_width: usize,
+    ctx: &AnsiContext,
+) -> String {
+    let key_width = natural[0].min(available_width / 3);
+    let mut segments: Vec<Vec<usize>> = vec![vec![]];
+    for col in 1..natural.len() {
+        let segment = segments.last_mut().unwrap();
+        let width = key_width
+            + segment.iter().map(|&c| natural[c]).sum::<usize>()
+            + natural[col]
+            + 3 * (segment.len() + 2)
+            + 1;
+        if width > available_width && !segment.is_empty() {
+            segments.push(vec![col]);
+        } else {
+            segment.push(col);
+        }
     }
 
-    // Re-wrap cell contents to fit capped column widths and recalculate row heights
-    for (row_idx, row) in rows.iter_mut().enumerate() {
+    segments
+        .iter()
+        .map(|segment| {
+            let cols: Vec<usize> = std::iter::once(0).chain(segment.iter().copied()).collect();
+            let rows: Vec<Vec<Vec<String>>> = rows
+                .iter()
+                .map(|row| {
+                    cols.iter()
+                        .map(|&c| row.get(c).cloned().unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            let natural: Vec<usize> = cols.iter().map(|&c| natural[c]).collect();
+            let alignments: Vec<TableAlignment> = cols.iter().map(|&c| alignments[c]).collect();
+            let widths = fit_column_widths(&natural, available_width);
+            draw_table(&rows, &widths, &alignments, ctx)
+        })
+        .join("\n\n")
+}
+
+// a card per row, the first column as its title and every other column as a field
+fn transpose_table(rows: &[Vec<Vec<String>>], available_width: usize, ctx: &AnsiContext) -> String {
+    let header = &rows[0];
+    rows[1..]
+        .iter()
+        .map(|row| {
+            let card: Vec<Vec<Vec<String>>> = header
+                .iter()
+                .enumerate()
+                .map(|(i, name)| vec![name.clone(), row.get(i).cloned().unwrap_or_default()])
+                .collect();
+            let mut natural = [0, 0];
+            for field in &card {
+                for (i, cell) in field.iter().enumerate() {
+                    let width = cell.iter().map(|line| string_len(line)).max().unwrap_or(0);
+                    natural[i] = natural[i].max(width);
+                }
+            }
+            let widths = fit_column_widths(&natural, available_width);
+            draw_table(&card, &widths, &[TableAlignment::None; 2], ctx)
+        })
+        .join("\n\n")
+}
+
+fn draw_table(
+    rows: &[Vec<Vec<String>>],
+    column_widths: &[usize],
+    alignments: &[TableAlignment],
+    ctx: &AnsiContext,
+) -> String {
+    // Re-wrap cell contents to fit the column widths and calculate row heights
+    let mut rows = rows.to_vec();
+    let mut row_heights: Vec<usize> = Vec::new();
+    for row in rows.iter_mut() {
         let mut max_lines_in_row: usize = 1;
 
         for (col_idx, cell) in row.iter_mut().enumerate() {
@@ -691,7 +890,7 @@ fn render_table<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String {
             *cell = new_lines;
         }
 
-        row_heights[row_idx] = max_lines_in_row;
+        row_heights.push(max_lines_in_row);
     }
 
     let color = &ctx.theme.border.fg;
@@ -745,10 +944,8 @@ fn render_table<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String {
                         (padding / 2, padding - (padding / 2))
                     } else {
                         match alignments[col_idx] {
-                            comrak::nodes::TableAlignment::Center => {
-                                (padding / 2, padding - (padding / 2))
-                            }
-                            comrak::nodes::TableAlignment::Right => (padding, 0),
+                            TableAlignment::Center => (padding / 2, padding - (padding / 2)),
+                            TableAlignment::Right => (padding, 0),
                             _ => (0, padding),
                         }
                     };
@@ -776,27 +973,7 @@ fn render_table<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String {
         }
     }
 
-    let sps = node.data.borrow().sourcepos;
-    if ctx.center {
-        let le = string_len(result.lines().nth(1).unwrap_or_default());
-        let offset = sps.start.column.saturating_sub(1);
-        let offset = (ctx.wininfo.sc_width as usize - offset)
-            .saturating_sub(le)
-            .saturating_div(2);
-
-        result
-            .lines()
-            .map(|line| format!("{}{line}", " ".repeat(offset)))
-            .join("\n")
-    } else if ctx.should_wrap() {
-        let indent = ctx.indent();
-        result
-            .lines()
-            .map(|line| format!("{}{line}", " ".repeat(indent)))
-            .join("\n")
-    } else {
-        result
-    }
+    result
 }
 
 fn render_strong<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String {
@@ -833,6 +1010,29 @@ fn render_link<'a>(node: &'a AstNode<'a>, ctx: &mut AnsiContext) -> String {
     let cyan = &ctx.theme.cyan.fg;
     let start = format!("{UNDERLINE}{cyan}");
     let content = content.replace(RESET, &format!("{RESET}{start}"));
+
+    if let Some(refs) = ctx.link_refs.as_mut() {
+        // autolinks already show their url
+        let marker = if trim_ansi_string(content.clone()) == *url {
+            String::new()
+        } else {
+            format!("{}[{}]{RESET}", ctx.theme.comment.fg, refs.number(url))
+        };
+        let last = content.lines().count().saturating_sub(1);
+        return content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let marker = if i == last { marker.as_str() } else { "" };
+                if i == 0 {
+                    format!("{start}\u{f0339} {line}{RESET}{marker}")
+                } else {
+                    format!("\u{00A0}\u{00A0}{start}{line}{RESET}{marker}")
+                }
+            })
+            .join("\n");
+    }
+
     let osc8_start = format!("\x1b]8;;{}\x1b\\", url);
     let osc8_end = "\x1b]8;;\x1b\\";
     content
@@ -8,15 +8,19 @@ use strip_ansi_escapes::strip_str;
 use syntect::{
     easy::HighlightLines,
     highlighting::Style,
-    parsing::SyntaxReference,
+    parsing::{SyntaxReference, SyntaxSet},
     util::{LinesWithEndings, as_24_bit_terminal_escaped},
 };
+use tracing::debug;
 use unicode_width::UnicodeWidthStr;
 
-use crate::config::Theme;
+use crate::{config::Theme, diagrams};
 
 use super::render::{AnsiContext, BOLD, RESET};
 
+/// Every syntax mcat highlights with, shared by the markdown viewer, diffs and previews.
+pub static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(two_face::syntax::extra_newlines);
+
 static ANSI_ESCAPE_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
 
@@ -451,71 +455,246 @@ pub fn wrap_highlighted_line(
     buf
 }
 
-pub fn format_code_simple(code: &str, lang: &str, ctx: &AnsiContext, indent: usize) -> String {
-    let header = match get_lang_icon_and_color(lang) {
-        Some((icon, color)) => &format!("{color}{icon} {lang}{RESET}",),
-        None => lang,
-    };
+/// What docs tooling puts in a fence's info string besides the language, e.g.
+/// `rust {3,5-7} title="main.rs" showLineNumbers=12`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CodeInfo {
+    pub lang: String,
+    /// 1 based, inclusive line ranges to highlight.
+    pub highlight: Vec<(usize, usize)>,
+    pub title: Option<String>,
+    /// Set by `showLineNumbers`, forces line numbers and starts counting from it.
+    pub start_line: Option<usize>,
+}
+
+impl CodeInfo {
+    pub fn parse(info: &str) -> Self {
+        let mut out = CodeInfo::default();
+        for token in split_info(info) {
+            if let Some(ranges) = token.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+                out.highlight.extend(parse_ranges(ranges));
+            } else if let Some((key, value)) = token.split_once('=') {
+                let value = value.trim_matches(['"', '\'']);
+                match key {
+                    "title" | "filename" => out.title = Some(value.to_owned()),
+                    "showLineNumbers" => out.start_line = Some(value.parse().unwrap_or(1)),
+                    _ => {}
+                }
+            } else if token == "showLineNumbers" {
+                out.start_line = Some(1);
+            } else if out.lang.is_empty() {
+                // `js{3}`, the ranges glued to the language
+                match token.split_once('{') {
+                    Some((lang, ranges)) => {
+                        out.lang = lang.to_owned();
+                        out.highlight
+                            .extend(parse_ranges(ranges.trim_end_matches('}')));
+                    }
+                    None => out.lang = token.to_owned(),
+                }
+            }
+        }
+        out
+    }
+
+    pub fn highlights(&self, line: usize) -> bool {
+        self.highlight
+            .iter()
+            .any(|(from, to)| (*from..=*to).contains(&line))
+    }
+
+    /// `Some` for diff fences, holding the language of `diff-rust` style ones.
+    pub fn diff(&self) -> Option<Option<&str>> {
+        match self.lang.strip_prefix("diff") {
+            Some("") => Some(None),
+            Some(rest) => rest.strip_prefix('-').map(Some),
+            None => None,
+        }
+    }
+}
+
+// splits on whitespace outside of quotes and braces
+fn split_info(info: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let (mut start, mut quote, mut depth) = (None, None, 0);
+    for (i, c) in info.char_indices() {
+        match c {
+            '"' | '\'' if quote == Some(c) => quote = None,
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            '{' if quote.is_none() => depth += 1,
+            '}' if quote.is_none() => depth -= 1,
+            c if c.is_whitespace() && quote.is_none() && depth <= 0 => {
+                if let Some(from) = start.take() {
+                    tokens.push(&info[from..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(from) = start {
+        tokens.push(&info[from..]);
+    }
+    tokens
+}
+
+fn parse_ranges(ranges: &str) -> Vec<(usize, usize)> {
+    ranges
+        .split(',')
+        .filter_map(|r| {
+            let r = r.trim();
+            match r.split_once('-') {
+                Some((from, to)) => Some((from.trim().parse().ok()?, to.trim().parse().ok()?)),
+                None => r.parse().ok().map(|n| (n, n)),
+            }
+        })
+        .collect()
+}
+
+/// A highlighted line of a code block, without its newline.
+pub struct CodeLine {
+    pub text: String,
+    /// Highlighted (`{3,5-7}`) or added / removed diff lines.
+    pub bg: Option<String>,
+}
+
+impl CodeLine {
+    /// Fills each (possibly wrapped) line of `text` with the background, up to `width`.
+    pub fn paint(&self, text: &str, width: usize) -> String {
+        let Some(bg) = &self.bg else {
+            return text.to_owned();
+        };
+        text.lines()
+            .map(|line| {
+                let pad = width.saturating_sub(string_len(line));
+                let line = line.replace(RESET, &format!("{RESET}{bg}"));
+                format!("{bg}{line}{}{RESET}", " ".repeat(pad))
+            })
+            .join("\n")
+    }
+}
 
+/// Highlights `code` line by line. Diff fences get added / removed backgrounds, and
+/// `diff-rust` style ones highlight what's after the marker as the inner language.
+pub fn highlight_code(code: &str, info: &CodeInfo, ctx: &AnsiContext) -> Vec<CodeLine> {
+    let diff = info.diff();
+    let lang = match diff {
+        Some(Some(inner)) => inner,
+        _ => info.lang.as_str(),
+    };
     let ts = ctx.theme.to_syntect_theme();
-    let syntax = resolve_syntax(ctx, lang, code);
+    let syntax = resolve_syntax(&ctx.syntax_map, lang, code);
     let mut highlighter = HighlightLines::new(syntax, &ts);
+    let (added, removed) = ctx.theme.diff_bgs();
 
-    let line_count = code.lines().count().saturating_sub(1);
-    let content = LinesWithEndings::from(code)
+    LinesWithEndings::from(code)
         .enumerate()
-        .filter_map(|(i, line)| {
-            if line_count == i && line.trim().is_empty() {
-                return None;
+        .map(|(i, line)| {
+            let mut bg = info
+                .highlights(i + 1)
+                .then(|| ctx.theme.keyword_bg.bg.clone());
+            let is_header = ["+++", "---", "@@"].iter().any(|h| line.starts_with(h));
+            if diff.is_some() && !is_header {
+                match line.chars().next() {
+                    Some('+') => bg = Some(added.clone()),
+                    Some('-') => bg = Some(removed.clone()),
+                    _ => {}
+                }
+            }
+
+            let text = match diff {
+                Some(Some(_)) if is_header => {
+                    format!("{}{}{RESET}", ctx.theme.cyan.fg, line.trim_end())
+                }
+                Some(Some(_)) => {
+                    let (marker, rest) = match line.chars().next() {
+                        Some(m @ ('+' | '-' | ' ')) => (m, &line[1..]),
+                        _ => (' ', line),
+                    };
+                    let color = match marker {
+                        '+' => &ctx.theme.green.fg,
+                        '-' => &ctx.theme.red.fg,
+                        _ => &ctx.theme.comment.fg,
+                    };
+                    let ranges: Vec<(Style, &str)> =
+                        highlighter.highlight_line(rest, &SYNTAXES).unwrap();
+                    let highlighted = as_24_bit_terminal_escaped(&ranges[..], false);
+                    format!("{color}{marker}{highlighted}")
+                }
+                _ => {
+                    let ranges: Vec<(Style, &str)> =
+                        highlighter.highlight_line(line, &SYNTAXES).unwrap();
+                    as_24_bit_terminal_escaped(&ranges[..], false)
+                }
+            };
+            CodeLine {
+                text: text.trim_end_matches(['\n', '\r']).to_owned(),
+                bg,
             }
-            let ranges: Vec<(Style, &str)> = highlighter.highlight_line(line, &ctx.ps).unwrap();
-            let highlighted = as_24_bit_terminal_escaped(&ranges[..], false);
-            Some(format!("  {}", highlighted.trim_matches('\n')))
         })
-        .join("\n");
+        .collect()
+}
+
+fn code_header(info: &CodeInfo, close: &str) -> String {
+    let label = info.title.as_deref().unwrap_or(&info.lang);
+    match get_lang_icon_and_color(&info.lang) {
+        Some((icon, color)) => format!("{color}{icon} {label}{close}"),
+        None => label.to_owned(),
+    }
+}
+
+pub fn format_code_simple(code: &str, info: &CodeInfo, ctx: &AnsiContext, indent: usize) -> String {
+    let header = code_header(info, RESET);
+
+    let lines = highlight_code(code, info, ctx);
+    let last = code.lines().count().saturating_sub(1);
+    let last_blank = code.lines().last().is_some_and(|l| l.trim().is_empty());
 
     let sub_indent = 4usize;
     let sub_indent = " ".repeat(sub_indent.saturating_sub(indent));
     let (space, sub_space, indent, sub_indent) = info_for_wrapping(ctx, indent, "", &sub_indent);
-    let content = content
-        .lines()
-        .map(|line| {
-            let line = format!("{indent}{line}");
-            wrap_highlighted_line(line, space, sub_space, &sub_indent, true)
-                .trim_matches('\n')
-                .to_owned()
+    let content = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !(*i == last && last_blank))
+        .map(|(_, line)| {
+            let text = format!("{indent}  {}", line.text);
+            let wrapped = wrap_highlighted_line(text, space, sub_space, &sub_indent, true);
+            line.paint(wrapped.trim_matches('\n'), space + string_len(&indent))
         })
         .join("\n");
 
     format!("{indent}{header}\n{content}{RESET}")
 }
 
-pub fn resolve_syntax<'a>(ctx: &'a AnsiContext, lang: &str, code: &str) -> &'a SyntaxReference {
+/// Picks the syntax for a fence's language or a file's extension: `--syntax-map` first, then
+/// the name / extension itself, then the first line (shebangs, `<?xml`..), plain text otherwise.
+pub fn resolve_syntax(
+    syntax_map: &[(String, String)],
+    lang: &str,
+    code: &str,
+) -> &'static SyntaxReference {
     let key = lang.to_lowercase();
-    ctx.syntax_map
+    syntax_map
         .iter()
         .find(|(ext, _)| *ext == key)
-        .and_then(|(_, syn)| ctx.ps.find_syntax_by_token(syn))
-        .or_else(|| ctx.ps.find_syntax_by_token(lang))
+        .and_then(|(_, syn)| SYNTAXES.find_syntax_by_token(syn))
+        .or_else(|| SYNTAXES.find_syntax_by_token(&key))
         .or_else(|| {
             code.lines()
                 .next()
-                .and_then(|l| ctx.ps.find_syntax_by_first_line(l))
+                .and_then(|l| SYNTAXES.find_syntax_by_first_line(l))
         })
-        .unwrap_or_else(|| ctx.ps.find_syntax_plain_text())
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text())
 }
 
-pub fn format_code_full(code: &str, lang: &str, ctx: &AnsiContext) -> String {
-    let ts = ctx.theme.to_syntect_theme();
-    let syntax = resolve_syntax(ctx, lang, code);
-    let mut highlighter = HighlightLines::new(syntax, &ts);
-
-    let header = match get_lang_icon_and_color(lang) {
-        Some((icon, color)) => &format!("{color}{icon} {lang}",),
-        None => lang,
-    };
+pub fn format_code_full(code: &str, info: &CodeInfo, ctx: &AnsiContext) -> String {
+    let header = code_header(info, "");
+    let lines = highlight_code(code, info, ctx);
 
-    let max_lines = code.lines().count();
+    let first_num = info.start_line.unwrap_or(1);
+    let max_lines = first_num + code.lines().count().saturating_sub(1);
     let num_width = max_lines.to_string().chars().count() + 2;
     // -1 because the indent is 1 based
     let term_width = ctx.wininfo.sc_width;
@@ -543,16 +722,15 @@ pub fn format_code_full(code: &str, lang: &str, ctx: &AnsiContext) -> String {
 
     let prefix = format!("{}{color}│{RESET}     ", " ".repeat(num_width));
     let sub_text_size = text_size.saturating_sub(4); // 4 extra space for visual indent.
-    for (num, line) in (1..).zip(LinesWithEndings::from(code)) {
+    for (num, line) in (first_num..).zip(&lines) {
         let left_space = num_width - num.to_string().chars().count();
         let left_offset = left_space / 2;
         let right_offset = left_space - left_offset;
-        let ranges: Vec<(Style, &str)> = highlighter.highlight_line(line, &ctx.ps).unwrap();
-        let highlighted = as_24_bit_terminal_escaped(&ranges[..], false);
         let highlighted =
-            wrap_highlighted_line(highlighted, text_size, sub_text_size, &prefix, true);
+            wrap_highlighted_line(line.text.clone(), text_size, sub_text_size, &prefix, true);
+        let highlighted = line.paint(highlighted.trim_end_matches('\n'), text_size + 1);
         buffer.push_str(&format!(
-            "{color}{}{num}{}│ {RESET}{}",
+            "{color}{}{num}{}│ {RESET}{}\n",
             " ".repeat(left_offset),
             " ".repeat(right_offset),
             highlighted
@@ -568,14 +746,11 @@ pub fn format_code_full(code: &str, lang: &str, ctx: &AnsiContext) -> String {
     buffer
 }
 
-pub fn format_code_box(code: &str, lang: &str, title: &str, ctx: &AnsiContext) -> String {
+pub fn format_code_box(code: &str, info: &CodeInfo, title: &str, ctx: &AnsiContext) -> String {
     let term_width = ctx.wininfo.sc_width as usize;
     let color = &ctx.theme.border.fg;
     let content = code.trim();
-
-    let ts = ctx.theme.to_syntect_theme();
-    let syntax = resolve_syntax(ctx, lang, code);
-    let mut highlighter = HighlightLines::new(syntax, &ts);
+    let lines = highlight_code(content, info, ctx);
 
     let max_line_width = content
         .lines()
@@ -605,11 +780,9 @@ pub fn format_code_box(code: &str, lang: &str, title: &str, ctx: &AnsiContext) -
     let prefix = format!("{color}│{RESET}     ");
     let content_width = box_width.saturating_sub(4); // -4 for "│ " on each side
     let sub_content_width = content_width.saturating_sub(4); // 4 spaces for visual indent
-    for line in LinesWithEndings::from(content) {
-        let ranges: Vec<(Style, &str)> = highlighter.highlight_line(line, &ctx.ps).unwrap();
-        let highlighted = as_24_bit_terminal_escaped(&ranges[..], false);
+    for line in &lines {
         let wrapped = wrap_highlighted_line(
-            highlighted,
+            line.text.clone(),
             content_width,
             sub_content_width,
             &prefix,
@@ -625,12 +798,9 @@ pub fn format_code_box(code: &str, lang: &str, title: &str, ctx: &AnsiContext) -
                 content_width + 2 // +2 for reversing the indent to "| " " |" spaces and indent
             };
             let padding = av_space.saturating_sub(visible_len);
+            let padded = format!("{}{}", wrapped_line, " ".repeat(padding));
 
-            buffer.push_str(&format!(
-                "{}{} {color}│{RESET}\n",
-                wrapped_line,
-                " ".repeat(padding)
-            ));
+            buffer.push_str(&format!("{} {color}│{RESET}\n", line.paint(&padded, 0)));
         }
     }
 
@@ -650,6 +820,19 @@ pub fn format_tb(ctx: &AnsiContext, offset: usize) -> String {
     format!("{border}{br}{RESET}")
 }
 
+/// Whether a table cell holds a number, allowing for thousands separators, currency signs and
+/// percentages (`1,234.5`, `$12`, `-3%`).
+pub fn is_numeric_cell(cell: &str) -> bool {
+    let cell = cell.trim();
+    let cell = cell.strip_prefix(['+', '-']).unwrap_or(cell);
+    let cell = cell.trim_start_matches(['$', '€', '£', '¥']);
+    let cell = cell.strip_suffix('%').unwrap_or(cell);
+    let digits: String = cell.chars().filter(|c| !matches!(c, ',' | '_')).collect();
+    digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && digits.chars().any(|c| c.is_ascii_digit())
+        && digits.parse::<f64>().is_ok()
+}
+
 #[rustfmt::skip]
 pub fn to_superscript(ch: char) -> Option<char> {
     Some(match ch {
@@ -770,7 +953,10 @@ pub fn prettify_latex(src: &str, ctx: &AnsiContext) -> String {
     out
 }
 
-pub fn preprocess_ast<'a>(root: &'a AstNode<'a>, theme: &Theme) {
+/// Normalizes quarto style fences, and turns diagram fences into svg images when `images`
+/// will be shown (they stay as code otherwise, charts then get a unicode fallback).
+pub fn preprocess_ast<'a>(root: &'a AstNode<'a>, theme: &Theme, images: bool) {
+    let custom = theme.to_custom();
     for node in root.descendants() {
         if !matches!(node.data.borrow().value, NodeValue::CodeBlock(_)) {
             continue;
@@ -799,22 +985,26 @@ pub fn preprocess_ast<'a>(root: &'a AstNode<'a>, theme: &Theme) {
             cb.info = info;
         }
 
-        let mermaid_source = {
+        if !images {
+            continue;
+        }
+        let diagram = {
             let data = node.data.borrow();
             let NodeValue::CodeBlock(ref cb) = data.value else {
                 unreachable!()
             };
-            matches!(cb.info.trim(), "mermaid" | "mmd").then(|| cb.literal.clone())
+            diagrams::find(&cb.info).map(|renderer| (renderer, cb.literal.clone()))
         };
-        if let Some(source) = mermaid_source {
-            let mut opts = mermaid_rs_renderer::RenderOptions::modern();
-            opts.theme = theme.to_custom().to_mermaid_theme();
-            if let Ok(svg) = mermaid_rs_renderer::render_with_options(&source, opts) {
-                let b64 = base64::engine::general_purpose::STANDARD.encode(svg.as_bytes());
-                node.data.borrow_mut().value = NodeValue::Image(Box::new(NodeLink {
-                    url: format!("data:image/svg+xml;base64,{b64}"),
-                    title: String::new(),
-                }));
+        if let Some((renderer, source)) = diagram {
+            match renderer.render(&source, &custom) {
+                Ok(svg) => {
+                    let b64 = base64::engine::general_purpose::STANDARD.encode(svg.as_bytes());
+                    node.data.borrow_mut().value = NodeValue::Image(Box::new(NodeLink {
+                        url: format!("data:image/svg+xml;base64,{b64}"),
+                        title: String::new(),
+                    }));
+                }
+                Err(e) => debug!(error = %e, "failed to render diagram, kept as code"),
             }
         }
     }
@@ -823,10 +1013,13 @@ pub fn preprocess_ast<'a>(root: &'a AstNode<'a>, theme: &Theme) {
 // we only test core wrapping logic..
 #[cfg(test)]
 mod tests {
-    use rasteroid::{RasterEncoder, term_misc::Wininfo};
+    use rasteroid::{RasterEncoder, multiplexer::Multiplexer, term_misc::Wininfo};
 
     use crate::{
-        config::McatConfig, markdown_viewer::image_preprocessor::ImagePreprocessor,
+        config::{McatConfig, MdTableMode},
+        markdown_viewer::{
+            comrak_options, image_preprocessor::ImagePreprocessor, render::parse_node,
+        },
         themes::CustomTheme,
     };
 
@@ -842,11 +1035,12 @@ mod tests {
             sc_height: 20,
             spx_width: 1920,
             spx_height: 1080,
-            is_tmux: false,
+            multiplexer: Multiplexer::None,
             needs_inline: true,
+            text_mode: Default::default(),
+            color_depth: Default::default(),
         });
         AnsiContext {
-            ps: two_face::syntax::extra_newlines(),
             theme: CustomTheme::github(),
             wininfo: conf.wininfo.clone().unwrap(),
             hide_line_numbers: false,
@@ -860,9 +1054,45 @@ mod tests {
             force_simple_code_block: 0,
             list_depth: 0,
             syntax_map: vec![],
+            link_refs: None,
+            table_mode: MdTableMode::Auto,
         }
     }
 
+    #[test]
+    fn code_info_attributes() {
+        let info = CodeInfo::parse(r#"rust {3,5-7} title="my main.rs" showLineNumbers=12"#);
+        assert_eq!(info.lang, "rust");
+        assert_eq!(info.highlight, vec![(3, 3), (5, 7)]);
+        assert_eq!(info.title.as_deref(), Some("my main.rs"));
+        assert_eq!(info.start_line, Some(12));
+        assert!(info.highlights(6) && !info.highlights(4));
+
+        let info = CodeInfo::parse("js{2} showLineNumbers");
+        assert_eq!((info.lang.as_str(), info.start_line), ("js", Some(1)));
+        assert_eq!(info.highlight, vec![(2, 2)]);
+    }
+
+    #[test]
+    fn code_info_diff_langs() {
+        assert_eq!(CodeInfo::parse("diff").diff(), Some(None));
+        assert_eq!(CodeInfo::parse("diff-rust").diff(), Some(Some("rust")));
+        assert_eq!(CodeInfo::parse("rust").diff(), None);
+    }
+
+    #[test]
+    fn diff_lines_get_backgrounds() {
+        let ctx = make_ctx();
+        let lines = highlight_code(
+            "@@ -1 +1 @@\n-a\n+b\n c\n",
+            &CodeInfo::parse("diff-rust"),
+            &ctx,
+        );
+        let bgs: Vec<bool> = lines.iter().map(|l| l.bg.is_some()).collect();
+        assert_eq!(bgs, vec![false, true, true, false]);
+        assert!(strip_str(&lines[1].text).starts_with("-a"));
+    }
+
     #[test]
     fn test_trim_ansi_string_trims() {
         assert_eq!(trim_ansi_string("  hello  ".into()), "hello");
@@ -1040,6 +1270,65 @@ mod tests {
         let expected = wrap_highlighted_line(format!("  {text}"), 46, 46, "  ", false);
         assert_eq!(result, expected);
     }
+
+    fn render_table(md: &str, mode: MdTableMode) -> String {
+        let arena = comrak::Arena::new();
+        let root = comrak::parse_document(&arena, md, &comrak_options());
+        let table = root
+            .descendants()
+            .find(|n| matches!(n.data.borrow().value, NodeValue::Table(_)))
+            .unwrap();
+        let mut ctx = make_ctx();
+        ctx.table_mode = mode;
+        // as if nested, so the table isn't indented
+        ctx.collecting_depth = 1;
+        strip_str(parse_node(table, &mut ctx))
+    }
+
+    const WIDE_TABLE: &str = "| name | first column | second column | third column | fourth column |\n\
+         |---|---|---|---|---|\n\
+         | alpha | some text here | more text here | and even more | the last one |\n\
+         | beta | 1 | 2 | 3 | 4 |\n";
+
+    #[test]
+    fn numeric_cells() {
+        for cell in ["42", "-3.5", "1,234,567", "$12.50", "+7%", ".5", "€ 3"] {
+            assert_eq!(is_numeric_cell(cell), cell != "€ 3", "{cell}");
+        }
+        for cell in ["", "n/a", "inf", "NaN", "v1.2", "12 apples"] {
+            assert!(!is_numeric_cell(cell), "{cell}");
+        }
+    }
+
+    #[test]
+    fn numeric_columns_align_right() {
+        let table = render_table(
+            "| item | price |\n|---|---|\n| tea | 3 |\n| cake | 12.50 |\n",
+            MdTableMode::Auto,
+        );
+        assert!(table.contains("│ tea  │     3 │"), "{table}");
+        assert!(table.contains("│ cake │ 12.50 │"), "{table}");
+    }
+
+    #[test]
+    fn wide_tables_split_and_transpose() {
+        let split = render_table(WIDE_TABLE, MdTableMode::Split);
+        assert!(split.lines().all(|l| string_len(l) <= 50), "{split}");
+        let segments = split.matches('╭').count();
+        assert!(segments >= 2, "{split}");
+        // the key column repeats in every segment
+        assert_eq!(split.matches("alpha").count(), segments);
+
+        let cards = render_table(WIDE_TABLE, MdTableMode::Transpose);
+        assert_eq!(cards.matches('╭').count(), 2, "{cards}");
+        assert!(
+            cards.contains("│ fourth column │ the last one   │"),
+            "{cards}"
+        );
+
+        // two data rows squashed into 50 columns read best as cards
+        assert_eq!(render_table(WIDE_TABLE, MdTableMode::Auto), cards);
+    }
 }
 
 #[cfg(test)]
@@ -1051,7 +1340,7 @@ mod preprocess_ast_tests {
     fn infos_and_images(md: &str) -> (Vec<String>, usize) {
         let arena = Arena::new();
         let root = parse_document(&arena, md, &comrak::Options::default());
-        preprocess_ast(root, &Theme::default());
+        preprocess_ast(root, &Theme::default(), true);
 
         let mut infos = Vec::new();
         let mut images = 0;
@@ -1088,9 +1377,44 @@ mod preprocess_ast_tests {
         assert_eq!(images, 3);
     }
 
+    #[test]
+    fn dot_fences_become_images() {
+        let (infos, images) = infos_and_images(
+            "```dot\ndigraph { a -> b; }\n```\n\n```{graphviz}\ngraph { a -- b }\n```\n",
+        );
+        assert_eq!(infos, Vec::<String>::new());
+        assert_eq!(images, 2);
+    }
+
+    #[test]
+    fn diagrams_stay_code_without_images() {
+        let arena = Arena::new();
+        let md = "```chart\nx,y\na,1\n```\n\n```mermaid\nA-->B\n```\n";
+        let root = parse_document(&arena, md, &comrak::Options::default());
+        preprocess_ast(root, &Theme::default(), false);
+        assert!(
+            root.descendants()
+                .all(|n| !matches!(n.data.borrow().value, NodeValue::Image(_)))
+        );
+    }
+
     #[test]
     fn non_codeblock_input_untouched() {
         let (infos, images) = infos_and_images("# heading\n\nparagraph `inline`\n");
         assert!(infos.is_empty() && images == 0);
     }
+
+    #[test]
+    fn syntaxes_resolve_from_the_map_name_extension_or_shebang() {
+        let map = vec![("conf".to_owned(), "toml".to_owned())];
+        let name = |lang: &str, code: &str| resolve_syntax(&map, lang, code).name.clone();
+        assert_eq!(name("conf", ""), "TOML");
+        assert_eq!(name("RS", ""), "Rust");
+        assert_eq!(name("python", ""), "Python");
+        assert_eq!(
+            name("", "#!/bin/bash\necho hi\n"),
+            "Bourne Again Shell (bash)"
+        );
+        assert_eq!(name("nope", "just words"), "Plain Text");
+    }
 }
@@ -1,15 +1,26 @@
 use anyhow::{Context, Result};
+use ffmpeg_sidecar::event::FfmpegEvent;
 use flate2::read::GzDecoder;
 use hayro::{RenderCache, hayro_syntax::Pdf};
-use image::{DynamicImage, GenericImage};
+use image::{
+    AnimationDecoder, DynamicImage, GenericImage, ImageDecoder, ImageFormat, ImageReader,
+    RgbaImage,
+    codecs::{
+        gif::{GifDecoder, GifEncoder, Repeat},
+        png::PngDecoder,
+        webp::WebPDecoder,
+    },
+    metadata::Orientation,
+};
 use infer::{
     app::is_exe,
     archive::is_pdf,
-    image::{is_gif, is_jxl},
+    image::{is_avif, is_gif, is_heif, is_jxl},
     is_video,
 };
 use lzma_rust2::XzReader;
 use markdownify::MarkdownifyInput;
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
 use pelite::PeFile;
 use rasteroid::{
     RasterEncoder,
@@ -21,21 +32,24 @@ use resvg::{
     tiny_skia,
     usvg::{self, Options, Tree},
 };
+use serde_json::json;
 use std::{
+    borrow::Cow,
     fs::{self},
     io::{Cursor, Read, Write},
     path::{Path, PathBuf},
     process::Command,
+    rc::Rc,
     sync::Arc,
 };
 use tempfile::NamedTempFile;
 
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
-    cdp::ChromeHeadless,
+    cdp::{ChromeHeadless, Viewport},
     config::{McatConfig, Theme},
-    fetch_manager, markdown_viewer,
+    diagrams, fetch_manager, markdown_viewer,
     prompter::RUNTIME,
 };
 
@@ -47,10 +61,14 @@ pub enum McatKind {
     Html,
 
     Video,
-    Gif, // have different logic on iterm
+    Gif, // any animated image (gif, apng, animated webp), decoded without ffmpeg
 
     Image,
     Mermaid,
+    // the other diagram languages, rendered through `diagrams`
+    Dot,
+    PlantUml,
+    D2,
     Svg, // svg is handled manually, since its not supported by the image crate
     JpegXL,
 
@@ -80,12 +98,13 @@ impl McatKind {
         let handlers: &[(Checker, &[&str], McatKind)] = &[
             (is_pdf, &["pdf"], Self::Pdf),
             (is_gif, &["gif"], Self::Gif), // gif most be before video check.
+            (is_animated_image, &["apng"], Self::Gif), // and before the image check.
             (
-                |b| image::guess_format(b).is_ok(),
+                |b| image::guess_format(b).is_ok() || is_heif(b) || is_avif(b),
                 &[
-                    "png", "jpg", "jpeg", "webp", "tiff", "tif", "bmp", "ico", "avif", "exr",
-                    "qoi", "hdr", "dds", "farbfeld", "pnm", "pbm", "pgm", "ppm", "pam", "tga",
-                    "pcx",
+                    "png", "jpg", "jpeg", "webp", "tiff", "tif", "bmp", "ico", "avif", "heic",
+                    "heif", "exr", "qoi", "hdr", "dds", "farbfeld", "pnm", "pbm", "pgm", "ppm",
+                    "pam", "tga", "pcx",
                 ],
                 Self::Image,
             ),
@@ -100,6 +119,9 @@ impl McatKind {
             (is_jxl, &["jxl"], Self::JpegXL),
             (is_svg, &["svg"], Self::Svg),
             (|_| false, &["mermaid"], Self::Mermaid),
+            (|_| false, &["dot", "gv"], Self::Dot),
+            (|_| false, &["puml", "plantuml", "pu"], Self::PlantUml),
+            (|_| false, &["d2"], Self::D2),
             (|_| false, &["html", "htm"], Self::Html),
             (
                 // markdown exts need to be synced with markdownfiy.
@@ -127,6 +149,17 @@ impl McatKind {
     pub fn from_ext(ext: &str) -> Option<Self> {
         Self::detect(None, Some(ext))
     }
+
+    /// The fence info string of diagram kinds, what `diagrams::render` takes.
+    pub fn diagram_lang(&self) -> Option<&'static str> {
+        match self {
+            McatKind::Mermaid => Some("mermaid"),
+            McatKind::Dot => Some("dot"),
+            McatKind::PlantUml => Some("plantuml"),
+            McatKind::D2 => Some("d2"),
+            _ => None,
+        }
+    }
 }
 
 type Checker = fn(&[u8]) -> bool;
@@ -206,6 +239,20 @@ impl McatFile {
         Ok(html)
     }
 
+    /// A pdf of the file. documents go through their themed html and chromium's print,
+    /// pdfs (and tex / typst, compiled) are kept as they are.
+    pub fn to_pdf(&self, config: &McatConfig) -> Result<Vec<u8>> {
+        let html = match self.kind {
+            McatKind::Pdf => return Ok(self.bytes.clone()),
+            McatKind::Tex => return Ok(self.tex_to_pdf()?.bytes),
+            McatKind::Typst => return Ok(self.typst_to_pdf()?.bytes),
+            McatKind::Video => anyhow::bail!("Cannot turn a video into a pdf"),
+            McatKind::Html => encoding_rs::UTF_8.decode(&self.bytes).0.into_owned(),
+            _ => self.to_html(Some(config.theme.clone()), config.inline_images_in_md)?,
+        };
+        html_to_pdf(&with_base_href(&html, self.path.as_deref()), config)
+    }
+
     pub fn to_image(&self, config: &McatConfig, pad: bool, resize: bool) -> Result<DynamicImage> {
         debug!(kind = ?self.kind, pad, resize, "converting to image");
         let wininfo = config
@@ -230,14 +277,15 @@ impl McatFile {
                     self.id.clone(),
                     true,
                 )?;
-                html_to_image(&file)?
+                html_to_image(&file, config)?
             }
-            McatKind::Mermaid => {
-                let theme = config.theme.to_custom().to_mermaid_theme();
-                let mut opts = mermaid_rs_renderer::RenderOptions::modern();
-                opts.theme = theme;
-                let svg =
-                    mermaid_rs_renderer::render_with_options(str::from_utf8(&self.bytes)?, opts)?;
+            McatKind::Mermaid | McatKind::Dot | McatKind::PlantUml | McatKind::D2 => {
+                let lang = self.kind.diagram_lang().unwrap_or_default();
+                let svg = diagrams::render(
+                    lang,
+                    str::from_utf8(&self.bytes)?,
+                    &config.theme.to_custom(),
+                )?;
                 svg_to_image(
                     svg.as_bytes(),
                     wininfo,
@@ -248,12 +296,12 @@ impl McatFile {
                     resize,
                 )?
             }
-            McatKind::Html => html_to_image(self)?,
+            McatKind::Html => html_to_image(self, config)?,
             McatKind::Video => anyhow::bail!(
                 "Cannot turn video format to image, this is most likely a bug and should not reach here."
             ),
             McatKind::Gif => image::load_from_memory(&self.bytes)?,
-            McatKind::Image => image::load_from_memory(&self.bytes)?,
+            McatKind::Image => decode_image(&self.bytes, config.no_img_metadata)?,
             McatKind::Svg => {
                 return svg_to_image(&self.bytes, wininfo, width, height, is_ascii, pad, resize);
             }
@@ -295,6 +343,13 @@ impl McatFile {
 
     pub fn to_album(&self, config: &McatConfig) -> Result<Vec<DynamicImage>> {
         match self.kind {
+            McatKind::PreMarkdown | McatKind::Markdown | McatKind::Html if config.html_tiles => {
+                let wininfo = config
+                    .wininfo
+                    .as_ref()
+                    .context("this is likely a bug, wininfo isn't set when splitting a page")?;
+                Ok(screen_tiles(self.to_image(config, false, false)?, wininfo))
+            }
             McatKind::PreMarkdown
             | McatKind::Markdown
             | McatKind::Html
@@ -305,6 +360,9 @@ impl McatFile {
             | McatKind::Exe
             | McatKind::JpegXL
             | McatKind::Mermaid
+            | McatKind::Dot
+            | McatKind::PlantUml
+            | McatKind::D2
             | McatKind::Lnk => {
                 let img = self.to_image(config, false, false)?;
                 Ok(vec![img])
@@ -432,7 +490,27 @@ impl McatFile {
         })
     }
 
+    /// Returns the animation as gif bytes, apng and webp animations are re-encoded.
+    pub fn to_gif(&self) -> Result<Cow<'_, [u8]>> {
+        if is_gif(&self.bytes) {
+            return Ok(Cow::Borrowed(&self.bytes));
+        }
+
+        let frames = decode_animation(&self.bytes)?;
+        let mut buf = Vec::new();
+        {
+            let mut encoder = GifEncoder::new_with_speed(&mut buf, 10);
+            encoder.set_repeat(Repeat::Infinite)?;
+            encoder.encode_frames(frames)?;
+        }
+        Ok(Cow::Owned(buf))
+    }
+
     pub fn to_frames(&self) -> Result<(Box<dyn Iterator<Item = rasteroid::VideoFrame>>, u32, u32)> {
+        if self.kind == McatKind::Gif {
+            return animation_to_frames(&self.bytes);
+        }
+
         let mut command = fetch_manager::get_ffmpeg().context(
             "ffmpeg isn't installed. either install it manually, or call `mcat --fetch-ffmpeg`",
         )?;
@@ -477,6 +555,28 @@ impl McatFile {
     }
 }
 
+/// Reads the duration of a video from ffmpeg's input metadata, without decoding it.
+pub fn video_duration(path: &Path) -> Result<f64> {
+    let mut command = fetch_manager::get_ffmpeg().context("ffmpeg isn't installed")?;
+    command.input(path.to_string_lossy());
+    let mut child = command.spawn()?;
+
+    let duration = child.iter()?.find_map(|event| match event {
+        FfmpegEvent::ParsedDuration(d) => Some(d.duration),
+        _ => None,
+    });
+    let _ = child.kill();
+    let _ = child.wait();
+
+    duration.context("ffmpeg didn't report a duration")
+}
+
+pub fn pdf_page_count(bytes: &[u8]) -> Result<usize> {
+    let pdf = Pdf::new(Arc::new(bytes.to_vec()))
+        .map_err(|e| anyhow::anyhow!("failed to load PDF: {e:?}"))?;
+    Ok(pdf.pages().len())
+}
+
 // converting methods.
 
 pub fn svg_to_image(
@@ -500,17 +600,18 @@ pub fn svg_to_image(
     let pixmap_size = tree.size();
     let src_width = pixmap_size.width();
     let src_height = pixmap_size.height();
+    let (cell_w, cell_h) = wininfo.text_mode.cell_size();
 
     let width = match width {
         Some(w) if needs_resize => match is_ascii {
-            true => wininfo.dim_to_cells(w, SizeDirection::Width)?,
+            true => wininfo.dim_to_cells(w, SizeDirection::Width)? * cell_w,
             false => wininfo.dim_to_px(w, SizeDirection::Width)?,
         },
         _ => (src_width as u32).min(wininfo.spx_width as u32),
     };
     let height = match height {
         Some(h) if needs_resize => match is_ascii {
-            true => wininfo.dim_to_cells(h, SizeDirection::Height)? * 2,
+            true => wininfo.dim_to_cells(h, SizeDirection::Height)? * cell_h,
             false => wininfo.dim_to_px(h, SizeDirection::Height)?,
         },
         _ => (src_height as u32).min(wininfo.spx_height as u32),
@@ -720,21 +821,295 @@ pub fn url_to_image(bytes: &[u8]) -> Result<DynamicImage> {
     }
 }
 
-pub fn html_to_image(source: &McatFile) -> Result<DynamicImage> {
+pub fn html_to_image(source: &McatFile, config: &McatConfig) -> Result<DynamicImage> {
     let (html, _, _) = encoding_rs::UTF_8.decode(&source.bytes);
     let mut tmp_file = NamedTempFile::with_suffix(".html")?;
     tmp_file.write_all(html.as_bytes())?;
     let url = Url::from_file_path(tmp_file.path())
         .map_err(|_| anyhow::anyhow!("failed to create url for chromium"))?;
 
+    let viewport = Viewport {
+        width: config
+            .html_width
+            .or_else(|| config.wininfo.as_ref().map(|w| u32::from(w.spx_width))),
+        height: config
+            .wininfo
+            .as_ref()
+            .map_or(1080, |w| u32::from(w.spx_height)),
+        scale: config.html_scale,
+        full_page: !config.html_viewport,
+    };
     let img_bytes: Vec<u8> = RUNTIME.block_on(async {
         let browser = ChromeHeadless::new(url.as_str()).await?;
-        browser.capture_screenshot().await
+        browser.capture_screenshot(&viewport).await
     })?;
 
     Ok(image::load_from_memory(&img_bytes)?)
 }
 
+/// Cuts a tall capture into tiles with the screen's aspect ratio, the last one is shorter.
+fn screen_tiles(img: DynamicImage, wininfo: &Wininfo) -> Vec<DynamicImage> {
+    let tile_height = (u64::from(img.width()) * u64::from(wininfo.spx_height)
+        / u64::from(wininfo.spx_width.max(1)))
+    .max(1) as u32;
+    if img.height() <= tile_height {
+        return vec![img];
+    }
+    (0..img.height())
+        .step_by(tile_height as usize)
+        .map(|y| img.crop_imm(0, y, img.width(), tile_height.min(img.height() - y)))
+        .collect()
+}
+
+pub fn html_to_pdf(html: &str, config: &McatConfig) -> Result<Vec<u8>> {
+    let mut tmp_file = NamedTempFile::with_suffix(".html")?;
+    tmp_file.write_all(html.as_bytes())?;
+    let url = Url::from_file_path(tmp_file.path())
+        .map_err(|_| anyhow::anyhow!("failed to create url for chromium"))?;
+    let params = print_params(config);
+
+    RUNTIME.block_on(async {
+        let browser = ChromeHeadless::new(url.as_str()).await?;
+        browser.print_to_pdf(params).await
+    })
+}
+
+// the parameters of chromium's `Page.printToPDF`, sizes in inches
+fn print_params(config: &McatConfig) -> serde_json::Value {
+    let (width, height) = config.pdf_page.inches();
+    let margin = config.pdf_margin;
+    let has_header_footer = config.pdf_header.is_some() || config.pdf_footer.is_some();
+    // chromium fills in its own date / title header for a missing one
+    let empty = "<span></span>";
+    json!({
+        "paperWidth": width,
+        "paperHeight": height,
+        "marginTop": margin,
+        "marginBottom": margin,
+        "marginLeft": margin,
+        "marginRight": margin,
+        "printBackground": true,
+        "displayHeaderFooter": has_header_footer,
+        "headerTemplate": config.pdf_header.as_deref().unwrap_or(empty),
+        "footerTemplate": config.pdf_footer.as_deref().unwrap_or(empty),
+    })
+}
+
+// html is loaded from a temp file, relative images and links have to point back next to the source
+fn with_base_href(html: &str, source: Option<&Path>) -> String {
+    let Some(dir) = source
+        .and_then(|p| p.canonicalize().ok())
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .and_then(|dir| Url::from_directory_path(dir).ok())
+    else {
+        return html.to_owned();
+    };
+    let base = format!("<base href=\"{dir}\">");
+    match html.find("<head>") {
+        Some(i) => format!("{}{base}{}", &html[..i + 6], &html[i + 6..]),
+        None => format!("{base}{html}"),
+    }
+}
+
+/// decodes gif / apng / animated webp, looping forever like ffmpeg's `-stream_loop -1`.
+fn animation_to_frames(
+    bytes: &[u8],
+) -> Result<(Box<dyn Iterator<Item = rasteroid::VideoFrame>>, u32, u32)> {
+    // browsers bump tiny delays up, many gifs rely on it
+    const MIN_DELAY: f32 = 0.02;
+    const DEFAULT_DELAY: f32 = 0.1;
+
+    let frames = decode_animation(bytes)?;
+
+    let mut timestamp = 0.0;
+    let frames: Vec<rasteroid::VideoFrame> = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = numer as f32 / denom.max(1) as f32 / 1000.0;
+            let current = (DynamicImage::ImageRgba8(frame.into_buffer()), timestamp);
+            timestamp += if delay < MIN_DELAY {
+                DEFAULT_DELAY
+            } else {
+                delay
+            };
+            current
+        })
+        .collect();
+    let duration = timestamp;
+
+    let first = frames.first().context("no frames found")?;
+    let width = first.0.width();
+    let height = first.0.height();
+
+    let loops = if frames.len() > 1 { usize::MAX } else { 1 };
+    let frames = Rc::new(frames);
+    let looped = (0..loops).flat_map(move |n| {
+        let frames = frames.clone();
+        (0..frames.len()).map(move |i| {
+            let (img, timestamp) = &frames[i];
+            (img.clone(), timestamp + n as f32 * duration)
+        })
+    });
+
+    Ok((Box::new(looped), width, height))
+}
+
+/// Decodes an image the way it's meant to be seen, rotated by its exif orientation and with its
+/// icc profile converted to srgb. `raw` skips both.
+/// formats the image crate can't decode (heic, avif) go through ffmpeg.
+pub fn decode_image(bytes: &[u8], raw: bool) -> Result<DynamicImage> {
+    decode_image_or(bytes, raw, ffmpeg_to_image)
+}
+
+fn decode_image_or(
+    bytes: &[u8],
+    raw: bool,
+    fallback: impl FnOnce(&[u8]) -> Result<DynamicImage>,
+) -> Result<DynamicImage> {
+    match decode_natively(bytes, raw) {
+        Err(image::ImageError::Unsupported(e)) => {
+            debug!(error = %e, "unsupported by the image crate, decoding with ffmpeg");
+            fallback(bytes)
+        }
+        result => Ok(result?),
+    }
+}
+
+fn decode_natively(bytes: &[u8], raw: bool) -> image::ImageResult<DynamicImage> {
+    if raw {
+        return image::load_from_memory(bytes);
+    }
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let icc = decoder.icc_profile().ok().flatten();
+    let mut img = DynamicImage::from_decoder(decoder)?;
+
+    if let Some(icc) = icc {
+        match icc_to_srgb(&img, &icc) {
+            Ok(Some(converted)) => img = converted,
+            Ok(None) => {}
+            Err(e) => debug!(error = %e, "failed to apply the icc profile"),
+        }
+    }
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+// heic keeps its index at the end, so ffmpeg gets a seekable file rather than a pipe
+fn ffmpeg_to_image(bytes: &[u8]) -> Result<DynamicImage> {
+    let mut command = fetch_manager::get_ffmpeg().context(
+        "this image format needs ffmpeg. either install it manually, or call `mcat --fetch-ffmpeg`",
+    )?;
+    let mut tmp = NamedTempFile::new()?;
+    tmp.write_all(bytes)?;
+
+    command
+        .input(tmp.path().to_string_lossy())
+        .frames(1)
+        .format("rawvideo")
+        .pix_fmt("rgba")
+        .output("-");
+    let mut child = command.spawn()?;
+    let frame = child
+        .iter()?
+        .filter_frames()
+        .next()
+        .context("ffmpeg couldn't decode the image")?;
+    let _ = child.wait();
+
+    // ffmpeg before 6.1 doesn't stitch grid heic / avif, it hands over the first tile instead
+    if let Some((width, height)) = declared_size(bytes)
+        && u64::from(frame.width) * u64::from(frame.height) < u64::from(width) * u64::from(height)
+    {
+        warn!(
+            decoded = %format!("{}x{}", frame.width, frame.height),
+            declared = %format!("{width}x{height}"),
+            "ffmpeg decoded less than the whole image, tiled heic / avif needs ffmpeg 6.1 or newer"
+        );
+    }
+
+    rgba_frame(frame.width, frame.height, frame.data)
+}
+
+// the largest `ispe` (image spatial extents) box of a heif / avif, a grid image declares its
+// full size there and every tile its own
+fn declared_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    let be = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+    bytes
+        .windows(20)
+        .filter(|w| be(w) == 20 && &w[4..8] == b"ispe")
+        .map(|w| (be(&w[12..16]), be(&w[16..20])))
+        .max_by_key(|(w, h)| u64::from(*w) * u64::from(*h))
+}
+
+fn rgba_frame(width: u32, height: u32, data: Vec<u8>) -> Result<DynamicImage> {
+    let img = RgbaImage::from_raw(width, height, data)
+        .context("ffmpeg returned a frame of the wrong size")?;
+    Ok(DynamicImage::ImageRgba8(img))
+}
+
+// only rgb profiles are converted, and to 8 bit since that's what the terminal gets anyway
+fn icc_to_srgb(img: &DynamicImage, icc: &[u8]) -> Result<Option<DynamicImage>> {
+    let profile = ColorProfile::new_from_slice(icc)?;
+    if profile.color_space != DataColorSpace::Rgb {
+        return Ok(None);
+    }
+    let transform = profile.create_transform_8bit(
+        Layout::Rgba,
+        &ColorProfile::new_srgb(),
+        Layout::Rgba,
+        TransformOptions::default(),
+    )?;
+    let src = img.to_rgba8();
+    let mut dst = RgbaImage::new(src.width(), src.height());
+    transform.transform(src.as_raw(), &mut dst)?;
+    Ok(Some(DynamicImage::ImageRgba8(dst)))
+}
+
+/// Number of frames of a gif / apng / animated webp.
+pub fn frame_count(bytes: &[u8]) -> Result<usize> {
+    Ok(decode_animation(bytes)?.len())
+}
+
+fn decode_animation(bytes: &[u8]) -> Result<Vec<image::Frame>> {
+    let cursor = Cursor::new(bytes);
+    let frames = match image::guess_format(bytes)? {
+        ImageFormat::Gif => GifDecoder::new(cursor)?.into_frames(),
+        ImageFormat::Png => PngDecoder::new(cursor)?.apng()?.into_frames(),
+        ImageFormat::WebP => WebPDecoder::new(cursor)?.into_frames(),
+        format => anyhow::bail!("{format:?} isn't an animated image format"),
+    }
+    .collect_frames()?;
+
+    Ok(frames)
+}
+
+fn is_animated_image(b: &[u8]) -> bool {
+    match image::guess_format(b) {
+        Ok(ImageFormat::Png) => is_apng(b),
+        // VP8X header with the animation flag set
+        Ok(ImageFormat::WebP) => b.len() > 20 && &b[12..16] == b"VP8X" && b[20] & 0x02 != 0,
+        _ => false,
+    }
+}
+
+// acTL must come before the first IDAT, any number of chunks (iCCP, tEXt..) may come before it
+fn is_apng(b: &[u8]) -> bool {
+    let mut pos = 8;
+    while let Some(header) = b.get(pos..pos + 8) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        match &header[4..8] {
+            b"acTL" => return true,
+            b"IDAT" | b"IEND" => return false,
+            _ => pos = pos.saturating_add(12).saturating_add(len),
+        }
+    }
+    false
+}
+
 fn is_svg(b: &[u8]) -> bool {
     let head = &b[..b.len().min(2048)];
     let s = String::from_utf8_lossy(head);
@@ -800,3 +1175,298 @@ fn is_mermaid(b: &[u8]) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PdfPageSize;
+    use rasteroid::term_misc::EnvIdentifiers;
+
+    #[test]
+    fn tall_pages_split_into_screen_tiles() {
+        let env = EnvIdentifiers::new();
+        let wininfo = Wininfo::new(Some("1000x500"), Some("100x50"), None, None, &env).unwrap();
+
+        // twice the width, so a screen is 1000 tall and the rest is a shorter last tile
+        let page = DynamicImage::new_rgb8(2000, 2500);
+        let tiles = screen_tiles(page, &wininfo);
+        let heights: Vec<u32> = tiles.iter().map(|t| t.height()).collect();
+        assert_eq!(heights, [1000, 1000, 500]);
+        assert!(tiles.iter().all(|t| t.width() == 2000));
+
+        let short = DynamicImage::new_rgb8(2000, 800);
+        assert_eq!(screen_tiles(short, &wininfo).len(), 1);
+    }
+
+    // ftyp boxes the image crate can't decode
+    fn ftyp(brand: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = b"\x00\x00\x00\x18ftyp".to_vec();
+        bytes.extend_from_slice(brand);
+        bytes.extend_from_slice(b"\x00\x00\x00\x00mif1");
+        bytes.extend_from_slice(brand);
+        bytes
+    }
+
+    fn png(img: &DynamicImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn unsupported_images_go_through_the_fallback() {
+        for brand in [b"heic", b"avif"] {
+            let img =
+                decode_image_or(&ftyp(brand), false, |_| Ok(DynamicImage::new_rgb8(3, 2))).unwrap();
+            assert_eq!((img.width(), img.height()), (3, 2));
+
+            let err = decode_image_or(&ftyp(brand), true, |_| anyhow::bail!("no ffmpeg"));
+            assert_eq!(err.unwrap_err().to_string(), "no ffmpeg");
+        }
+    }
+
+    #[test]
+    fn decodable_and_broken_images_skip_the_fallback() {
+        let bytes = png(&DynamicImage::new_rgb8(4, 1));
+        let fallback = |_: &[u8]| -> Result<DynamicImage> { panic!("fell back to ffmpeg") };
+        assert_eq!(decode_image_or(&bytes, false, fallback).unwrap().width(), 4);
+
+        // a decoding error isn't an unsupported format
+        assert!(decode_image_or(&bytes[..bytes.len() / 2], false, fallback).is_err());
+    }
+
+    #[test]
+    fn rgba_frames_are_read_row_by_row() {
+        let data = vec![255, 0, 0, 255, 0, 0, 255, 128];
+        let img = rgba_frame(2, 1, data.clone()).unwrap().to_rgba8();
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(1, 0).0, [0, 0, 255, 128]);
+
+        assert!(rgba_frame(2, 1, data[..7].to_vec()).is_err());
+        assert!(rgba_frame(1, 2, data.clone()).is_ok());
+        assert!(rgba_frame(3, 1, data).is_err());
+    }
+
+    #[test]
+    fn heif_sizes_come_from_the_largest_ispe() {
+        let ispe = |w: u32, h: u32| {
+            let mut b = 20u32.to_be_bytes().to_vec();
+            b.extend_from_slice(b"ispe\0\0\0\0");
+            b.extend_from_slice(&w.to_be_bytes());
+            b.extend_from_slice(&h.to_be_bytes());
+            b
+        };
+        // a 2x2 grid of 512x512 tiles, the grid item's extents come after the tiles'
+        let mut meta = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic".to_vec();
+        for _ in 0..4 {
+            meta.extend(ispe(512, 512));
+        }
+        meta.extend(ispe(1024, 1000));
+        assert_eq!(declared_size(&meta), Some((1024, 1000)));
+
+        // the fourcc alone, without a 20 byte box around it, isn't a size
+        assert_eq!(
+            declared_size(b"\0\0\0\x10ispe\0\0\0\0\0\0\0\x01\0\0\0\x01"),
+            None
+        );
+        assert_eq!(declared_size(&png(&DynamicImage::new_rgba8(4, 4))), None);
+    }
+
+    #[test]
+    fn ffmpeg_decodes_to_rgba() {
+        if fetch_manager::get_ffmpeg().is_none() {
+            eprintln!("ffmpeg isn't installed, skipping");
+            return;
+        }
+        let src = RgbaImage::from_raw(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 255]).unwrap();
+        let img = ffmpeg_to_image(&png(&DynamicImage::ImageRgba8(src.clone()))).unwrap();
+        assert_eq!(img.to_rgba8(), src);
+    }
+
+    fn rgba_frames(delays_ms: &[u32]) -> Vec<image::Frame> {
+        delays_ms
+            .iter()
+            .enumerate()
+            .map(|(i, ms)| {
+                let img = RgbaImage::from_pixel(4, 2, image::Rgba([i as u8 * 60, 0, 0, 255]));
+                image::Frame::from_parts(img, 0, 0, image::Delay::from_numer_denom_ms(*ms, 1))
+            })
+            .collect()
+    }
+
+    fn gif(delays_ms: &[u32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = GifEncoder::new(&mut bytes);
+        encoder.set_repeat(Repeat::Infinite).unwrap();
+        encoder.encode_frames(rgba_frames(delays_ms)).unwrap();
+        drop(encoder);
+        bytes
+    }
+
+    fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut crc = flate2::Crc::new();
+        crc.update(kind);
+        crc.update(data);
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(kind);
+        chunk.extend(data);
+        chunk.extend(crc.sum().to_be_bytes());
+        chunk
+    }
+
+    // `padding` bytes of tEXt go right after IHDR, before acTL
+    fn apng(delays_ms: &[u16], padding: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 4, 2);
+        encoder.set_color(png::ColorType::Rgba);
+        if delays_ms.len() > 1 {
+            encoder.set_animated(delays_ms.len() as u32, 0).unwrap();
+        }
+        let mut writer = encoder.write_header().unwrap();
+        for (i, ms) in delays_ms.iter().enumerate() {
+            if delays_ms.len() > 1 {
+                writer.set_frame_delay(*ms, 1000).unwrap();
+            }
+            writer.write_image_data(&[i as u8 * 60; 4 * 2 * 4]).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let text = [b"Comment\0".as_slice(), &vec![b'a'; padding]].concat();
+        // the signature and IHDR
+        [&bytes[..33], &png_chunk(b"tEXt", &text), &bytes[33..]].concat()
+    }
+
+    fn riff_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend((data.len() as u32).to_le_bytes());
+        chunk.extend(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    // lossless frames wrapped in ANMF chunks, the image crate only encodes stills
+    fn animated_webp(delays_ms: &[u32]) -> Vec<u8> {
+        let u24 = |v: u32| v.to_le_bytes()[..3].to_vec();
+        let mut body = b"WEBP".to_vec();
+        body.extend(riff_chunk(
+            b"VP8X",
+            &[vec![0x12, 0, 0, 0], u24(3), u24(1)].concat(),
+        ));
+        body.extend(riff_chunk(b"ANIM", &[0, 0, 0, 0, 0, 0]));
+        for frame in rgba_frames(delays_ms) {
+            let mut still = Vec::new();
+            DynamicImage::ImageRgba8(frame.buffer().clone())
+                .write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut still))
+                .unwrap();
+            let (numer, _) = frame.delay().numer_denom_ms();
+            let header = [vec![0; 6], u24(3), u24(1), u24(numer), vec![0]].concat();
+            // the still's VP8L chunk, past `RIFF size WEBP`
+            body.extend(riff_chunk(
+                b"ANMF",
+                &[header, still[12..].to_vec()].concat(),
+            ));
+        }
+        riff_chunk(b"RIFF", &body)
+    }
+
+    fn timestamps(bytes: &[u8], count: usize) -> Vec<f32> {
+        let (frames, width, height) = animation_to_frames(bytes).unwrap();
+        assert_eq!((width, height), (4, 2));
+        frames
+            .take(count)
+            .map(|(_, t)| (t * 1000.0).round())
+            .collect()
+    }
+
+    #[test]
+    fn gifs_decode_with_their_delays() {
+        let bytes = gif(&[50, 200, 10]);
+        assert_eq!(frame_count(&bytes).unwrap(), 3);
+        // the 10ms frame is bumped to 100ms like browsers do, then it loops
+        assert_eq!(
+            timestamps(&bytes, 7),
+            [0.0, 50.0, 250.0, 350.0, 400.0, 600.0, 700.0]
+        );
+        assert_eq!(McatKind::detect(Some(&bytes), None), Some(McatKind::Gif));
+    }
+
+    #[test]
+    fn apngs_are_animated_past_large_chunks() {
+        for padding in [0, 10_000] {
+            let bytes = apng(&[30, 0], padding);
+            assert!(is_animated_image(&bytes), "padding {padding}");
+            assert_eq!(frame_count(&bytes).unwrap(), 2);
+            assert_eq!(timestamps(&bytes, 4), [0.0, 30.0, 130.0, 160.0]);
+        }
+
+        let still = apng(&[0], 10_000);
+        assert!(!is_animated_image(&still));
+        assert!(!is_apng(&still[..40]));
+        let still = png(&DynamicImage::new_rgb8(2, 2));
+        assert!(!is_animated_image(&still));
+        assert_eq!(McatKind::detect(Some(&still), None), Some(McatKind::Image));
+    }
+
+    #[test]
+    fn animated_webps_are_detected_and_decoded() {
+        let bytes = animated_webp(&[40, 60]);
+        assert!(is_animated_image(&bytes));
+        assert_eq!(frame_count(&bytes).unwrap(), 2);
+        assert_eq!(timestamps(&bytes, 3), [0.0, 40.0, 100.0]);
+
+        let mut still = Vec::new();
+        DynamicImage::new_rgba8(4, 2)
+            .write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut still))
+            .unwrap();
+        assert!(!is_animated_image(&still));
+    }
+
+    #[test]
+    fn print_params_are_in_inches() {
+        let mut config = McatConfig::default();
+        config.pdf_page = PdfPageSize::Letter;
+        config.pdf_margin = 0.5;
+        let params = print_params(&config);
+        assert_eq!(params["paperWidth"], 8.5);
+        assert_eq!(params["paperHeight"], 11.0);
+        for side in ["Top", "Bottom", "Left", "Right"] {
+            assert_eq!(params[format!("margin{side}")], 0.5);
+        }
+        assert_eq!(params["printBackground"], true);
+        // no header or footer, and chromium's default one is blanked out
+        assert_eq!(params["displayHeaderFooter"], false);
+        assert_eq!(params["headerTemplate"], "<span></span>");
+        assert_eq!(params["footerTemplate"], "<span></span>");
+
+        config.pdf_footer = Some("<span class=pageNumber></span>".to_owned());
+        let params = print_params(&config);
+        assert_eq!(params["displayHeaderFooter"], true);
+        assert_eq!(params["headerTemplate"], "<span></span>");
+        assert_eq!(params["footerTemplate"], "<span class=pageNumber></span>");
+    }
+
+    #[test]
+    fn base_href_points_next_to_the_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("doc.md");
+        fs::write(&source, "# doc").unwrap();
+        let base = Url::from_directory_path(dir.path().canonicalize().unwrap()).unwrap();
+
+        let html = with_base_href("<html><head><title>t</title></head></html>", Some(&source));
+        assert_eq!(
+            html,
+            format!("<html><head><base href=\"{base}\"><title>t</title></head></html>")
+        );
+        assert_eq!(
+            with_base_href("<p>hi</p>", Some(&source)),
+            format!("<base href=\"{base}\"><p>hi</p>")
+        );
+        // stdin, or a path that doesn't exist, is left alone
+        assert_eq!(with_base_href("<p>hi</p>", None), "<p>hi</p>");
+        let missing = dir.path().join("missing.md");
+        assert_eq!(with_base_href("<p>hi</p>", Some(&missing)), "<p>hi</p>");
+    }
+}
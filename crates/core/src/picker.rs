@@ -0,0 +1,676 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use rasteroid::{Encoder, RasterEncoder};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    config::{McatConfig, MdImageMode},
+    image_viewer::draw_frame,
+    markdown_viewer::{
+        self,
+        render::{BOLD_BLUE, FAINT, RESET, REVERSE},
+        utils::get_lang_icon_and_color,
+    },
+    mcat_file::{McatFile, McatKind},
+    prompter::collect_gitignored_paths,
+};
+
+struct Entry {
+    path: PathBuf,
+    rel: String,
+    depth: usize,
+    is_dir: bool,
+}
+
+enum Action {
+    Redraw,
+    Confirm,
+    Cancel,
+    None,
+}
+
+struct Picker<'a> {
+    config: &'a McatConfig,
+    entries: Vec<Entry>,
+    query: String,
+    collapsed: HashSet<usize>,
+    selected: HashSet<usize>,
+    visible: Vec<usize>,
+    cursor: usize,
+    scroll: usize,
+    matcher: SkimMatcherV2,
+    previews: HashMap<usize, Vec<u8>>,
+}
+
+/// Fuzzy file picker with a live preview of the highlighted file.
+///
+/// type to filter, `Tab` selects, `←/→` fold folders, `Enter` confirms.
+/// returns nothing when cancelled.
+pub fn pick_files(dir: &Path, config: &McatConfig, out: &mut impl Write) -> Result<Vec<PathBuf>> {
+    let mut paths = collect_gitignored_paths(dir, config.hidden)?;
+    paths.sort();
+
+    let entries = paths
+        .into_iter()
+        .map(|path| {
+            let rel = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+            Entry {
+                depth: rel.components().count().saturating_sub(1),
+                rel: rel.to_string_lossy().into_owned(),
+                is_dir: path.is_dir(),
+                path,
+            }
+        })
+        .collect();
+
+    let mut picker = Picker {
+        config,
+        entries,
+        query: String::new(),
+        collapsed: HashSet::new(),
+        selected: HashSet::new(),
+        visible: Vec::new(),
+        cursor: 0,
+        scroll: 0,
+        matcher: SkimMatcherV2::default(),
+        previews: HashMap::new(),
+    };
+    picker.refresh();
+
+    enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+    let result = picker.run(out);
+    execute!(out, Show, LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+
+    result
+}
+
+impl Picker<'_> {
+    fn run(&mut self, out: &mut impl Write) -> Result<Vec<PathBuf>> {
+        self.draw(out)?;
+        loop {
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match self.handle_key(key) {
+                Action::Redraw => self.draw(out)?,
+                Action::Confirm => return Ok(self.selection()),
+                Action::Cancel => return Ok(Vec::new()),
+                Action::None => {}
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Action::Cancel,
+            KeyCode::Char('c') if ctrl => return Action::Cancel,
+            KeyCode::Enter => return Action::Confirm,
+
+            KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Char('p' | 'k') if ctrl => self.move_cursor(-1),
+            KeyCode::Down => self.move_cursor(1),
+            KeyCode::Char('n' | 'j') if ctrl => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-(self.list_height() as isize)),
+            KeyCode::PageDown => self.move_cursor(self.list_height() as isize),
+
+            KeyCode::Tab => {
+                let Some(&idx) = self.visible.get(self.cursor) else {
+                    return Action::None;
+                };
+                if !self.selected.remove(&idx) {
+                    self.selected.insert(idx);
+                }
+                self.move_cursor(1);
+            }
+
+            // folding only makes sense for the tree, not for search results
+            KeyCode::Right if self.query.is_empty() => {
+                let Some(&idx) = self.visible.get(self.cursor) else {
+                    return Action::None;
+                };
+                if !self.collapsed.remove(&idx) {
+                    return Action::None;
+                }
+                self.refresh();
+                self.focus(idx);
+            }
+            KeyCode::Left if self.query.is_empty() => {
+                let Some(&idx) = self.visible.get(self.cursor) else {
+                    return Action::None;
+                };
+                if self.entries[idx].is_dir && !self.collapsed.contains(&idx) {
+                    self.collapsed.insert(idx);
+                    self.refresh();
+                    self.focus(idx);
+                } else if let Some(parent) = self.parent_of(idx) {
+                    self.focus(parent);
+                } else {
+                    return Action::None;
+                }
+            }
+
+            KeyCode::Backspace => {
+                if self.query.pop().is_none() {
+                    return Action::None;
+                }
+                self.refresh();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.refresh();
+            }
+            _ => return Action::None,
+        }
+
+        self.clamp_scroll();
+        Action::Redraw
+    }
+
+    /// Rebuilds the visible list from the query, or from the tree when there's no query,
+    /// and moves the cursor back to the top.
+    fn refresh(&mut self) {
+        self.visible = if self.query.is_empty() {
+            let mut folded: Option<&Path> = None;
+            let mut visible = Vec::new();
+            for (i, entry) in self.entries.iter().enumerate() {
+                if let Some(dir) = folded {
+                    if entry.path.starts_with(dir) {
+                        continue;
+                    }
+                    folded = None;
+                }
+                if self.collapsed.contains(&i) {
+                    folded = Some(&entry.path);
+                }
+                visible.push(i);
+            }
+            visible
+        } else {
+            let mut scored: Vec<(i64, usize)> = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter_map(|(i, entry)| {
+                    let score = self.matcher.fuzzy_match(&entry.rel, &self.query)?;
+                    Some((score, i))
+                })
+                .collect();
+            scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+            scored.into_iter().map(|(_, i)| i).collect()
+        };
+
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    /// Moves the cursor onto `idx` when it's visible.
+    fn focus(&mut self, idx: usize) {
+        if let Some(pos) = self.visible.iter().position(|&v| v == idx) {
+            self.cursor = pos;
+        }
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let max = self.visible.len().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize + delta).clamp(0, max.max(0)) as usize;
+    }
+
+    fn clamp_scroll(&mut self) {
+        let height = self.list_height();
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+    }
+
+    fn parent_of(&self, idx: usize) -> Option<usize> {
+        let parent = self.entries[idx].path.parent()?;
+        self.entries[..idx]
+            .iter()
+            .rposition(|entry| entry.path == parent)
+    }
+
+    /// Selected files, folders are expanded into their files.
+    /// falls back to the highlighted entry when nothing was selected.
+    fn selection(&self) -> Vec<PathBuf> {
+        let picked: Vec<usize> = if self.selected.is_empty() {
+            self.visible.get(self.cursor).copied().into_iter().collect()
+        } else {
+            self.selected.iter().copied().collect()
+        };
+
+        let mut files = HashSet::new();
+        for idx in picked {
+            let entry = &self.entries[idx];
+            if entry.is_dir {
+                files.extend(
+                    self.entries
+                        .iter()
+                        .filter(|e| !e.is_dir && e.path.starts_with(&entry.path))
+                        .map(|e| e.path.clone()),
+                );
+            } else {
+                files.insert(entry.path.clone());
+            }
+        }
+
+        let mut files: Vec<PathBuf> = files.into_iter().collect();
+        files.sort();
+        files
+    }
+
+    fn size(&self) -> (u16, u16) {
+        self.config
+            .wininfo
+            .as_ref()
+            .map(|w| (w.sc_width, w.sc_height))
+            .unwrap_or((80, 24))
+    }
+
+    fn list_width(&self) -> u16 {
+        let (width, _) = self.size();
+        (width * 2 / 5).max(20).min(width)
+    }
+
+    // first row is the query, last one the help
+    fn list_height(&self) -> usize {
+        let (_, height) = self.size();
+        height.saturating_sub(2).max(1) as usize
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<()> {
+        let (width, height) = self.size();
+        let list_width = self.list_width();
+        let mut buf: Vec<u8> = Vec::new();
+
+        // kitty images survive the screen clear
+        if self.config.encoder == Some(RasterEncoder::Kitty)
+            && let Some(wininfo) = &self.config.wininfo
+        {
+            buf.extend_from_slice(wininfo.multiplexer.wrap("\x1b_Ga=d,d=A\x1b\\").as_bytes());
+        }
+
+        let count = format!("{}/{}", self.visible.len(), self.entries.len());
+        let prompt = fit(
+            &format!("> {}", self.query),
+            (list_width as usize).saturating_sub(count.len()),
+        );
+        queue!(
+            buf,
+            MoveTo(0, 0),
+            Print(format!("{prompt}{FAINT}{count}{RESET}"))
+        )?;
+
+        for row in 0..self.list_height() {
+            let y = row as u16 + 1;
+            queue!(
+                buf,
+                MoveTo(list_width, y),
+                Print(format!("{FAINT}│{RESET}"))
+            )?;
+            let Some(&idx) = self.visible.get(self.scroll + row) else {
+                continue;
+            };
+            let highlighted = self.scroll + row == self.cursor;
+            let line = self.format_entry(idx, list_width as usize, highlighted);
+            queue!(buf, MoveTo(0, y), Print(line))?;
+        }
+
+        let help = "[↑/↓] Move  [Tab] Select  [←/→] Fold  [Enter] Open  [Esc] Quit";
+        queue!(
+            buf,
+            MoveTo(0, height.saturating_sub(1)),
+            Print(format!("{FAINT}{}{RESET}", fit(help, width as usize)))
+        )?;
+
+        if let Some(&idx) = self.visible.get(self.cursor) {
+            let preview = match self.previews.get(&idx) {
+                Some(preview) => preview.clone(),
+                None => {
+                    let preview = self
+                        .render_preview(idx)
+                        .unwrap_or_else(|e| self.text_preview(&format!("no preview: {e}")));
+                    self.previews.insert(idx, preview.clone());
+                    preview
+                }
+            };
+            buf.extend_from_slice(&preview);
+        }
+
+        draw_frame(out, buf)?;
+        Ok(())
+    }
+
+    fn format_entry(&self, idx: usize, width: usize, highlighted: bool) -> String {
+        let entry = &self.entries[idx];
+        let marker = if self.selected.contains(&idx) {
+            "● "
+        } else {
+            "  "
+        };
+        let name = if self.query.is_empty() {
+            entry
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        } else {
+            entry.rel.clone()
+        };
+        let indent = if self.query.is_empty() {
+            "  ".repeat(entry.depth)
+        } else {
+            String::new()
+        };
+
+        let (icon, color) = if entry.is_dir {
+            let fold = if self.collapsed.contains(&idx) {
+                "▸"
+            } else {
+                "▾"
+            };
+            (format!("{fold} \u{f024b}"), BOLD_BLUE)
+        } else {
+            let ext = entry
+                .path
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_lowercase();
+            match get_lang_icon_and_color(&ext) {
+                Some((icon, color)) => (icon.to_owned(), color),
+                None => (" ".to_owned(), ""),
+            }
+        };
+        let suffix = if entry.is_dir { "/" } else { "" };
+
+        let text = fit(&format!("{marker}{indent}{icon} {name}{suffix}"), width);
+        if highlighted {
+            format!("{REVERSE}{text}{RESET}")
+        } else {
+            // only the icon and name are coloured, the fitted text is re-split
+            let prefix_len = marker.chars().count() + indent.chars().count();
+            let (prefix, rest) = text.split_at(
+                text.char_indices()
+                    .nth(prefix_len)
+                    .map(|(i, _)| i)
+                    .unwrap_or(text.len()),
+            );
+            format!("{prefix}{color}{rest}{RESET}")
+        }
+    }
+
+    fn preview_area(&self) -> (u16, u16, u16, u16) {
+        let (width, _) = self.size();
+        let x = self.list_width() + 2;
+        (x, 1, width.saturating_sub(x), self.list_height() as u16)
+    }
+
+    fn text_preview(&self, text: &str) -> Vec<u8> {
+        let (x, y, w, h) = self.preview_area();
+        let mut buf = Vec::new();
+        for (i, line) in text.lines().take(h as usize).enumerate() {
+            let _ = queue!(buf, MoveTo(x, y + i as u16), Print(fit(line, w as usize)));
+        }
+        buf
+    }
+
+    fn render_preview(&self, idx: usize) -> Result<Vec<u8>> {
+        let entry = &self.entries[idx];
+        if entry.is_dir {
+            let children: Vec<&str> = self
+                .entries
+                .iter()
+                .filter(|e| e.path.parent() == Some(entry.path.as_path()))
+                .filter_map(|e| e.path.file_name()?.to_str())
+                .collect();
+            return Ok(self.text_preview(&children.join("\n")));
+        }
+
+        let (x, y, w, h) = self.preview_area();
+        let file = McatFile::from_path(&entry.path, true)?;
+        let mut buf = Vec::new();
+
+        match file.kind {
+            McatKind::Video => return Ok(self.text_preview("video, no preview")),
+            McatKind::Image
+            | McatKind::Gif
+            | McatKind::Svg
+            | McatKind::JpegXL
+            | McatKind::Pdf
+            | McatKind::Exe
+            | McatKind::Lnk
+            | McatKind::Mermaid
+            | McatKind::Dot
+            | McatKind::PlantUml
+            | McatKind::D2 => {
+                let encoder = self
+                    .config
+                    .encoder
+                    .context("this is likely a bug, encoder wasn't set at the picker")?;
+                let wininfo = self
+                    .config
+                    .wininfo
+                    .as_ref()
+                    .context("this is likely a bug, wininfo wasn't set at the picker")?;
+                let mut config = self.config.clone();
+                config.img_width = format!("{w}c");
+                config.img_height = format!("{h}c");
+                let img = file.to_image(&config, false, true)?;
+                encoder.encode_image(&img, &mut buf, wininfo, None, Some((x, y)))?;
+            }
+            _ => {
+                let mut config = self.config.clone();
+                config.padding = 0;
+                config.md_image = MdImageMode::None;
+                config.inline_images_in_md = false;
+                if let Some(wininfo) = config.wininfo.as_mut() {
+                    wininfo.sc_width = w;
+                }
+                let md = file.to_markdown_input(false)?.convert()?;
+                let ansi = markdown_viewer::md_to_ansi(&md, config, Some(&entry.path), None)?;
+                for (i, line) in ansi.lines().take(h as usize).enumerate() {
+                    queue!(buf, MoveTo(x, y + i as u16), Print(line), Print(RESET))?;
+                }
+            }
+        }
+
+        Ok(buf)
+    }
+}
+
+/// Cuts `text` to `width` columns, and pads it so highlighted rows span the full width.
+pub fn fit(text: &str, width: usize) -> String {
+    let mut result = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width {
+            break;
+        }
+        used += w;
+        result.push(c);
+    }
+    result.push_str(&" ".repeat(width - used));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_picker<'a>(config: &'a McatConfig, paths: &[&str]) -> Picker<'a> {
+        let entries = paths
+            .iter()
+            .map(|rel| {
+                let rel = rel.trim_end_matches('/');
+                Entry {
+                    path: Path::new("/root").join(rel),
+                    rel: rel.to_owned(),
+                    depth: rel.matches('/').count(),
+                    is_dir: paths.contains(&format!("{rel}/").as_str()),
+                }
+            })
+            .collect();
+        let mut picker = Picker {
+            config,
+            entries,
+            query: String::new(),
+            collapsed: HashSet::new(),
+            selected: HashSet::new(),
+            visible: Vec::new(),
+            cursor: 0,
+            scroll: 0,
+            matcher: SkimMatcherV2::default(),
+            previews: HashMap::new(),
+        };
+        picker.refresh();
+        picker
+    }
+
+    const PATHS: &[&str] = &[
+        "docs/",
+        "docs/guide.md",
+        "docs/intro.md",
+        "readme.md",
+        "src/",
+        "src/main.rs",
+    ];
+
+    fn visible_rels<'a>(picker: &'a Picker) -> Vec<&'a str> {
+        picker
+            .visible
+            .iter()
+            .map(|&i| picker.entries[i].rel.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn refresh_hides_collapsed_folders() {
+        let config = McatConfig::default();
+        let mut picker = make_picker(&config, PATHS);
+        assert_eq!(picker.visible.len(), PATHS.len());
+
+        picker.collapsed.insert(0);
+        picker.refresh();
+        assert_eq!(
+            visible_rels(&picker),
+            ["docs", "readme.md", "src", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn refresh_filters_and_ranks_by_query() {
+        let config = McatConfig::default();
+        let mut picker = make_picker(&config, PATHS);
+        picker.query = "main".to_owned();
+        picker.refresh();
+        assert_eq!(visible_rels(&picker), ["src/main.rs"]);
+
+        // ties keep the tree order, a closer match ranks first
+        picker.query = "md".to_owned();
+        picker.refresh();
+        assert_eq!(
+            visible_rels(&picker),
+            ["docs/guide.md", "docs/intro.md", "readme.md"]
+        );
+        picker.query = "sm".to_owned();
+        picker.refresh();
+        assert_eq!(
+            visible_rels(&picker),
+            ["src/main.rs", "docs/guide.md", "docs/intro.md"]
+        );
+
+        picker.query = "zzz".to_owned();
+        picker.refresh();
+        assert!(picker.visible.is_empty());
+        assert_eq!(picker.selection(), Vec::<PathBuf>::new());
+    }
+
+    #[test]
+    fn refresh_resets_the_cursor() {
+        let config = McatConfig::default();
+        let mut picker = make_picker(&config, PATHS);
+        picker.move_cursor(3);
+        assert_eq!(picker.cursor, 3);
+
+        picker.query = "r".to_owned();
+        picker.refresh();
+        assert_eq!((picker.cursor, picker.scroll), (0, 0));
+    }
+
+    #[test]
+    fn move_cursor_clamps() {
+        let config = McatConfig::default();
+        let mut picker = make_picker(&config, PATHS);
+        picker.move_cursor(-5);
+        assert_eq!(picker.cursor, 0);
+        picker.move_cursor(100);
+        assert_eq!(picker.cursor, PATHS.len() - 1);
+        picker.move_cursor(-2);
+        assert_eq!(picker.cursor, PATHS.len() - 3);
+    }
+
+    #[test]
+    fn folding_keeps_the_cursor_on_the_folder() {
+        let config = McatConfig::default();
+        let mut picker = make_picker(&config, PATHS);
+        picker.move_cursor(4);
+        picker.handle_key(KeyEvent::from(KeyCode::Left));
+        assert_eq!(visible_rels(&picker)[picker.cursor], "src");
+
+        picker.handle_key(KeyEvent::from(KeyCode::Right));
+        assert_eq!(visible_rels(&picker)[picker.cursor], "src");
+        assert_eq!(picker.visible.len(), PATHS.len());
+    }
+
+    #[test]
+    fn selection_expands_folders() {
+        let config = McatConfig::default();
+        let mut picker = make_picker(&config, PATHS);
+        picker.selected.insert(0);
+        assert_eq!(
+            picker.selection(),
+            [
+                Path::new("/root/docs/guide.md"),
+                Path::new("/root/docs/intro.md")
+            ]
+        );
+    }
+
+    #[test]
+    fn fit_cuts_and_pads() {
+        assert_eq!(fit("hello", 8), "hello   ");
+        assert_eq!(fit("hello", 3), "hel");
+        assert_eq!(fit("", 2), "  ");
+        // a wide char that doesn't fit is dropped and padded over
+        assert_eq!(fit("ab漢", 3), "ab ");
+        assert_eq!(fit("漢字", 4), "漢字");
+        assert_eq!(fit("abc", 0), "");
+    }
+}
@@ -1,8 +1,6 @@
 use anyhow::Result;
-use ignore::WalkBuilder;
+use ignore::{WalkBuilder, overrides::OverrideBuilder};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use inquire::MultiSelect;
-use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 use std::sync::atomic::AtomicBool;
@@ -12,7 +10,10 @@ use std::sync::{
 };
 use tokio::runtime::{Builder, Runtime};
 
-use crate::markdown_viewer::utils::get_lang_icon_and_color;
+use crate::config::McatConfig;
+use crate::ls_preview::Preview;
+use crate::markdown_viewer::utils::SYNTAXES;
+use crate::mcat_file::McatKind;
 
 pub static RUNTIME: LazyLock<Runtime> =
     LazyLock::new(|| Builder::new_current_thread().enable_all().build().unwrap());
@@ -221,54 +222,71 @@ impl GhosttyBar {
     }
 }
 
-pub fn prompt_for_files(dir: &Path, hidden: bool) -> Result<Vec<PathBuf>> {
-    let mut all_paths = collect_gitignored_paths(dir, hidden)?;
-    all_paths.sort();
-
-    let tree_view = format_file_list(&all_paths, dir);
-
-    let index_map: HashMap<String, PathBuf> = tree_view
-        .iter()
-        .cloned()
-        .zip(all_paths.iter().cloned())
-        .collect();
-
-    let selected = MultiSelect::new("Select files or folders", tree_view)
-        .with_page_size(20)
-        .with_vim_mode(true)
-        .prompt()?;
-
-    let selected_paths: HashSet<PathBuf> = selected
-        .into_iter()
-        .filter_map(|label| index_map.get(&label).cloned())
-        .collect();
+/// Non interactive version of [`crate::picker::pick_files`], picks files using the directory input flags.
+pub fn collect_files(dir: &Path, config: &McatConfig) -> Result<Vec<PathBuf>> {
+    let mut overrides = OverrideBuilder::new(dir);
+    for glob in &config.include {
+        overrides.add(glob)?;
+    }
+    for glob in &config.exclude {
+        overrides.add(&format!("!{glob}"))?;
+    }
 
-    // if a folder is selected, skip its inner files
-    let mut final_files = HashSet::new();
+    let max_depth = match config.max_depth {
+        Some(depth) => Some(depth),
+        None if config.recursive => None,
+        None => Some(1),
+    };
+    let walker = WalkBuilder::new(dir)
+        .standard_filters(!config.hidden)
+        .hidden(!config.hidden)
+        .follow_links(true)
+        .max_depth(max_depth)
+        .overrides(overrides.build()?)
+        .build();
 
-    for path in &selected_paths {
-        if path.is_file() {
-            // only include files not covered by a selected folder
-            let covered = selected_paths
-                .iter()
-                .any(|other| other.is_dir() && path.starts_with(other));
-            if !covered {
-                final_files.insert(path.clone());
-            }
-        } else if path.is_dir() {
-            for file in all_paths
-                .iter()
-                .filter(|p| p.is_file() && p.starts_with(path))
-            {
-                final_files.insert(file.clone());
+    let mut paths = vec![];
+    for entry in walker.flatten() {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        if let Some(max) = config.max_size
+            && entry.metadata().is_ok_and(|m| m.len() > max)
+        {
+            continue;
+        }
+        if !config.only.is_empty() {
+            let ext = entry
+                .path()
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let kind = match McatKind::from_ext(&ext) {
+                Some(kind) => kind,
+                None if is_text_ext(&ext) => McatKind::PreMarkdown,
+                None => continue,
+            };
+            if !config.only.iter().any(|f| f.matches(&kind)) {
+                continue;
             }
         }
+        paths.push(entry.into_path());
     }
 
-    Ok(final_files.into_iter().collect())
+    Ok(paths)
+}
+
+// extensions mcat doesn't know itself only count as text when markdownify or syntect can read them
+fn is_text_ext(ext: &str) -> bool {
+    let ext = ext.to_lowercase();
+    match Preview::from_ext(&ext) {
+        Some(Preview::Document | Preview::Archive) => true,
+        Some(Preview::Text) => ext == "txt" || SYNTAXES.find_syntax_by_extension(&ext).is_some(),
+        None => false,
+    }
 }
 
-fn collect_gitignored_paths(dir: &Path, hidden: bool) -> Result<Vec<PathBuf>> {
+pub fn collect_gitignored_paths(dir: &Path, hidden: bool) -> Result<Vec<PathBuf>> {
     let walker = WalkBuilder::new(dir)
         .standard_filters(!hidden)
         .hidden(!hidden)
@@ -292,77 +310,3 @@ fn collect_gitignored_paths(dir: &Path, hidden: bool) -> Result<Vec<PathBuf>> {
 
     Ok(paths)
 }
-
-fn format_file_list(paths: &[PathBuf], base: &Path) -> Vec<String> {
-    let mut formatted = vec![];
-    let reset = "\x1b[0m";
-    let bold = "\x1b[1m";
-    let blue = "\x1b[34m";
-    let purple = "\x1b[35m";
-    let dir_color = &format!("{bold}{blue}");
-    let link_color = purple;
-
-    for (i, path) in paths.iter().enumerate() {
-        let rel = path.strip_prefix(base).unwrap_or(path);
-        let depth = rel.components().count().saturating_sub(1);
-        let name = path.file_name().unwrap_or_default().to_string_lossy();
-        let ext = path
-            .extension()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_lowercase();
-        let is_dir = path.is_dir();
-        let is_link = path.is_symlink();
-
-        let mut line = String::new();
-        if depth > 0 {
-            line.push_str(&"│   ".repeat(depth - 1));
-            let is_last = paths
-                .get(i + 1)
-                .map(|next| {
-                    let next_rel = next.strip_prefix(base).unwrap_or(next);
-                    next_rel.components().count().saturating_sub(1) < depth
-                })
-                .unwrap_or(true);
-            line.push_str(if is_last { "└── " } else { "├── " });
-            line.push_str(reset);
-        }
-
-        let name_color = if is_link {
-            link_color
-        } else if is_dir {
-            dir_color
-        } else {
-            ""
-        };
-        if is_dir {
-            line.push_str(&format!("{name_color}\u{f024b} {name}/{reset}"));
-        } else if let Some((icon, color)) = get_lang_icon_and_color(&ext) {
-            line.push_str(&format!("{color}{icon}{reset} {name_color}{name}{reset}"));
-        } else {
-            line.push_str(&format!("{name_color}{name}{reset}"));
-        }
-
-        // add invisible unique suffix to make each label distinct
-        line.push_str(&encode_invisible_id(i));
-
-        formatted.push(line);
-    }
-
-    formatted
-}
-
-fn encode_invisible_id(id: usize) -> String {
-    let charset = ['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}'];
-    let mut encoded = String::new();
-    let mut n = id;
-    if n == 0 {
-        encoded.push(charset[0]);
-    } else {
-        while n > 0 {
-            encoded.push(charset[n % 4]);
-            n /= 4;
-        }
-    }
-    encoded
-}
@@ -1,7 +1,10 @@
 use anyhow::{Context, Result};
 use futures::StreamExt;
 use regex::Regex;
-use reqwest::{Client, Response};
+use reqwest::{
+    Client, Response, StatusCode,
+    header::{IF_MODIFIED_SINCE, IF_NONE_MATCH},
+};
 use scraper::Html;
 use std::sync::{LazyLock, OnceLock};
 use std::time::Duration;
@@ -9,6 +12,7 @@ use std::time::Duration;
 use tracing::{debug, info, warn};
 
 use crate::{
+    cache::{self, RemoteEntry},
     mcat_file::{McatFile, McatKind},
     prompter::MultiBar,
 };
@@ -49,7 +53,8 @@ pub async fn scrape_biggest_media(
         url.to_string()
     };
 
-    let response = match get_response(client, &url, bar).await {
+    let cached = cache::load_remote(&url);
+    let response = match get_response(client, &url, bar, cached.as_ref()).await {
         Ok(r) => r,
         Err(e) => {
             warn!(url = %url, error = %e.root_cause(), "failed to fetch");
@@ -57,14 +62,16 @@ pub async fn scrape_biggest_media(
         }
     };
 
+    if response.status() == StatusCode::NOT_MODIFIED
+        && let Some(cached) = cached
+    {
+        info!(url = %url, size = cached.bytes.len(), "using cached media");
+        let format = format_from_url_and_mime(&url, cached.mime.as_deref());
+        return file_from_download(cached.bytes, &url, format);
+    }
+
     let mime = get_mime(&response);
-    let format = mime.as_deref().and_then(|m| {
-        if m == "application/octet-stream" {
-            ext_from_url(&url).as_deref().and_then(McatKind::from_ext)
-        } else {
-            format_from_mime(m)
-        }
-    });
+    let format = format_from_url_and_mime(&url, mime.as_deref());
 
     match format {
         // html, try to scrape for something
@@ -81,6 +88,8 @@ pub async fn scrape_biggest_media(
         }
         // known type, just download
         Some(fmt) => {
+            let etag = get_header(&response, "etag");
+            let last_modified = get_header(&response, "last-modified");
             let data = match download(response, options, bar).await {
                 Ok(d) => d,
                 Err(e) => {
@@ -88,11 +97,15 @@ pub async fn scrape_biggest_media(
                     return Err(e);
                 }
             };
-            let mut file = McatFile::from_bytes(data, None, ext_from_url(&url), Some(url), true)?;
-            if file.kind == McatKind::PreMarkdown {
-                file.kind = fmt;
-            }
-            info!(url = %file.id.as_deref().unwrap_or_default(), kind = ?file.kind, size = file.bytes.len(), "downloaded media");
+            let entry = RemoteEntry {
+                bytes: data,
+                mime,
+                etag,
+                last_modified,
+            };
+            cache::store_remote(&url, &entry);
+            let file = file_from_download(entry.bytes, &url, Some(fmt))?;
+            info!(url = %url, kind = ?file.kind, size = file.bytes.len(), "downloaded media");
             Ok(file)
         }
         None => {
@@ -102,6 +115,26 @@ pub async fn scrape_biggest_media(
     }
 }
 
+fn format_from_url_and_mime(url: &str, mime: Option<&str>) -> Option<McatKind> {
+    mime.and_then(|m| {
+        if m == "application/octet-stream" {
+            ext_from_url(url).as_deref().and_then(McatKind::from_ext)
+        } else {
+            format_from_mime(m)
+        }
+    })
+}
+
+fn file_from_download(data: Vec<u8>, url: &str, format: Option<McatKind>) -> Result<McatFile> {
+    let mut file = McatFile::from_bytes(data, None, ext_from_url(url), Some(url.to_owned()), true)?;
+    if file.kind == McatKind::PreMarkdown
+        && let Some(fmt) = format
+    {
+        file.kind = fmt;
+    }
+    Ok(file)
+}
+
 fn ext_from_url(url: &str) -> Option<String> {
     url.split('?')
         .next()
@@ -110,6 +143,14 @@ fn ext_from_url(url: &str) -> Option<String> {
         .map(|e| e.to_string())
 }
 
+fn get_header(response: &Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_owned)
+}
+
 fn get_mime(response: &Response) -> Option<String> {
     response
         .headers()
@@ -209,7 +250,7 @@ async fn scrape_html(
         .context("no valid media found on page")?;
     debug!(url = %best_url, "selected best candidate");
 
-    let response = get_response(client, &best_url, bar).await?;
+    let response = get_response(client, &best_url, bar, None).await?;
     let mime = get_mime(&response);
     let format = mime.as_deref().and_then(format_from_mime);
     let data = download(response, options, bar).await?;
@@ -228,10 +269,25 @@ async fn scrape_html(
     Ok(file)
 }
 
-async fn get_response(client: &Client, url: &str, bar: Option<&MultiBar>) -> Result<Response> {
+/// Sends a GET to `url`, revalidating `cached` if given (which may answer with 304).
+async fn get_response(
+    client: &Client,
+    url: &str,
+    bar: Option<&MultiBar>,
+    cached: Option<&RemoteEntry>,
+) -> Result<Response> {
     let handle = bar.map(|b| b.add(None, Some(&format!("Fetching {url}..."))));
 
-    let request = client.get(url).send();
+    let mut request = client.get(url);
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let request = request.send();
     tokio::pin!(request);
 
     let response = tokio::select! {
@@ -247,7 +303,11 @@ async fn get_response(client: &Client, url: &str, bar: Option<&MultiBar>) -> Res
     if let Some(h) = handle {
         h.finish();
     }
-    anyhow::ensure!(response.status().is_success(), response.status());
+    let not_modified = cached.is_some() && response.status() == StatusCode::NOT_MODIFIED;
+    anyhow::ensure!(
+        response.status().is_success() || not_modified,
+        response.status()
+    );
 
     Ok(response)
 }
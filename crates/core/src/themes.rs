@@ -66,6 +66,31 @@ impl From<&str> for ThemeColor {
     }
 }
 
+impl ThemeColor {
+    /// The red, green and blue channels.
+    pub fn rgb(&self) -> [u8; 3] {
+        [self.color.r, self.color.g, self.color.b]
+    }
+
+    /// The colour as `RRGGBB`, the way latex's `\color[HTML]` takes it.
+    pub fn hex(&self) -> String {
+        format!(
+            "{:02X}{:02X}{:02X}",
+            self.color.r, self.color.g, self.color.b
+        )
+    }
+
+    /// Whether light text reads better on this colour, same cutoff as `readable_on`.
+    pub fn is_dark(&self) -> bool {
+        let (r, g, b) = (
+            self.color.r as f32,
+            self.color.g as f32,
+            self.color.b as f32,
+        );
+        0.2126 * r + 0.7152 * g + 0.0722 * b <= 140.0
+    }
+}
+
 impl From<&config::Theme> for CustomTheme {
     fn from(s: &config::Theme) -> Self {
         match s {
@@ -675,6 +700,30 @@ impl CustomTheme {
         }
     }
 
+    /// Backgrounds of added and removed diff lines, green and red faded into the background.
+    pub fn diff_bgs(&self) -> (String, String) {
+        self.faded_green_red(0.25)
+    }
+
+    /// Stronger [`Self::diff_bgs`], for the words that changed within a line.
+    pub fn diff_word_bgs(&self) -> (String, String) {
+        self.faded_green_red(0.5)
+    }
+
+    fn faded_green_red(&self, amount: f32) -> (String, String) {
+        let bg = &self.background.color;
+        let fade = |c: &ThemeColor| {
+            let mix = |a: u8, b: u8| (a as f32 * amount + b as f32 * (1.0 - amount)).round() as u8;
+            let (r, g, b) = (
+                mix(c.color.r, bg.r),
+                mix(c.color.g, bg.g),
+                mix(c.color.b, bg.b),
+            );
+            format!("\x1b[48;2;{r};{g};{b}m")
+        };
+        (fade(&self.green), fade(&self.red))
+    }
+
     pub fn to_mermaid_theme(&self) -> mermaid_rs_renderer::Theme {
         let mut t = mermaid_rs_renderer::Theme::modern();
 
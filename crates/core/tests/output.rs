@@ -27,6 +27,18 @@ fn dummy_pdf() -> Vec<u8> {
         .unwrap()
 }
 
+// 3 frame 32x16 gif, red / green / blue
+fn dummy_gif() -> Vec<u8> {
+    general_purpose::STANDARD
+        .decode(
+            "R0lGODlhIAAQAIAAAAAAAAAAACH/C05FVFNDQVBFMi4wAwEAAAAh+QQIFAAAACwAAAAAIAAQAID/\
+         AAAAAAACFISPqcvtD6OctNqLs968+w+G4kgVACH5BAgUAAAALAAAAAAgABAAgAD/AAAAAAIUhI+p\
+         y+0Po5y02ouz3rz7D4biSBUAIfkECBQAAAAsAAAAACAAEACAAAD/AAAAAhSEj6nL7Q+jnLTai7Pe\
+         vPsPhuJIFQA7",
+        )
+        .unwrap()
+}
+
 fn has_cursor_forward_escape(bytes: &[u8]) -> bool {
     let mut i = 0;
     while i + 2 < bytes.len() {
@@ -132,6 +144,25 @@ fn stdin_pdf_inline_ascii_no_center_omits_escape() {
     );
 }
 
+#[test]
+fn stdin_pdf_inline_braille_text_mode() {
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["-o", "inline", "--ascii", "--text-mode", "braille"])
+        .write_stdin(dummy_pdf())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout
+            .chars()
+            .any(|c| ('\u{2801}'..='\u{28ff}').contains(&c)),
+        "expected braille cells in the output"
+    );
+}
+
 // see https://github.com/Skardyy/mcat/issues/86
 #[test]
 fn rust_file_piped_is_raw() {
@@ -183,3 +214,317 @@ fn stdin_md_with_color_always_flag_is_rendered_not_raw() {
         "expected ANSI escape sequences in colored output"
     );
 }
+
+#[test]
+fn stdin_md_color_depth_256_has_no_truecolor_escapes() {
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["-c", "--color-depth", "256"])
+        .write_stdin("# Header\n\nhello `world`")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("38;5;"), "expected 256 colour escapes");
+    assert!(!stdout.contains("38;2;"), "no truecolor escapes expected");
+}
+
+#[test]
+fn stdin_gif_inline_iterm_is_sent_once() {
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["-o", "inline", "--iterm"])
+        .write_stdin(dummy_gif())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.matches("\x1b]1337;File=").count(),
+        1,
+        "expected the gif to be sent as a single image"
+    );
+    assert!(stdout.contains("preserveAspectRatio=1"));
+}
+
+#[test]
+fn forced_encoder_falls_back_inside_zellij() {
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["-o", "inline", "--iterm"])
+        .env("ZELLIJ", "0")
+        .env_remove("TMUX")
+        .write_stdin(dummy_pdf())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        !stdout.contains("\x1b]1337;File="),
+        "zellij can't show iterm images"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("warning: Iterm images can't pass through Zellij"));
+}
+
+// the cache dir only follows XDG_CACHE_HOME on linux
+#[cfg(target_os = "linux")]
+#[test]
+fn ls_thumbnails_are_cached_unless_disabled() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.gif"), dummy_gif()).unwrap();
+    let thumbnails = |cache: &std::path::Path| {
+        std::fs::read_dir(cache.join("mcat").join("thumbnails"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .flat_map(|size| std::fs::read_dir(size.path()).unwrap().flatten())
+            .count()
+    };
+
+    let cache = tempfile::tempdir().unwrap();
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .env("XDG_CACHE_HOME", cache.path())
+        .args(["ls", "--ascii"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(thumbnails(cache.path()), 1);
+
+    let cache = tempfile::tempdir().unwrap();
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .env("XDG_CACHE_HOME", cache.path())
+        .args(["ls", "--ascii", "--no-cache"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(thumbnails(cache.path()), 0);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn ls_previews_text_files_unless_icons() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+    let run = |extra: &[&str]| {
+        let cache = tempfile::tempdir().unwrap();
+        let output = Command::cargo_bin("mcat")
+            .unwrap()
+            .env("XDG_CACHE_HOME", cache.path())
+            .args(["ls", "--ascii", "--theme", "nord"])
+            .args(extra)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        // previews are cached per theme, icons aren't cached at all
+        std::fs::read_dir(cache.path().join("mcat").join("thumbnails"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|size| size.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let sizes = run(&[]);
+    assert_eq!(sizes.len(), 1);
+    assert!(sizes[0].ends_with("-nord"));
+    assert!(run(&["--ls-icons"]).is_empty());
+}
+
+#[test]
+fn ls_tree_respects_max_depth() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("sub").join("deep")).unwrap();
+    std::fs::write(dir.path().join("sub").join("a.txt"), "12345").unwrap();
+    std::fs::write(dir.path().join("sub").join("deep").join("b.txt"), "123").unwrap();
+
+    let run = |extra: &[&str]| {
+        let output = Command::cargo_bin("mcat")
+            .unwrap()
+            .args(["ls", "--tree", "--ascii", "--no-cache"])
+            .args(extra)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let full = run(&[]);
+    assert!(full.contains("b.txt"));
+    assert!(full.contains("└── "));
+    assert!(full.contains("2 directories, 2 files"));
+
+    // sizes still count what's below the depth limit
+    let shallow = run(&["--max-depth", "1"]);
+    assert!(!shallow.contains("a.txt"));
+    assert!(shallow.contains("8B"));
+}
+
+#[test]
+fn ls_long_is_tab_separated_when_piped() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.gif"), dummy_gif()).unwrap();
+    std::fs::write(dir.path().join("b.pdf"), dummy_pdf()).unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["ls", "-l", "--no-cache"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rows: Vec<Vec<&str>> = stdout.lines().map(|l| l.split('\t').collect()).collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows.iter().all(|r| r.len() == 6));
+
+    let row = |name: &str| rows.iter().find(|r| r[5] == name).unwrap();
+    assert_eq!(row("sub")[3], "Dir");
+    assert_eq!(row("a.gif")[3], "Gif");
+    assert_eq!(row("a.gif")[4], "32x16");
+    assert_eq!(row("b.pdf")[3], "Pdf");
+    assert_eq!(row("b.pdf")[4], "1 page");
+    assert!(!stdout.contains('\x1b'));
+}
+
+#[test]
+fn info_reports_image_metadata() {
+    let mut tmp = Builder::new().suffix(".gif").tempfile().unwrap();
+    tmp.write_all(&dummy_gif()).unwrap();
+
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .arg("--info")
+        .arg(tmp.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("| Format | Gif |"));
+    assert!(stdout.contains("| Dimensions | 32x16 |"));
+    assert!(stdout.contains("| Frames | 3 |"));
+    assert!(stdout.contains("![histogram](data:image/png;base64,"));
+
+    let mut md = Builder::new().suffix(".md").tempfile().unwrap();
+    md.write_all(b"# not an image").unwrap();
+    Command::cargo_bin("mcat")
+        .unwrap()
+        .arg("--info")
+        .arg(md.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("only works with images"));
+}
+
+#[test]
+fn images_follow_exif_orientation_unless_disabled() {
+    // 2x1 png with an eXIf chunk saying "rotate 90 cw"
+    let png = general_purpose::STANDARD
+        .decode(
+            "iVBORw0KGgoAAAANSUhEUgAAAAIAAAABCAIAAAB7QOjdAAAAGmVYSWZNTQAqAAAACAABARIAAwAAAAEABgAA\
+             AAAAANZnS2kAAAANSURBVHicY/jPAAT/AQcAAf/iI55ZAAAAAElFTkSuQmCC",
+        )
+        .unwrap();
+    let mut tmp = Builder::new().suffix(".png").tempfile().unwrap();
+    tmp.write_all(&png).unwrap();
+
+    let size = |extra: &[&str]| {
+        let output = Command::cargo_bin("mcat")
+            .unwrap()
+            .arg(tmp.path())
+            .args(["-o", "image"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let ihdr = &output.stdout[16..24];
+        (
+            u32::from_be_bytes(ihdr[..4].try_into().unwrap()),
+            u32::from_be_bytes(ihdr[4..].try_into().unwrap()),
+        )
+    };
+
+    assert_eq!(size(&[]), (1, 2));
+    assert_eq!(size(&["--no-img-metadata"]), (2, 1));
+}
+
+#[test]
+fn diff_piped_is_a_unified_patch() {
+    let mut a = Builder::new().suffix(".rs").tempfile().unwrap();
+    a.write_all(b"fn a() {}\nfn b() {}\n").unwrap();
+    let mut b = Builder::new().suffix(".rs").tempfile().unwrap();
+    b.write_all(b"fn a() {}\nfn c() {}\n").unwrap();
+
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .arg("--diff")
+        .arg(a.path())
+        .arg(b.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("@@ -1,2 +1,2 @@\n fn a() {}\n-fn b() {}\n+fn c() {}\n"));
+
+    Command::cargo_bin("mcat")
+        .unwrap()
+        .arg("--diff")
+        .arg(a.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("exactly two files"));
+}
+
+#[test]
+fn diff_of_two_images_is_a_heatmap() {
+    let mut gif = Builder::new().suffix(".gif").tempfile().unwrap();
+    gif.write_all(&dummy_gif()).unwrap();
+    let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="16"><rect width="16" height="16" fill="red"/></svg>"#;
+    let mut other = Builder::new().suffix(".svg").tempfile().unwrap();
+    other.write_all(svg).unwrap();
+
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["--diff", "-o", "image"])
+        .arg(gif.path())
+        .arg(other.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.starts_with(b"\x89PNG"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pixels differ"));
+}
+
+#[test]
+fn pdf_output_keeps_pdfs_as_they_are() {
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["-o", "pdf"])
+        .write_stdin(dummy_pdf())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, dummy_pdf());
+}
+
+#[test]
+fn pdf_margin_needs_a_unit() {
+    Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["-o", "pdf", "--pdf-margin", "15"])
+        .write_stdin(dummy_pdf())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown length unit"));
+}
@@ -62,6 +62,24 @@ fn stdin_pdf_detected_as_pdf() {
         .stdout(predicate::str::contains("kind: Pdf"));
 }
 
+#[test]
+fn stdin_heic_and_avif_detected_as_image() {
+    // ftyp boxes, decoded through ffmpeg
+    for brand in [b"heic", b"avif"] {
+        let mut bytes = b"\x00\x00\x00\x18ftyp".to_vec();
+        bytes.extend_from_slice(brand);
+        bytes.extend_from_slice(b"\x00\x00\x00\x00mif1");
+        bytes.extend_from_slice(brand);
+        Command::cargo_bin("mcat")
+            .unwrap()
+            .arg("--testing")
+            .write_stdin(bytes)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("kind: Image"));
+    }
+}
+
 #[test]
 fn stdin_jpeg_detected_as_image() {
     // JPEG magic bytes
@@ -136,3 +154,77 @@ fn mmd_file_with_mathpix_content_is_markdown() {
                 .or(predicate::str::contains("kind: PreMarkdown")),
         );
 }
+
+#[test]
+fn dir_without_tty_is_collected_non_recursively() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.md"), "# a").unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub").join("b.md"), "# b").unwrap();
+
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .arg("--testing")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("kind: Markdown").count(), 1);
+}
+
+#[test]
+fn dir_recursive_with_filters() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.md"), "# a").unwrap();
+    std::fs::write(dir.path().join("big.md"), "#".repeat(4096)).unwrap();
+    std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub").join("b.md"), "# b").unwrap();
+    std::fs::write(dir.path().join("sub").join("skip.md"), "# skip").unwrap();
+
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["--testing", "-R", "--only", "doc", "--max-size", "1K"])
+        .args(["--exclude", "skip.md"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("kind: Markdown").count(), 2);
+    assert!(!stdout.contains("PreMarkdown"));
+}
+
+#[test]
+fn dir_only_text_skips_unknown_extensions() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "notes").unwrap();
+    std::fs::write(dir.path().join("main.o"), [0u8, 1, 2, 3]).unwrap();
+    std::fs::write(dir.path().join("blob.bin"), [0u8, 1, 2, 3]).unwrap();
+    std::fs::write(dir.path().join("Makefile"), "all:").unwrap();
+
+    let output = Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["--testing", "--only", "text"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("kind: PreMarkdown").count(), 2);
+}
+
+#[test]
+fn huge_max_size_is_an_error() {
+    Command::cargo_bin("mcat")
+        .unwrap()
+        .args(["--testing", "-R", "--max-size", "99999999999999G", "."])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("size too large"));
+}
@@ -1,7 +1,7 @@
 use crate::{MarkdownifyInput, error::ParsingError};
 use std::{
     collections::BTreeMap,
-    io::{Cursor, Read},
+    io::{Cursor, Read, Seek},
     path::Path,
 };
 use tar::Archive;
@@ -87,6 +87,42 @@ pub fn parse_tar(
     tree.render()
 }
 
+/// Lists up to `limit` files of a zip archive, only its central directory is read.
+pub fn list_zip(reader: impl Read + Seek, limit: usize) -> Result<Vec<String>, ParsingError> {
+    let archive = ZipArchive::new(reader).map_err(|e| ParsingError::ArchiveError(e.to_string()))?;
+    Ok(archive
+        .file_names()
+        .filter(|name| !name.ends_with('/') && !should_skip_file(name))
+        .take(limit)
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Lists up to `limit` files of a tar archive, reading it only as far as the last of them.
+pub fn list_tar(reader: impl Read, limit: usize) -> Result<Vec<String>, ParsingError> {
+    let mut archive = Archive::new(reader);
+    let mut names = Vec::new();
+    for entry in archive
+        .entries()
+        .map_err(|e| ParsingError::ArchiveError(e.to_string()))?
+    {
+        let entry = entry.map_err(|e| ParsingError::ArchiveError(e.to_string()))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(|e| ParsingError::ArchiveError(e.to_string()))?
+            .to_string_lossy()
+            .to_string();
+        names.push(name);
+        if names.len() >= limit {
+            break;
+        }
+    }
+    Ok(names)
+}
+
 fn should_skip_file(name: &str) -> bool {
     name.starts_with("__MACOSX/")
         || name.contains("/._")
@@ -121,6 +157,13 @@ impl FileTree {
         Ok(output)
     }
 
+    /// Renders just the tree of the files, without the fence and the contents.
+    pub fn tree(&self) -> String {
+        let mut output = String::new();
+        self.render_tree(&mut output);
+        output
+    }
+
     fn render_tree(&self, output: &mut String) {
         let mut root: BTreeMap<String, Node> = BTreeMap::new();
 
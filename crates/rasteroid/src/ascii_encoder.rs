@@ -2,6 +2,7 @@ use image::DynamicImage;
 
 use crate::{
     VideoFrame,
+    color::ColorDepth,
     error::RasterError,
     image_extended::InlineImage,
     term_misc::{self, Wininfo, ensure_space},
@@ -11,26 +12,89 @@ use std::{
     time::Duration,
 };
 
+/// How images are drawn with text when no graphics protocol is available.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum TextMode {
+    /// Upper/lower half blocks, 1x2 pixels per cell.
+    #[default]
+    Half,
+    /// Quadrant blocks, 2x2 pixels per cell.
+    Quadrant,
+    /// Sextant blocks (Unicode 13), 2x3 pixels per cell.
+    Sextant,
+    /// Braille dots, 2x4 pixels per cell. one colour per cell, dithered.
+    Braille,
+    /// Half blocks using the xterm 256 colour palette.
+    Ansi256,
+    /// Half blocks using the 16 basic ansi colours.
+    Ansi16,
+}
+
+impl TextMode {
+    /// Returns how many image pixels a single cell covers, as `(width, height)`.
+    ///
+    /// ```
+    /// use rasteroid::ascii_encoder::TextMode;
+    ///
+    /// assert_eq!(TextMode::Half.cell_size(), (1, 2));
+    /// assert_eq!(TextMode::Sextant.cell_size(), (2, 3));
+    /// assert_eq!(TextMode::Braille.cell_size(), (2, 4));
+    /// ```
+    pub fn cell_size(&self) -> (u32, u32) {
+        match self {
+            TextMode::Half | TextMode::Ansi256 | TextMode::Ansi16 => (1, 2),
+            TextMode::Quadrant => (2, 2),
+            TextMode::Sextant => (2, 3),
+            TextMode::Braille => (2, 4),
+        }
+    }
+
+    fn color_depth(&self) -> ColorDepth {
+        match self {
+            TextMode::Ansi256 => ColorDepth::Ansi256,
+            TextMode::Ansi16 => ColorDepth::Ansi16,
+            _ => ColorDepth::TrueColor,
+        }
+    }
+}
+
+const MIN_VISUAL_WEIGHT: f32 = 25.0; // tweak for strictness
+
+// 4x4 ordered dithering matrix, used for braille
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// Writes `img` as text using [`Wininfo::text_mode`], every cell covering [`TextMode::cell_size`] pixels.
+/// the image should already be resized for it, see [`crate::image_extended::InlineImage::resize_plus`].
+/// colours are limited to [`Wininfo::color_depth`].
 pub fn encode_image(
     img: &DynamicImage,
     out: &mut impl Write,
     offset: Option<u16>,
     print_at: Option<(u16, u16)>,
+    wininfo: &Wininfo,
 ) -> Result<(), RasterError> {
     let rgba_image = img.to_rgba8();
 
-    let w = rgba_image.width() as usize;
-    let h = rgba_image.height() as usize;
-    let h_adjusted = if h % 2 == 1 { h - 1 } else { h };
+    let mode = wininfo.text_mode;
+    let (cw, ch) = mode.cell_size();
+    let cols = rgba_image.width().div_ceil(cw);
+    let rows = rgba_image.height().div_ceil(ch);
+    let palette = mode.color_depth().min(wininfo.color_depth);
 
-    // Luminance threshold: tweak this to suppress small sparkles
-    const LUM_THRESHOLD: f32 = 35.0;
+    // pixels outside the image are treated as transparent
+    let pixel = |x: u32, y: u32| -> Option<[u8; 3]> {
+        let p = rgba_image.get_pixel_checked(x, y)?;
+        let (r, g, b, a) = (p[0], p[1], p[2], p[3]);
+        (visual_weight(r, g, b, a) > MIN_VISUAL_WEIGHT).then_some([r, g, b])
+    };
 
-    let mut last_max_height = 0;
-    for y in (0..h_adjusted).step_by(2) {
+    for row in 0..rows {
         if let Some(at) = print_at {
-            let at = (at.0, at.1 + (y / 2) as u16);
-            last_max_height = y;
+            let at = (at.0, at.1 + row as u16);
             let loc = term_misc::loc_to_terminal(Some(at));
             out.write_all(loc.as_ref())?;
         }
@@ -39,38 +103,28 @@ pub fn encode_image(
             out.write_all(center.as_ref())?;
         }
 
-        for x in 0..w {
-            let upper = rgba_image.get_pixel(x as u32, y as u32);
-            let lower = rgba_image.get_pixel(x as u32, (y + 1) as u32);
-
-            let (ru, gu, bu, au) = (upper[0], upper[1], upper[2], upper[3]);
-            let (rl, gl, bl, al) = (lower[0], lower[1], lower[2], lower[3]);
-
-            const MIN_VISUAL_WEIGHT: f32 = 25.0; // tweak for strictness
-
-            let upper_w = visual_weight(ru, gu, bu, au);
-            let lower_w = visual_weight(rl, gl, bl, al);
-
-            let upper_visible = upper_w > MIN_VISUAL_WEIGHT;
-            let lower_visible = lower_w > MIN_VISUAL_WEIGHT;
-
-            match (upper_visible, lower_visible) {
-                (false, false) => {
-                    out.write_all(b" ")?;
-                }
-                (true, false) => {
-                    write!(out, "\x1b[38;2;{};{};{}m▀\x1b[0m", ru, gu, bu)?;
+        for col in 0..cols {
+            let (x0, y0) = (col * cw, row * ch);
+            match mode {
+                TextMode::Braille => {
+                    let cell: Vec<_> = (0..ch)
+                        .flat_map(|dy| (0..cw).map(move |dx| (x0 + dx, y0 + dy)))
+                        .map(|(x, y)| {
+                            let p = rgba_image.get_pixel_checked(x, y)?;
+                            let weight = visual_weight(p[0], p[1], p[2], p[3]);
+                            let threshold =
+                                (BAYER[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0 * 255.0;
+                            (weight > threshold).then_some([p[0], p[1], p[2]])
+                        })
+                        .collect();
+                    write_braille_cell(out, &cell, palette)?;
                 }
-                (false, true) => {
-                    write!(out, "\x1b[38;2;{};{};{}m▄\x1b[0m", rl, gl, bl)?;
-                }
-                (true, true) => {
-                    // Use dual-color upper/lower block
-                    write!(
-                        out,
-                        "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀\x1b[0m",
-                        ru, gu, bu, rl, gl, bl
-                    )?;
+                _ => {
+                    let cell: Vec<_> = (0..ch)
+                        .flat_map(|dy| (0..cw).map(move |dx| (x0 + dx, y0 + dy)))
+                        .map(|(x, y)| pixel(x, y))
+                        .collect();
+                    write_block_cell(out, &cell, mode, palette)?;
                 }
             }
         }
@@ -78,37 +132,135 @@ pub fn encode_image(
         out.write_all(b"\n")?;
     }
 
-    if h % 2 == 1 {
-        if let Some(at) = print_at {
-            let add_y = (last_max_height + 2) / 2;
-            let at = (at.0, at.1 + add_y as u16);
-            let loc = term_misc::loc_to_terminal(Some(at));
-            out.write_all(loc.as_ref())?;
-        }
-        if let Some(off) = offset {
-            let center = term_misc::offset_to_terminal(Some(off));
-            out.write_all(center.as_ref())?;
-        }
+    out.write_all(b"\x1b[0m")?;
+    Ok(())
+}
 
-        for x in 0..w {
-            let p = rgba_image.get_pixel(x as u32, (h - 1) as u32);
-            let (r, g, b, a) = (p[0], p[1], p[2], p[3]);
-            let lum = luminance(r, g, b);
+/// Splits the cell into a bright and a dark group, the bright one is drawn with the glyph
+/// and the dark one as the background. transparent pixels are left to the terminal background.
+fn write_block_cell(
+    out: &mut impl Write,
+    cell: &[Option<[u8; 3]>],
+    mode: TextMode,
+    palette: ColorDepth,
+) -> Result<(), RasterError> {
+    let visible: Vec<[u8; 3]> = cell.iter().flatten().copied().collect();
+    if visible.is_empty() {
+        out.write_all(b" ")?;
+        return Ok(());
+    }
 
-            if a == 0 || lum < LUM_THRESHOLD {
-                out.write_all(b" ")?;
+    let mut mask = 0usize;
+    let mut bg = None;
+    if visible.len() < cell.len() {
+        for (i, p) in cell.iter().enumerate() {
+            if p.is_some() {
+                mask |= 1 << i;
+            }
+        }
+    } else {
+        let mean = visible
+            .iter()
+            .map(|p| luminance(p[0], p[1], p[2]))
+            .sum::<f32>()
+            / visible.len() as f32;
+        let mut dark = Vec::new();
+        for (i, p) in visible.iter().enumerate() {
+            if luminance(p[0], p[1], p[2]) >= mean {
+                mask |= 1 << i;
             } else {
-                write!(out, "\x1b[38;2;{};{};{}m▀\x1b[0m", r, g, b)?;
+                dark.push(*p);
             }
         }
+        if !dark.is_empty() {
+            bg = Some(average(&dark));
+        }
+    }
 
-        out.write_all(b"\n")?;
+    let bright: Vec<[u8; 3]> = cell
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .filter_map(|(_, p)| *p)
+        .collect();
+
+    write_color(out, average(&bright), palette, false)?;
+    if let Some(bg) = bg {
+        write_color(out, bg, palette, true)?;
     }
+    write!(out, "{}\x1b[0m", block_glyph(mode, mask))?;
 
-    out.write_all(b"\x1b[0m")?;
     Ok(())
 }
 
+fn write_braille_cell(
+    out: &mut impl Write,
+    cell: &[Option<[u8; 3]>],
+    palette: ColorDepth,
+) -> Result<(), RasterError> {
+    // cell is row major 2x4, braille dots are numbered down the columns first
+    const DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+    let mut bits = 0;
+    let mut lit = Vec::new();
+    for (i, p) in cell.iter().enumerate() {
+        if let Some(p) = p {
+            bits |= DOTS[i];
+            lit.push(*p);
+        }
+    }
+    if lit.is_empty() {
+        out.write_all(b" ")?;
+        return Ok(());
+    }
+
+    write_color(out, average(&lit), palette, false)?;
+    let glyph = char::from_u32(0x2800 + bits).unwrap_or(' ');
+    write!(out, "{glyph}\x1b[0m")?;
+
+    Ok(())
+}
+
+fn block_glyph(mode: TextMode, mask: usize) -> char {
+    match mode {
+        TextMode::Quadrant => QUADRANTS[mask],
+        TextMode::Sextant => match mask {
+            0 => ' ',
+            21 => '▌',
+            42 => '▐',
+            63 => '█',
+            // U+1FB00 onwards skips the patterns that already exist as half blocks
+            m => {
+                let skipped = (m > 21) as usize + (m > 42) as usize;
+                char::from_u32(0x1FB00 + (m - 1 - skipped) as u32).unwrap_or(' ')
+            }
+        },
+        _ => [' ', '▀', '▄', '█'][mask],
+    }
+}
+
+fn write_color(
+    out: &mut impl Write,
+    rgb: [u8; 3],
+    palette: ColorDepth,
+    background: bool,
+) -> Result<(), RasterError> {
+    write!(out, "\x1b[{}m", palette.sgr(rgb, background))?;
+    Ok(())
+}
+
+fn average(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let n = pixels.len().max(1) as u32;
+    let sum = pixels.iter().fold([0u32; 3], |acc, p| {
+        [
+            acc[0] + p[0] as u32,
+            acc[1] + p[1] as u32,
+            acc[2] + p[2] as u32,
+        ]
+    });
+    sum.map(|c| (c / n) as u8)
+}
+
 fn luminance(r: u8, g: u8, b: u8) -> f32 {
     0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32
 }
@@ -142,7 +294,7 @@ pub fn encode_frames(
         let resized = img.resize_plus(wininfo, Some("80%"), Some("40%"), true, false)?;
         let mut buffer = Vec::new();
 
-        encode_image(&resized, &mut buffer, offset, print_at)?;
+        encode_image(&resized, &mut buffer, offset, print_at, wininfo)?;
 
         clear_write_frame(&mut out, &buffer, start)?;
         start = false;
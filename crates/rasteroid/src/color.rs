@@ -0,0 +1,481 @@
+use std::{borrow::Cow, env, fmt, fs, path::PathBuf};
+
+use crate::term_misc::EnvIdentifiers;
+
+/// How many colours the terminal can show.
+///
+/// ordered from least to most capable, so `min` picks the more restrictive one.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    #[default]
+    TrueColor,
+}
+
+// xterm defaults, the actual colours depend on the terminal theme
+const ANSI16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorDepth {
+    /// Detects the colour depth from the env (`COLORTERM`, known terminals), then the terminfo
+    /// entry of `TERM` (`RGB` / `Tc`, `colors`) and the terminfo naming of `TERM` (`-256color`).
+    ///
+    /// only downgrades to 16 colours when that's certain, the linux console, `vt*`, `ansi` and
+    /// `*16color` terminals. anything else falls back to truecolor, plain `xterm` and `screen`
+    /// included, since containers and multiplexers report those for terminals that do have it.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rasteroid::{color::ColorDepth, term_misc::EnvIdentifiers};
+    ///
+    /// let env = EnvIdentifiers {
+    ///     data: HashMap::from([("TERM".to_string(), "xterm-256color".to_string())]),
+    /// };
+    /// assert_eq!(ColorDepth::detect(&env), ColorDepth::Ansi256);
+    ///
+    /// let env = EnvIdentifiers {
+    ///     data: HashMap::from([
+    ///         ("TERM".to_string(), "xterm-256color".to_string()),
+    ///         ("COLORTERM".to_string(), "truecolor".to_string()),
+    ///     ]),
+    /// };
+    /// assert_eq!(ColorDepth::detect(&env), ColorDepth::TrueColor);
+    ///
+    /// let env = EnvIdentifiers {
+    ///     data: HashMap::from([("TERM".to_string(), "linux".to_string())]),
+    /// };
+    /// assert_eq!(ColorDepth::detect(&env), ColorDepth::Ansi16);
+    ///
+    /// let env = EnvIdentifiers {
+    ///     data: HashMap::from([("TERM".to_string(), "xterm".to_string())]),
+    /// };
+    /// assert_eq!(ColorDepth::detect(&env), ColorDepth::TrueColor);
+    /// ```
+    pub fn detect(env: &EnvIdentifiers) -> Self {
+        if env.contains("COLORTERM", "truecolor") || env.contains("COLORTERM", "24bit") {
+            return ColorDepth::TrueColor;
+        }
+        if env.has_key("KITTY_WINDOW_ID")
+            || env.has_key("KONSOLE_VERSION")
+            || env.has_key("WT_PROFILE_ID")
+            || [
+                "direct",
+                "kitty",
+                "ghostty",
+                "wezterm",
+                "iterm",
+                "alacritty",
+                "foot",
+                "rio",
+                "vscode",
+            ]
+            .iter()
+            .any(|t| env.term_contains(t))
+        {
+            return ColorDepth::TrueColor;
+        }
+
+        let term = env.data.get("TERM").map(String::as_str).unwrap_or_default();
+        let terminfo = TermColors::load(term).unwrap_or_default();
+        if terminfo.rgb || terminfo.colors.is_some_and(|c| c >= 1 << 24) {
+            return ColorDepth::TrueColor;
+        }
+        if env.term_contains("256") || terminfo.colors.is_some_and(|c| c >= 256) {
+            return ColorDepth::Ansi256;
+        }
+
+        match term {
+            "linux" | "ansi" | "cygwin" => ColorDepth::Ansi16,
+            term if term.starts_with("vt") || term.contains("16color") => ColorDepth::Ansi16,
+            _ => ColorDepth::TrueColor,
+        }
+    }
+
+    /// Returns the SGR parameters selecting `rgb` as the foreground (or background) colour.
+    ///
+    /// ```
+    /// use rasteroid::color::ColorDepth;
+    ///
+    /// assert_eq!(ColorDepth::TrueColor.sgr([255, 0, 0], false), "38;2;255;0;0");
+    /// assert_eq!(ColorDepth::Ansi256.sgr([255, 0, 0], true), "48;5;196");
+    /// assert_eq!(ColorDepth::Ansi16.sgr([255, 0, 0], false), "91");
+    /// ```
+    pub fn sgr(&self, [r, g, b]: [u8; 3], background: bool) -> String {
+        let layer = if background { 48 } else { 38 };
+        match self {
+            ColorDepth::TrueColor => format!("{layer};2;{r};{g};{b}"),
+            ColorDepth::Ansi256 => format!("{layer};5;{}", rgb_to_ansi256(r, g, b)),
+            ColorDepth::Ansi16 => {
+                let idx = rgb_to_ansi16(r, g, b);
+                let code = match (idx < 8, background) {
+                    (true, false) => 30 + idx,
+                    (false, false) => 90 + idx - 8,
+                    (true, true) => 40 + idx,
+                    (false, true) => 100 + idx - 8,
+                };
+                code.to_string()
+            }
+        }
+    }
+
+    /// Rewrites the truecolor (and 256 colour) SGR sequences in `text` to fit this depth.
+    /// anything else passes through untouched.
+    ///
+    /// ```
+    /// use rasteroid::color::ColorDepth;
+    ///
+    /// let text = "\x1b[1;38;2;255;0;0mhi\x1b[0m";
+    /// assert_eq!(ColorDepth::TrueColor.downsample(text), text);
+    /// assert_eq!(ColorDepth::Ansi256.downsample(text), "\x1b[1;38;5;196mhi\x1b[0m");
+    /// assert_eq!(ColorDepth::Ansi16.downsample(text), "\x1b[1;91mhi\x1b[0m");
+    /// ```
+    pub fn downsample<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if *self == ColorDepth::TrueColor || !text.contains("\x1b[") {
+            return Cow::Borrowed(text);
+        }
+
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("\x1b[") {
+            result.push_str(&rest[..start]);
+            rest = &rest[start + 2..];
+
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == ';'))
+                .unwrap_or(rest.len());
+            let (params, tail) = rest.split_at(end);
+            if tail.starts_with('m') {
+                result.push_str("\x1b[");
+                result.push_str(&self.downsample_params(params));
+            } else {
+                // not an SGR, leave it be
+                result.push_str("\x1b[");
+                result.push_str(params);
+            }
+            rest = tail;
+        }
+        result.push_str(rest);
+
+        Cow::Owned(result)
+    }
+
+    fn downsample_params(&self, params: &str) -> String {
+        let parts: Vec<&str> = params.split(';').collect();
+        let mut out: Vec<String> = Vec::with_capacity(parts.len());
+        let mut i = 0;
+        while i < parts.len() {
+            let layer = parts[i];
+            let background = layer == "48";
+            if layer == "38" || layer == "48" {
+                let num = |j: usize| parts.get(j).and_then(|v| v.parse::<u8>().ok());
+                match parts.get(i + 1).copied() {
+                    Some("2") => {
+                        if let (Some(r), Some(g), Some(b)) = (num(i + 2), num(i + 3), num(i + 4)) {
+                            out.push(self.sgr([r, g, b], background));
+                            i += 5;
+                            continue;
+                        }
+                    }
+                    Some("5") if *self == ColorDepth::Ansi16 => {
+                        if let Some(n) = num(i + 2) {
+                            out.push(self.sgr(ansi256_to_rgb(n), background));
+                            i += 3;
+                            continue;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            out.push(layer.to_string());
+            i += 1;
+        }
+        out.join(";")
+    }
+}
+
+impl fmt::Display for ColorDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorDepth::Ansi16 => "16 colors",
+            ColorDepth::Ansi256 => "256 colors",
+            ColorDepth::TrueColor => "Truecolor",
+        };
+        f.pad(name)
+    }
+}
+
+// index of `colors` among terminfo's numeric capabilities
+const TERMINFO_COLORS: usize = 13;
+
+/// What the compiled terminfo entry of a terminal says about its colours.
+#[derive(Debug, Default, PartialEq)]
+struct TermColors {
+    colors: Option<i32>,
+    /// the `RGB` capability, or tmux's `Tc`
+    rgb: bool,
+}
+
+impl TermColors {
+    fn load(term: &str) -> Option<Self> {
+        let first = term.chars().next()?;
+        if term.contains(['/', '\\']) || term.starts_with('.') {
+            return None;
+        }
+        // `x/xterm` on linux, `78/xterm` on macos
+        terminfo_dirs().into_iter().find_map(|dir| {
+            [first.to_string(), format!("{:x}", first as u32)]
+                .into_iter()
+                .find_map(|sub| fs::read(dir.join(sub).join(term)).ok())
+                .and_then(|bytes| Self::parse(&bytes))
+        })
+    }
+
+    // the legacy format (16 bit numbers) and ncurses' 32 bit one, see term(5)
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let num_size = match read_i16(bytes, 0)? {
+            0o432 => 2,
+            0o1036 => 4,
+            _ => return None,
+        };
+        let header = |i: usize| read_i16(bytes, i * 2).map(|v| v.max(0) as usize);
+        let (names, bools, nums, strs, table) =
+            (header(1)?, header(2)?, header(3)?, header(4)?, header(5)?);
+
+        let mut at = 12 + names + bools;
+        at += at % 2;
+        let colors = (nums > TERMINFO_COLORS)
+            .then(|| read_num(bytes, at + TERMINFO_COLORS * num_size, num_size))
+            .flatten()
+            .filter(|c| *c >= 0);
+        at += nums * num_size + strs * 2 + table;
+        at += at % 2;
+
+        Some(Self {
+            colors,
+            rgb: extended_rgb(bytes, at, num_size).unwrap_or(false),
+        })
+    }
+}
+
+// the extended capabilities after the standard ones, where RGB and Tc are
+fn extended_rgb(bytes: &[u8], at: usize, num_size: usize) -> Option<bool> {
+    let header = |i: usize| read_i16(bytes, at + i * 2).map(|v| v.max(0) as usize);
+    let (bools, nums, strs, offsets, table_size) =
+        (header(0)?, header(1)?, header(2)?, header(3)?, header(4)?);
+
+    let bool_values = bytes.get(at + 10..at + 10 + bools)?;
+    let mut pos = at + 10 + bools;
+    pos += pos % 2;
+    let nums_at = pos;
+    pos += nums * num_size + offsets * 2;
+    let table = bytes.get(pos..pos + table_size)?;
+
+    // the capability names are the last strings of the table, booleans then numbers then strings
+    let strings: Vec<&[u8]> = table.split(|b| *b == 0).collect();
+    let strings = &strings[..strings.len().saturating_sub(1)];
+    let names = &strings[strings.len().checked_sub(bools + nums + strs)?..];
+    Some(names.iter().enumerate().any(|(i, name)| {
+        if *name != b"RGB" && *name != b"Tc" {
+            return false;
+        }
+        if i < bools {
+            bool_values[i] == 1
+        } else if i < bools + nums {
+            read_num(bytes, nums_at + (i - bools) * num_size, num_size).is_some_and(|n| n > 0)
+        } else {
+            true
+        }
+    }))
+}
+
+fn read_i16(bytes: &[u8], at: usize) -> Option<i16> {
+    Some(i16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_num(bytes: &[u8], at: usize, size: usize) -> Option<i32> {
+    match size {
+        2 => read_i16(bytes, at).map(i32::from),
+        _ => Some(i32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?)),
+    }
+}
+
+fn terminfo_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Some(list) = env::var_os("TERMINFO_DIRS") {
+        dirs.extend(env::split_paths(&list).filter(|p| !p.as_os_str().is_empty()));
+    }
+    dirs.extend(
+        [
+            "/etc/terminfo",
+            "/lib/terminfo",
+            "/usr/share/terminfo",
+            "/usr/lib/terminfo",
+        ]
+        .map(PathBuf::from),
+    );
+    dirs
+}
+
+/// Finds the closest xterm 256 palette index, from either the colour cube or the gray ramp.
+pub fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let to_level = |v: u8| match v {
+        0..48 => 0,
+        48..115 => 1,
+        v => (v - 35) / 40,
+    };
+
+    let (ri, gi, bi) = (to_level(r), to_level(g), to_level(b));
+    let cube = [
+        CUBE_LEVELS[ri as usize],
+        CUBE_LEVELS[gi as usize],
+        CUBE_LEVELS[bi as usize],
+    ];
+
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_idx = (avg.saturating_sub(8) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_idx;
+
+    if distance([r, g, b], [gray, gray, gray]) < distance([r, g, b], cube) {
+        232 + gray_idx
+    } else {
+        16 + 36 * ri + 6 * gi + bi
+    }
+}
+
+/// Finds the closest of the 16 basic ansi colours.
+pub fn rgb_to_ansi16(r: u8, g: u8, b: u8) -> u8 {
+    ANSI16
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, c)| distance([r, g, b], **c))
+        .map(|(i, _)| i as u8)
+        .unwrap_or(7)
+}
+
+fn ansi256_to_rgb(n: u8) -> [u8; 3] {
+    match n {
+        0..16 => ANSI16[n as usize],
+        16..232 => {
+            let n = n - 16;
+            [
+                CUBE_LEVELS[(n / 36) as usize],
+                CUBE_LEVELS[(n / 6 % 6) as usize],
+                CUBE_LEVELS[(n % 6) as usize],
+            ]
+        }
+        n => {
+            let v = 8 + 10 * (n - 232);
+            [v, v, v]
+        }
+    }
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (*x as i32 - *y as i32).pow(2) as u32)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminfo(magic: i16, colors: i32, extended: Option<&[u8]>) -> Vec<u8> {
+        let names = b"test|a test terminal\0";
+        let mut bytes = Vec::new();
+        for v in [magic, names.len() as i16, 0, 14, 0, 0] {
+            bytes.extend(v.to_le_bytes());
+        }
+        bytes.extend(names);
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        for i in 0..14 {
+            let n = if i == TERMINFO_COLORS { colors } else { -1 };
+            match magic {
+                0o432 => bytes.extend((n as i16).to_le_bytes()),
+                _ => bytes.extend(n.to_le_bytes()),
+            }
+        }
+        if let Some(ext) = extended {
+            bytes.extend(ext);
+        }
+        bytes
+    }
+
+    // one extended boolean named `name`, set to `value`
+    fn extended_bool(name: &str, value: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for v in [1i16, 0, 0, 1, name.len() as i16 + 1] {
+            bytes.extend(v.to_le_bytes());
+        }
+        bytes.extend([value, 0]);
+        bytes.extend(0i16.to_le_bytes());
+        bytes.extend(name.as_bytes());
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn reads_colors_from_legacy_terminfo() {
+        let parsed = TermColors::parse(&terminfo(0o432, 256, None));
+        assert_eq!(
+            parsed,
+            Some(TermColors {
+                colors: Some(256),
+                rgb: false
+            })
+        );
+    }
+
+    #[test]
+    fn reads_rgb_from_extended_terminfo() {
+        let bytes = terminfo(0o1036, 1 << 24, Some(&extended_bool("RGB", 1)));
+        assert_eq!(
+            TermColors::parse(&bytes),
+            Some(TermColors {
+                colors: Some(1 << 24),
+                rgb: true
+            })
+        );
+
+        let bytes = terminfo(0o432, 8, Some(&extended_bool("Tc", 1)));
+        assert!(TermColors::parse(&bytes).unwrap().rgb);
+        let bytes = terminfo(0o432, 8, Some(&extended_bool("XT", 1)));
+        assert!(!TermColors::parse(&bytes).unwrap().rgb);
+    }
+
+    #[test]
+    fn rejects_garbage_and_paths() {
+        assert_eq!(TermColors::parse(b"not terminfo"), None);
+        assert_eq!(TermColors::parse(&[]), None);
+        assert_eq!(TermColors::load("../../etc/passwd"), None);
+        assert_eq!(TermColors::load(""), None);
+    }
+}
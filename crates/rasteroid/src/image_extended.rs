@@ -16,7 +16,7 @@ pub trait InlineImage {
     /// * `wininfo` - Terminal info used to resolve percentage and cell based sizes
     /// * `width` - Target width: `%` (percentage of terminal), `c` (cells), or plain number (pixels). `None` derives from height keeping aspect ratio
     /// * `height` - Target height, same format as `width`. `None` derives from width keeping aspect ratio
-    /// * `resize_for_ascii` - If `true`, works in cell dimensions instead of pixels. Cell height is doubled to account for half block rendering
+    /// * `resize_for_ascii` - If `true`, works in cell dimensions instead of pixels. Each cell is scaled by [`Wininfo::text_mode`]'s pixels per cell
     /// * `pad` - If `true`, pads with empty pixels to fill the exact requested size while keeping aspect ratio. If `false`, result may be smaller than requested
     ///
     /// ```
@@ -54,16 +54,17 @@ impl InlineImage for DynamicImage {
         pad: bool,
     ) -> Result<DynamicImage, RasterError> {
         let (src_width, src_height) = self.dimensions();
+        let (cell_w, cell_h) = wininfo.text_mode.cell_size();
         let width = match width {
             Some(w) => match resize_for_ascii {
-                true => wininfo.dim_to_cells(w, term_misc::SizeDirection::Width)?,
+                true => wininfo.dim_to_cells(w, term_misc::SizeDirection::Width)? * cell_w,
                 false => wininfo.dim_to_px(w, term_misc::SizeDirection::Width)?,
             },
             None => src_width,
         };
         let height = match height {
             Some(h) => match resize_for_ascii {
-                true => wininfo.dim_to_cells(h, term_misc::SizeDirection::Height)? * 2,
+                true => wininfo.dim_to_cells(h, term_misc::SizeDirection::Height)? * cell_h,
                 false => wininfo.dim_to_px(h, term_misc::SizeDirection::Height)?,
             },
             None => src_height,
@@ -3,7 +3,8 @@ use image::{DynamicImage, codecs::jpeg::JpegEncoder};
 use crate::{
     VideoFrame,
     error::RasterError,
-    term_misc::{self, EnvIdentifiers, Wininfo},
+    multiplexer::Multiplexer,
+    term_misc::{self, EnvIdentifiers, Wininfo, ensure_space},
 };
 use std::{
     io::{Cursor, Write},
@@ -33,18 +34,76 @@ pub fn encode_image(
     out.write_all(at.as_ref())?;
     out.write_all(center.as_ref())?;
 
-    let prefix = if wininfo.is_tmux {
-        "\x1bPtmux;\x1b\x1b"
-    } else {
-        "\x1b"
-    };
-    let suffix = if wininfo.is_tmux {
-        "\x07\x1b\\"
-    } else {
-        "\x07"
-    };
+    let args = format!("inline=1;size={}", buf.len());
+    write_file(out, &args, &base64_encoded, wininfo.multiplexer)?;
+
+    Ok(())
+}
+
+/// Sends an already encoded animation (gif) once, and lets the terminal play it.
+///
+/// `size` is the target `(cols, rows)` in cells, the terminal keeps the aspect ratio.
+pub fn encode_animation(
+    bytes: &[u8],
+    out: &mut impl Write,
+    offset: Option<u16>,
+    print_at: Option<(u16, u16)>,
+    size: (u32, u32),
+    wininfo: &Wininfo,
+) -> Result<(), RasterError> {
+    let (cols, rows) = size;
+    let base64_encoded = term_misc::image_to_base64(bytes);
+
+    // the multiplexer doesn't know about the image, so we move the cursor ourselves
+    let self_handle = wininfo.multiplexer.is_passthrough();
+    if self_handle {
+        ensure_space(out, rows as u16)?;
+    }
+
+    let center = term_misc::offset_to_terminal(offset);
+    let at = term_misc::loc_to_terminal(print_at);
+    out.write_all(at.as_ref())?;
+    out.write_all(center.as_ref())?;
+
+    let args = format!(
+        "inline=1;size={};width={cols};height={rows};preserveAspectRatio=1",
+        bytes.len()
+    );
+    write_file(out, &args, &base64_encoded, wininfo.multiplexer)?;
 
-    write!(out, "{prefix}]1337;File=inline=1;:{base64_encoded}{suffix}",)?;
+    if self_handle {
+        write!(out, "\x1B[{rows}B")?;
+    }
+
+    Ok(())
+}
+
+/// Writes a `File` sequence, large payloads are split with `MultipartFile`/`FilePart`
+/// so they don't hit the terminal's escape length limit.
+fn write_file(
+    out: &mut impl Write,
+    args: &str,
+    base64_encoded: &str,
+    mux: Multiplexer,
+) -> Result<(), RasterError> {
+    const MULTIPART_THRESHOLD: usize = 1024 * 1024;
+    const PART_SIZE: usize = 256 * 1024;
+
+    if base64_encoded.len() <= MULTIPART_THRESHOLD {
+        let seq = format!("\x1b]1337;File={args}:{base64_encoded}\x07");
+        out.write_all(mux.wrap(&seq).as_bytes())?;
+        return Ok(());
+    }
+
+    let seq = format!("\x1b]1337;MultipartFile={args}\x07");
+    out.write_all(mux.wrap(&seq).as_bytes())?;
+    // base64 is ascii, so any byte index is a char boundary
+    for start in (0..base64_encoded.len()).step_by(PART_SIZE) {
+        let end = (start + PART_SIZE).min(base64_encoded.len());
+        let seq = format!("\x1b]1337;FilePart={}\x07", &base64_encoded[start..end]);
+        out.write_all(mux.wrap(&seq).as_bytes())?;
+    }
+    out.write_all(mux.wrap("\x1b]1337;FileEnd\x07").as_bytes())?;
 
     Ok(())
 }
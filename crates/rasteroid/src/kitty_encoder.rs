@@ -11,6 +11,7 @@ use image::{DynamicImage, GenericImageView};
 use crate::{
     VideoFrame,
     error::RasterError,
+    multiplexer::Multiplexer,
     term_misc::{
         self, EnvIdentifiers, Wininfo, image_to_base64, loc_to_terminal, offset_to_terminal,
     },
@@ -22,7 +23,7 @@ fn transmit_shm(
     mut out: impl Write,
     opts: HashMap<String, String>,
     shm_name: &str,
-    tmux: bool,
+    mux: Multiplexer,
 ) -> Result<(), RasterError> {
     let mut opts_string = String::with_capacity(opts.len() * 8);
     for (key, value) in opts {
@@ -42,14 +43,8 @@ fn transmit_shm(
     shmem_slice[..data.len()].copy_from_slice(data);
     let shm_name = general_purpose::STANDARD.encode(shm_name);
 
-    let prefix = if tmux {
-        "\x1bPtmux;\x1b\x1b_G"
-    } else {
-        "\x1b_G"
-    };
-    let suffix = if tmux { "\x1b\x1b\\\x1b\\" } else { "\x1b\\" };
-
-    write!(out, "{prefix}{opts_string};{shm_name}{suffix}")?;
+    let seq = format!("\x1b_G{opts_string};{shm_name}\x1b\\");
+    out.write_all(mux.wrap(&seq).as_bytes())?;
 
     // should be cleaned later. but the nature of this is it can be leaked.
     // perhaps we need to reconsider how to do this, since its a leak that precedes the app
@@ -64,7 +59,7 @@ fn chunk_base64(
     size: usize,
     first_opts: HashMap<String, String>,
     sub_opts: HashMap<String, String>,
-    tmux: bool,
+    mux: Multiplexer,
 ) -> Result<(), RasterError> {
     // first block
     let mut first_opts_string = String::with_capacity(first_opts.len() * 8);
@@ -90,12 +85,6 @@ fn chunk_base64(
         sub_opts_string.push(',');
     }
 
-    let (prefix, suffix) = if tmux {
-        ("\x1bPtmux;\x1b\x1b_G", "\x1b\x1b\\\x1b\\")
-    } else {
-        ("\x1b_G", "\x1b\\")
-    };
-
     let total_bytes = base64.len();
     let mut start = 0;
 
@@ -110,10 +99,8 @@ fn chunk_base64(
             &sub_opts_string
         };
 
-        write!(
-            out,
-            "{prefix}{opts}q=2,m={more_chunks};{chunk_data}{suffix}"
-        )?;
+        let seq = format!("\x1b_G{opts}q=2,m={more_chunks};{chunk_data}\x1b\\");
+        out.write_all(mux.wrap(&seq).as_bytes())?;
 
         start = end;
     }
@@ -138,7 +125,7 @@ pub fn encode_image(
         ("i".to_string(), id.to_string()),
     ]);
 
-    if wininfo.is_tmux || wininfo.needs_inline {
+    if wininfo.multiplexer.is_passthrough() || wininfo.needs_inline {
         let (widthpx, heightpx) = img.dimensions();
         let cols =
             wininfo.dim_to_cells(&format!("{widthpx}px"), term_misc::SizeDirection::Width)?;
@@ -149,7 +136,14 @@ pub fn encode_image(
         opts.insert("r".to_string(), rows.to_string());
         opts.insert("c".to_string(), cols.to_string());
         let base64 = image_to_base64(&png);
-        chunk_base64(&base64, out, 4096, opts, HashMap::new(), wininfo.is_tmux)?;
+        chunk_base64(
+            &base64,
+            out,
+            4096,
+            opts,
+            HashMap::new(),
+            wininfo.multiplexer,
+        )?;
 
         let placement = create_unicode_placeholder(cols, rows, id, offset, print_at)?;
         out.write_all(placement.as_bytes())?;
@@ -159,7 +153,14 @@ pub fn encode_image(
         out.write_all(print_at_string.as_ref())?;
         out.write_all(center_string.as_ref())?;
         let base64 = image_to_base64(&png);
-        chunk_base64(&base64, out, 4096, opts, HashMap::new(), wininfo.is_tmux)?;
+        chunk_base64(
+            &base64,
+            out,
+            4096,
+            opts,
+            HashMap::new(),
+            wininfo.multiplexer,
+        )?;
     }
 
     Ok(())
@@ -257,11 +258,11 @@ fn process_frame(
     sub_opts: Option<HashMap<String, String>>,
     use_shm: bool,
     shm_name: &str,
-    tmux: bool,
+    mux: Multiplexer,
 ) -> Result<(), RasterError> {
     #[cfg(target_os = "linux")]
     if use_shm {
-        transmit_shm(data, out, first_opts, shm_name, tmux)?;
+        transmit_shm(data, out, first_opts, shm_name, mux)?;
         return Ok(());
     }
     let base64 = general_purpose::STANDARD.encode(data);
@@ -271,7 +272,7 @@ fn process_frame(
         4096,
         first_opts,
         sub_opts.unwrap_or_default(),
-        tmux,
+        mux,
     )?;
     Ok(())
 }
@@ -347,14 +348,8 @@ fn encode_frames_sep(
     let id = rand::random::<u32>();
     let shm_name = format!("mcat-video-{id}-");
 
-    let tmux = wininfo.is_tmux;
-    let inline = wininfo.needs_inline || tmux;
-    let prefix = if tmux {
-        "\x1bPtmux;\x1b\x1b_G"
-    } else {
-        "\x1b_G"
-    };
-    let suffix = if tmux { "\x1b\x1b\\\x1b\\" } else { "\x1b\\" };
+    let mux = wininfo.multiplexer;
+    let inline = wininfo.needs_inline || mux.is_passthrough();
 
     // if not inline, its going to be a single row, we can just print it at the start and be done
     if !inline {
@@ -396,12 +391,13 @@ fn encode_frames_sep(
         None,
         use_shm,
         &format!("{shm_name}thumb"),
-        tmux,
+        mux,
     )?;
 
     // starting the animation
     let z = 100;
-    write!(out, "{prefix}a=a,s=2,v=1,r=1,i={id},z={z}{suffix}")?;
+    let seq = format!("\x1b_Ga=a,s=2,v=1,r=1,i={id},z={z}\x1b\\");
+    out.write_all(mux.wrap(&seq).as_bytes())?;
 
     let shutdown = term_misc::setup_signal_handler();
 
@@ -438,7 +434,7 @@ fn encode_frames_sep(
             Some(sub_opts),
             use_shm,
             &format!("{shm_name}{c}"),
-            tmux,
+            mux,
         )
         .is_err()
         {
@@ -450,7 +446,8 @@ fn encode_frames_sep(
         let placement = create_unicode_placeholder(cols, rows, id, offset, print_at)?;
         out.write_all(placement.as_bytes())?;
     }
-    write!(out, "{prefix}a=a,s=3,v=1,r=1,i={id},z={z}{suffix}")?;
+    let seq = format!("\x1b_Ga=a,s=3,v=1,r=1,i={id},z={z}\x1b\\");
+    out.write_all(mux.wrap(&seq).as_bytes())?;
     Ok((id, last_index))
 }
 
@@ -1,18 +1,18 @@
-use std::{
-    io::{self, Write},
-    process::Command,
-};
+use std::io::Write;
 
 use image::DynamicImage;
+use multiplexer::Multiplexer;
 use term_misc::{EnvIdentifiers, ensure_space};
 
 use crate::{error::RasterError, term_misc::Wininfo};
 
 pub mod ascii_encoder;
+pub mod color;
 pub mod error;
 pub mod image_extended;
 pub mod iterm_encoder;
 pub mod kitty_encoder;
+pub mod multiplexer;
 pub mod sixel_encoder;
 pub mod term_misc;
 
@@ -58,11 +58,10 @@ impl Encoder for RasterEncoder {
         offset: Option<u16>,
         print_at: Option<(u16, u16)>,
     ) -> Result<(), RasterError> {
-        let is_tmux = wininfo.is_tmux;
         let self_handle = match self {
             RasterEncoder::Iterm | RasterEncoder::Sixel => true,
             RasterEncoder::Kitty | RasterEncoder::Ascii => false,
-        } && is_tmux;
+        } && wininfo.multiplexer.is_passthrough();
         let mut img_cells = 0;
         if self_handle {
             img_cells = wininfo.dim_to_cells(
@@ -81,7 +80,9 @@ impl Encoder for RasterEncoder {
             RasterEncoder::Sixel => {
                 sixel_encoder::encode_image(img, out, offset, print_at, wininfo)
             }
-            RasterEncoder::Ascii => ascii_encoder::encode_image(img, out, offset, print_at),
+            RasterEncoder::Ascii => {
+                ascii_encoder::encode_image(img, out, offset, print_at, wininfo)
+            }
         }?;
         if self_handle {
             write!(out, "\x1B[{img_cells}B")?;
@@ -110,8 +111,8 @@ impl Encoder for RasterEncoder {
                 }
                 kitty_encoder::encode_frames(frames, out, wininfo, offset, print_at)
             }
-            // iterm gif rendering might be abit smarter, just requires to convert the frames into
-            // gif, which takes time, time that now frames are rendered..
+            // for animated images prefer iterm_encoder::encode_animation, the terminal plays
+            // those itself. re-encoding video frames into a gif would take longer than drawing them.
             RasterEncoder::Iterm => {
                 iterm_encoder::encode_frames(frames, out, wininfo, offset, print_at)
             }
@@ -137,45 +138,46 @@ pub enum RasterEncoder {
 }
 impl RasterEncoder {
     /// Picks the best protocol for the current terminal. Falls back to Ascii.
+    ///
+    /// protocols the multiplexer can't pass through are skipped.
     pub fn auto_detect(env: &EnvIdentifiers) -> Self {
-        if kitty_encoder::is_kitty_capable(env) {
+        let mux = Multiplexer::detect(env);
+        if kitty_encoder::is_kitty_capable(env) && mux.supports(&Self::Kitty) {
             return Self::Kitty;
         }
-        if iterm_encoder::is_iterm_capable(env) {
+        if iterm_encoder::is_iterm_capable(env) && mux.supports(&Self::Iterm) {
             return Self::Iterm;
         }
-        if sixel_encoder::is_sixel_capable(env) {
+        if sixel_encoder::is_sixel_capable(env) && mux.supports(&Self::Sixel) {
             return Self::Sixel;
         }
 
         Self::Ascii
     }
-}
-
-/// Toggles tmux's `allow-passthrough` setting so graphics escapes reach the outer terminal.
-pub fn set_tmux_passthrough(enabled: bool) {
-    let status = if enabled { "on" } else { "off" };
-    let _ = Command::new("tmux")
-        .args(["set", "-g", "allow-passthrough", status])
-        .status();
-}
-
-fn get_tmux_terminal_name() -> Result<(String, String), io::Error> {
-    let output = Command::new("tmux")
-        .args([
-            "display-message",
-            "-p",
-            "#{client_termtype}|||#{client_termname}",
-        ])
-        .output()?;
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let parts: Vec<&str> = output_str.trim().split("|||").collect();
-
-    if parts.len() == 2 {
-        Ok((parts[0].to_string(), parts[1].to_string()))
-    } else {
-        Err(io::Error::other("Failed to parse tmux output"))
+    /// Returns `self` when the multiplexer can show it, otherwise what [`Self::auto_detect`] picks.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rasteroid::{RasterEncoder, term_misc::EnvIdentifiers};
+    ///
+    /// let env = EnvIdentifiers {
+    ///     data: HashMap::from([("ZELLIJ".to_string(), "0".to_string())]),
+    /// };
+    /// assert_eq!(RasterEncoder::Kitty.or_supported(&env), RasterEncoder::Ascii);
+    /// assert_eq!(RasterEncoder::Sixel.or_supported(&env), RasterEncoder::Sixel);
+    ///
+    /// let env = EnvIdentifiers {
+    ///     data: HashMap::from([("TMUX".to_string(), "/tmp/tmux-1000/default".to_string())]),
+    /// };
+    /// assert_eq!(RasterEncoder::Kitty.or_supported(&env), RasterEncoder::Kitty);
+    /// ```
+    pub fn or_supported(self, env: &EnvIdentifiers) -> Self {
+        if Multiplexer::detect(env).supports(&self) {
+            self
+        } else {
+            Self::auto_detect(env)
+        }
     }
 }
 
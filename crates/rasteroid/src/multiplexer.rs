@@ -0,0 +1,159 @@
+use std::{borrow::Cow, fmt, io, process::Command};
+
+use crate::{RasterEncoder, term_misc::EnvIdentifiers};
+
+/// screen keeps DCS strings in a fixed 768 byte buffer, anything longer is dropped.
+/// we leave a small margin for safety.
+const SCREEN_DCS_CHUNK: usize = 760;
+
+/// Terminal multiplexer sitting between us and the real terminal.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Multiplexer {
+    #[default]
+    None,
+    Tmux,
+    Screen,
+    Zellij,
+}
+
+impl Multiplexer {
+    /// Detects the multiplexer from the env (`TMUX`, `ZELLIJ`, `STY`).
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rasteroid::{multiplexer::Multiplexer, term_misc::EnvIdentifiers};
+    ///
+    /// let env = EnvIdentifiers {
+    ///     data: HashMap::from([("STY".to_string(), "1234.pts-0.host".to_string())]),
+    /// };
+    /// assert_eq!(Multiplexer::detect(&env), Multiplexer::Screen);
+    ///
+    /// let env = EnvIdentifiers {
+    ///     data: HashMap::from([("ZELLIJ".to_string(), "0".to_string())]),
+    /// };
+    /// assert_eq!(Multiplexer::detect(&env), Multiplexer::Zellij);
+    /// ```
+    pub fn detect(env: &EnvIdentifiers) -> Self {
+        // tmux first, it also sets TERM=screen* and may run inside screen
+        if env.is_tmux() {
+            Multiplexer::Tmux
+        } else if env.has_key("ZELLIJ") {
+            Multiplexer::Zellij
+        } else if env.has_key("STY") {
+            Multiplexer::Screen
+        } else {
+            Multiplexer::None
+        }
+    }
+
+    /// Returns `true` if graphics escapes must be wrapped to reach the outer terminal.
+    /// in that case the multiplexer doesn't track the image, so the cursor has to be handled by us.
+    pub fn is_passthrough(&self) -> bool {
+        matches!(self, Multiplexer::Tmux | Multiplexer::Screen)
+    }
+
+    /// Returns `true` if images from `encoder` can be shown through this multiplexer.
+    ///
+    /// zellij has no passthrough, but renders sixel itself.
+    pub fn supports(&self, encoder: &RasterEncoder) -> bool {
+        match self {
+            Multiplexer::None | Multiplexer::Tmux | Multiplexer::Screen => true,
+            Multiplexer::Zellij => matches!(encoder, RasterEncoder::Sixel | RasterEncoder::Ascii),
+        }
+    }
+
+    /// Wraps a complete escape sequence so it passes through the multiplexer untouched.
+    ///
+    /// ```
+    /// use rasteroid::multiplexer::Multiplexer;
+    ///
+    /// let seq = "\x1b_Ga=T;AAAA\x1b\\";
+    /// assert_eq!(Multiplexer::None.wrap(seq), seq);
+    /// assert_eq!(
+    ///     Multiplexer::Tmux.wrap(seq),
+    ///     "\x1bPtmux;\x1b\x1b_Ga=T;AAAA\x1b\x1b\\\x1b\\"
+    /// );
+    /// assert_eq!(Multiplexer::Screen.wrap(seq), "\x1bP\x1b_Ga=T;AAAA\x1b\x1b\\\x1bP\\\x1b\\");
+    /// ```
+    pub fn wrap<'a>(&self, seq: &'a str) -> Cow<'a, str> {
+        match self {
+            Multiplexer::Tmux => Cow::Owned(format!(
+                "\x1bPtmux;{}\x1b\\",
+                seq.replace('\x1b', "\x1b\x1b")
+            )),
+            Multiplexer::Screen => Cow::Owned(wrap_screen(seq)),
+            Multiplexer::None | Multiplexer::Zellij => Cow::Borrowed(seq),
+        }
+    }
+
+    /// Toggles the multiplexer passthrough setting when it has one (tmux's `allow-passthrough`).
+    pub fn set_passthrough(&self, enabled: bool) {
+        if *self == Multiplexer::Tmux {
+            let status = if enabled { "on" } else { "off" };
+            let _ = Command::new("tmux")
+                .args(["set", "-g", "allow-passthrough", status])
+                .status();
+        }
+    }
+}
+
+impl fmt::Display for Multiplexer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Multiplexer::None => "None",
+            Multiplexer::Tmux => "Tmux",
+            Multiplexer::Screen => "Screen",
+            Multiplexer::Zellij => "Zellij",
+        };
+        f.pad(name)
+    }
+}
+
+/// screen passes DCS content as is, but only up to its buffer size, so the sequence is split
+/// into multiple DCS strings. a chunk must never contain an `ESC \`, that would end it early,
+/// so we split right after the ESC instead.
+fn wrap_screen(seq: &str) -> String {
+    let bytes = seq.as_bytes();
+    let mut result = String::with_capacity(seq.len() + (seq.len() / SCREEN_DCS_CHUNK + 1) * 4);
+    let mut start = 0;
+
+    while start < bytes.len() {
+        let mut end = start;
+        while end < bytes.len() && end - start < SCREEN_DCS_CHUNK {
+            end += 1;
+            if bytes[end - 1] == 0x1b && bytes.get(end) == Some(&b'\\') {
+                break;
+            }
+        }
+        // payloads are ascii, but make sure we never cut a char
+        while !seq.is_char_boundary(end) {
+            end += 1;
+        }
+
+        result.push_str("\x1bP");
+        result.push_str(&seq[start..end]);
+        result.push_str("\x1b\\");
+        start = end;
+    }
+
+    result
+}
+
+pub(crate) fn get_tmux_terminal_name() -> Result<(String, String), io::Error> {
+    let output = Command::new("tmux")
+        .args([
+            "display-message",
+            "-p",
+            "#{client_termtype}|||#{client_termname}",
+        ])
+        .output()?;
+
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    let parts: Vec<&str> = output_str.trim().split("|||").collect();
+
+    if parts.len() == 2 {
+        Ok((parts[0].to_string(), parts[1].to_string()))
+    } else {
+        Err(io::Error::other("Failed to parse tmux output"))
+    }
+}
@@ -24,7 +24,15 @@ pub fn encode_image(
     out.write_all(print_at_string.as_ref())?;
     out.write_all(center.as_ref())?;
 
-    encode_sixel(out, &rgb_img, wininfo.is_tmux)?;
+    if wininfo.multiplexer.is_passthrough() {
+        // the whole sequence has to be wrapped at once, sixel data is plain ascii
+        let mut buf = Vec::new();
+        encode_sixel(&mut buf, &rgb_img)?;
+        let seq = String::from_utf8_lossy(&buf);
+        out.write_all(wininfo.multiplexer.wrap(&seq).as_bytes())?;
+    } else {
+        encode_sixel(out, &rgb_img)?;
+    }
 
     Ok(())
 }
@@ -37,7 +45,6 @@ pub fn is_sixel_capable(env: &EnvIdentifiers) -> bool {
 fn encode_sixel(
     out: &mut impl Write,
     img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
-    is_tmux: bool,
 ) -> Result<(), RasterError> {
     let width = img.width() as usize;
     let height = img.height() as usize;
@@ -46,18 +53,7 @@ fn encode_sixel(
         return Err(RasterError::EmptyImage);
     }
 
-    let prefix = if is_tmux {
-        "\x1bPtmux;\x1b\x1b"
-    } else {
-        "\x1b"
-    };
-    let suffix = if is_tmux {
-        "\x1b\x1b\\\x1b\\"
-    } else {
-        "\x1b\\"
-    };
-
-    write!(out, "{prefix}P0;1q\"1;1;{};{}", width, height)?;
+    write!(out, "\x1bP0;1q\"1;1;{};{}", width, height)?;
 
     let pixels: Vec<u8> = img
         .pixels()
@@ -139,7 +135,7 @@ fn encode_sixel(
         }
     }
 
-    out.write_all(suffix.as_bytes())?;
+    out.write_all(b"\x1b\\")?;
 
     Ok(())
 }
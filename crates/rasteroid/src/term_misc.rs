@@ -10,7 +10,12 @@ use crossterm::terminal::{size, window_size};
 use signal_hook::consts::signal::*;
 use signal_hook::flag;
 
-use crate::{error::RasterError, get_tmux_terminal_name};
+use crate::{
+    ascii_encoder::TextMode,
+    color::ColorDepth,
+    error::RasterError,
+    multiplexer::{Multiplexer, get_tmux_terminal_name},
+};
 
 /// Terminal window dimensions in both cells and pixels.
 ///
@@ -21,8 +26,12 @@ pub struct Wininfo {
     pub sc_height: u16,
     pub spx_width: u16,
     pub spx_height: u16,
-    pub is_tmux: bool,
+    pub multiplexer: Multiplexer,
     pub needs_inline: bool,
+    /// Cell renderer used by the ascii encoder.
+    pub text_mode: TextMode,
+    /// Colours the terminal can show, ansi output is downsampled to it.
+    pub color_depth: ColorDepth,
 }
 
 /// Encodes raw image bytes as base64.
@@ -174,8 +183,10 @@ impl Wininfo {
             sc_width: (sc_width as f32 * scalex) as u16,
             spx_height: (spx_height as f32 * scaley) as u16,
             spx_width: (spx_width as f32 * scalex) as u16,
-            is_tmux: env.is_tmux(),
+            multiplexer: Multiplexer::detect(env),
             needs_inline: false,
+            text_mode: TextMode::default(),
+            color_depth: ColorDepth::detect(env),
         })
     }
 }
@@ -363,9 +374,9 @@ fn get_size_windows() -> Option<(u16, u16)> {
     Some((width, height))
 }
 
-/// Snapshot of terminal environment variables (TERM, TERM_PROGRAM, TMUX, etc).
+/// Snapshot of terminal environment variables (TERM, TERM_PROGRAM, TMUX, ZELLIJ, STY, COLORTERM, etc).
 ///
-/// Used to detect which image protocol the terminal supports.
+/// Used to detect which image protocol and colour depth the terminal supports.
 #[derive(Clone)]
 pub struct EnvIdentifiers {
     pub data: HashMap<String, String>,
@@ -382,6 +393,9 @@ impl Default for EnvIdentifiers {
             "KONSOLE_VERSION",
             "WT_PROFILE_ID",
             "TMUX",
+            "ZELLIJ",
+            "STY",
+            "COLORTERM",
         ];
         let mut result = HashMap::new();
 